@@ -166,6 +166,15 @@ impl UnaryOp {
     }
 }
 
+/// Only numbers can be cast, anything else has to be converted explicitly.
+pub fn parse_cast_type(s: &str, span: Span) -> Result<IntermediateType, CompileError> {
+    match s {
+        "Int" => Ok(IntermediateType::Int),
+        "Float" => Ok(IntermediateType::Float),
+        _ => Err(CompileError::type_mismatch("Int or Float", s, span)),
+    }
+}
+
 pub fn parse_un_op(s: &str, span: Span) -> Result<UnaryOp, CompileError> {
     match s {
         "-" => Ok(UnaryOp::Neg),
@@ -261,6 +270,17 @@ impl Expr {
         }
     }
 
+    pub fn cast(expr: Expr, target: IntermediateType, span: Span, statement_id: usize) -> Self {
+        Self {
+            kind: ExprKind::Cast {
+                expr: Box::new(expr),
+                target,
+            },
+            span,
+            statement_id,
+        }
+    }
+
     pub fn invalid(span: Span, statement_id: usize) -> Self {
         Self {
             kind: ExprKind::Invalid,
@@ -298,6 +318,7 @@ impl Expr {
             ExprKind::BinaryOp { left, op, right } => {
                 format!("({} {} {})", left.pretty(), op.pretty(), right.pretty())
             }
+            ExprKind::Cast { expr, target } => format!("({} as {})", expr.pretty(), target),
             ExprKind::Invalid => "<error>".to_string(),
             ExprKind::Number(n) => n.clone(),
             ExprKind::UInt(u) => u.to_string(),
@@ -325,6 +346,10 @@ pub enum ExprKind {
         op: BinaryOp,
         right: Box<Expr>,
     },
+    Cast {
+        expr: Box<Expr>,
+        target: IntermediateType,
+    },
     Invalid,
 }
 
@@ -342,6 +367,7 @@ impl ExprKind {
                 false
             }
             ExprKind::UnaryOp { op: _, expr } => expr.kind.is_const(),
+            ExprKind::Cast { expr, target: _ } => expr.kind.is_const(),
             ExprKind::BinaryOp { left, op: _, right } => {
                 left.kind.is_const() && right.kind.is_const()
             }
@@ -704,6 +730,18 @@ impl std::fmt::Display for ProcessedType {
 }
 
 impl IntermediateType {
+    /// Types that `as` can convert between.
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            IntermediateType::Number
+                | IntermediateType::Int
+                | IntermediateType::UInt
+                | IntermediateType::Float
+                | IntermediateType::Byte
+        )
+    }
+
     // TODO
     pub fn try_to_processed(&self, span: Span) -> Result<ProcessedType, CompileError> {
         match self {
@@ -846,6 +884,7 @@ impl IntermediateType {
             ExprKind::String(_) => Ok(Self::String),
             ExprKind::Variable(_) => Ok(Self::Indeterminate),
             ExprKind::UnaryOp { op: _, expr } => Self::type_from_expr(expr),
+            ExprKind::Cast { expr: _, target } => Ok(target.clone()),
             ExprKind::BinaryOp { left, op, right } => {
                 // match op {
                 //     BinaryOp::Add => match (left.typ(), right.typ()) {
@@ -11,6 +11,21 @@ use crate::intermediate_pass1::{TypedIRExpr, TypedIRInstruction, TypedIRStmt};
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
+macro_rules! convert_from {
+    ($from:ident, $to:expr, $target:ident, $source:ident, [$($other:ident),*]) => {
+        paste! {
+            match $to {
+                BCType::$from => UnprocessedInstruction::[<Move $from>](($target.into(), $source.into())),
+                $(
+                    BCType::$other => {
+                        UnprocessedInstruction::[<Convert $from To $other>](($target.into(), $source.into()))
+                    }
+                )*
+            }
+        }
+    };
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum BCType {
     U8,
@@ -52,6 +67,23 @@ impl BCType {
             ProcessedType::Enum { variants } => todo!(),
         }
     }
+
+    /// The instruction that converts `source`, holding a `self`, into a `to` in `target`.
+    pub fn conversion(self, to: BCType, target: u8, source: u8) -> UnprocessedInstruction {
+        use BCType::*;
+        match self {
+            U8 => convert_from!(U8, to, target, source, [U16, U32, U64, I8, I16, I32, I64, F32, F64]),
+            U16 => convert_from!(U16, to, target, source, [U8, U32, U64, I8, I16, I32, I64, F32, F64]),
+            U32 => convert_from!(U32, to, target, source, [U8, U16, U64, I8, I16, I32, I64, F32, F64]),
+            U64 => convert_from!(U64, to, target, source, [U8, U16, U32, I8, I16, I32, I64, F32, F64]),
+            I8 => convert_from!(I8, to, target, source, [U8, U16, U32, U64, I16, I32, I64, F32, F64]),
+            I16 => convert_from!(I16, to, target, source, [U8, U16, U32, U64, I8, I32, I64, F32, F64]),
+            I32 => convert_from!(I32, to, target, source, [U8, U16, U32, U64, I8, I16, I64, F32, F64]),
+            I64 => convert_from!(I64, to, target, source, [U8, U16, U32, U64, I8, I16, I32, F32, F64]),
+            F32 => convert_from!(F32, to, target, source, [U8, U16, U32, U64, I8, I16, I32, I64, F64]),
+            F64 => convert_from!(F64, to, target, source, [U8, U16, U32, U64, I8, I16, I32, I64, F32]),
+        }
+    }
 }
 
 macro_rules! match_ops_for_type {
@@ -443,6 +475,13 @@ fn lower_expr_to_bytecode_stage_one(
                 }
             }
         }
+        TypedIRExpr::Convert { expr, to } => {
+            let (reg, instrs) = get_var_register(expr.id, reg_alloc)?;
+            instructions.extend(instrs);
+
+            let from = BCType::from_ir_type(expr.typ.clone());
+            instructions.push(from.conversion(BCType::from_ir_type(to.clone()), target_reg, reg));
+        }
         TypedIRExpr::Unary { op, expr } => {
             let (reg, instrs) = get_var_register(expr.id, reg_alloc)?;
             instructions.extend(instrs);
@@ -631,3 +670,48 @@ pub fn emit_string_concat(
 }
 
 pub fn builtin_prin(section_idx_reg: RegisterType) {}
+
+#[cfg(test)]
+mod tests {
+    use pest::Parser;
+    use solid_snake_vm::opcodes::UnprocessedInstruction;
+
+    use super::lower_ir_to_bytecode_stage_one;
+    use crate::error_reporting::CompileErrorKind;
+    use crate::intermediate_pass1::{AnalysisContext, analyze_ast};
+    use crate::parser::{Rule, SolidSnakeParser, build_ast};
+    use crate::preprocessor::preprocess_indentation;
+
+    fn analyze(source: &str) -> AnalysisContext {
+        let preprocessed = preprocess_indentation(source).unwrap();
+        let pairs = SolidSnakeParser::parse(Rule::Program, &preprocessed.transformed).unwrap();
+        let (ast, errors, _) = build_ast(pairs, 0);
+        let mut context = analyze_ast(&ast);
+        context.errors_mut().extend(errors);
+        context
+    }
+
+    #[test]
+    fn int_cast_to_float_converts() {
+        // The loop keeps `x` from being folded into a constant.
+        let context = analyze("let x = 1\nwhile x < 10:\n    x = x + 1\nlet y = x as Float\n");
+        assert!(!context.errors().is_fatal());
+
+        let (bytecode, _) =
+            lower_ir_to_bytecode_stage_one(context.typed_ir(), context.var_count()).unwrap();
+        assert!(
+            bytecode
+                .iter()
+                .any(|instr| matches!(instr, UnprocessedInstruction::ConvertI64ToF64(_)))
+        );
+    }
+
+    #[test]
+    fn string_cast_rejected() {
+        let context = analyze("let s = \"text\"\nlet y = s as Int\n");
+        assert!(context.errors().err_iter().any(|error| matches!(
+            &error.kind,
+            CompileErrorKind::TypeMismatch { actual, .. } if actual == "String"
+        )));
+    }
+}
@@ -325,6 +325,10 @@ pub enum TypedIRExpr {
         op: UnaryOp,
         expr: TypedIRVar,
     },
+    Convert {
+        expr: TypedIRVar,
+        to: ProcessedType,
+    },
 }
 
 impl TypedIRExpr {
@@ -333,6 +337,7 @@ impl TypedIRExpr {
             TypedIRExpr::Var(irvar) => vec![irvar.id()],
             TypedIRExpr::Binary { op: _, left, right } => vec![left.id(), right.id()],
             TypedIRExpr::Unary { op: _, expr } => vec![expr.id()],
+            TypedIRExpr::Convert { expr, to: _ } => vec![expr.id()],
             _ => Vec::new(),
         }
     }
@@ -349,6 +354,7 @@ impl std::fmt::Display for TypedIRExpr {
             TypedIRExpr::String(v) => write!(f, "{}", v),
             TypedIRExpr::Binary { op, left, right } => write!(f, "{} {} {}", left, op, right),
             TypedIRExpr::Unary { op, expr } => write!(f, "{:?} {}", op, expr),
+            TypedIRExpr::Convert { expr, to } => write!(f, "{} as {}", expr, to),
         }
     }
 }
@@ -371,6 +377,10 @@ pub enum IRExpr {
         op: UnaryOp,
         expr: IRVar,
     },
+    Convert {
+        expr: IRVar,
+        to: IntermediateType,
+    },
 }
 
 impl std::fmt::Display for IRExpr {
@@ -385,6 +395,7 @@ impl std::fmt::Display for IRExpr {
             IRExpr::String(v) => write!(f, "{}", v),
             IRExpr::Binary { op, left, right } => write!(f, "{} {} {}", left, op, right),
             IRExpr::Unary { op, expr } => write!(f, "{:?} {}", op, expr),
+            IRExpr::Convert { expr, to } => write!(f, "{} as {}", expr, to),
         }
     }
 }
@@ -432,6 +443,10 @@ impl IRExpr {
                 op,
                 expr: expr.try_to_typed_ir_var(ctx)?,
             }),
+            IRExpr::Convert { expr, to } => Ok(TypedIRExpr::Convert {
+                expr: expr.try_to_typed_ir_var(ctx)?,
+                to: to.try_to_processed(span)?,
+            }),
         }
     }
 
@@ -459,6 +474,7 @@ impl IRExpr {
                 left.typ().try_apply_type_hint(right.typ(), span)
             }
             IRExpr::Unary { op: _, expr } => Ok(expr.typ().clone()),
+            IRExpr::Convert { expr: _, to } => Ok(to.clone()),
         }
     }
 
@@ -467,6 +483,7 @@ impl IRExpr {
             IRExpr::Var(irvar) => vec![irvar.id()],
             IRExpr::Binary { op: _, left, right } => vec![left.id(), right.id()],
             IRExpr::Unary { op: _, expr } => vec![expr.id()],
+            IRExpr::Convert { expr, to: _ } => vec![expr.id()],
             _ => Vec::new(),
         }
     }
@@ -574,6 +591,27 @@ pub fn eval_unary_op(
     }
 }
 
+/// Folds a cast. Values the VM would flag as not fitting are left for runtime so the flag still gets set.
+pub fn eval_convert(val: &TypedConstVal, to: &IntermediateType) -> Option<TypedConstVal> {
+    use TypedConstVal::*;
+    match (val, to) {
+        (Int(_), IntermediateType::Int)
+        | (UInt(_), IntermediateType::UInt)
+        | (Float(_), IntermediateType::Float) => Some(val.clone()),
+        (Int(v), IntermediateType::Float) => Some(Float(*v as f64)),
+        (UInt(v), IntermediateType::Float) => Some(Float(*v as f64)),
+        (Int(v), IntermediateType::UInt) => u64::try_from(*v).ok().map(UInt),
+        (UInt(v), IntermediateType::Int) => i64::try_from(*v).ok().map(Int),
+        (Float(v), IntermediateType::Int) if (i64::MIN as f64..i64::MAX as f64).contains(&v.trunc()) => {
+            Some(Int(*v as i64))
+        }
+        (Float(v), IntermediateType::UInt) if (0.0..u64::MAX as f64).contains(&v.trunc()) => {
+            Some(UInt(*v as u64))
+        }
+        _ => None,
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum TypedConstVal {
     Bool(bool),
@@ -636,6 +674,13 @@ impl TypedConstVal {
                 };
                 eval_unary_op(*op, &val)
             }
+            IRExpr::Convert { expr, to } => {
+                let val = match ctx.var_const_map().get(expr.id()) {
+                    Some(v) if ctx.can_fold_var(expr.id(), statement_id) => v,
+                    _ => return Ok(None),
+                };
+                Ok(eval_convert(&val, to))
+            }
         }
     }
 }
@@ -1324,6 +1369,15 @@ fn resolve_types(ctx: &mut AnalysisContext) {
                     sync_irvar_type(right, ctx);
                 } else if let IRExpr::Unary { expr, .. } = value {
                     sync_irvar_type(expr, ctx);
+                } else if let IRExpr::Convert { expr, .. } = value {
+                    sync_irvar_type(expr, ctx);
+                    if !expr.typ().is_numeric() {
+                        ctx.errors.push_error(CompileError::type_mismatch(
+                            "Int or Float",
+                            format!("{:?}", expr.typ()),
+                            instr.span,
+                        ));
+                    }
                 }
 
                 if matches!(target.typ(), IntermediateType::Indeterminate) {
@@ -1701,6 +1755,33 @@ pub fn flatten_expr_to_var(
             }
             Ok((target, left_instrs))
         }
+        ExprKind::Cast { expr: inner, target: to } => {
+            let (temp_inner, _temp_inner_id) = fresh_temp_var(inner, ctx, None);
+            ctx.set_scope_id(temp_inner.id(), ctx.current_scope);
+            let (inner_var, mut inner_instrs) =
+                flatten_expr_to_var(inner, ctx, temp_inner, None, statement_id)?;
+
+            let assign_instr = IRInstruction {
+                span: expr.span,
+                kind: IRStmt::Assign {
+                    target: target.clone(),
+                    value: IRExpr::Convert {
+                        expr: inner_var.clone(),
+                        to: to.clone(),
+                    },
+                },
+                statement_id,
+                scope_id: ctx.current_scope,
+            };
+
+            inner_instrs.push(assign_instr);
+            if inner_var.is_temp() {
+                let drop_temp =
+                    IRInstruction::drop(inner_var, expr.span, statement_id, ctx.current_scope);
+                inner_instrs.push(drop_temp);
+            }
+            Ok((target, inner_instrs))
+        }
         ExprKind::Invalid => Ok((target, vec![])),
     }
 }
@@ -2153,6 +2234,12 @@ pub fn create_scopes_map_expr(expr: &Expr, ctx: &mut AnalysisContext, statement_
             create_scopes_map_expr(left, ctx, statement_id);
             create_scopes_map_expr(right, ctx, statement_id);
         }
+        ExprKind::Cast {
+            expr: inner_exp,
+            target: _,
+        } => {
+            create_scopes_map_expr(inner_exp, ctx, statement_id);
+        }
         ExprKind::Invalid => {}
     }
 }
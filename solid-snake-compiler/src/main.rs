@@ -19,6 +19,7 @@ use preprocessor::{PreprocessResult, preprocess_indentation};
 
 use pest::Parser;
 use solid_snake_vm::{
+    bytecode_module::BytecodeModule,
     executor::{ext::VmExecutorExt, interpreted::implimentation::VmInterpretedExecutor},
     opcodes::{DecodedInstruction, UnprocessedInstruction},
 };
@@ -27,7 +28,19 @@ use solid_snake_vm::{
 fn main() {
     env_logger::init();
 
-    let file_name = "./test_data/test.ss";
+    // Usage: solid-snake-compiler [source.ss] [-o module.ssb]
+    let mut file_name = "./test_data/test.ss".to_string();
+    let mut output_path: Option<String> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-o" {
+            output_path = args.next();
+        } else {
+            file_name = arg;
+        }
+    }
+    let file_name = file_name.as_str();
+
     let mut source_file = std::fs::File::open(file_name).unwrap();
     let mut source = String::new();
     source_file.read_to_string(&mut source).unwrap();
@@ -92,6 +105,16 @@ fn main() {
                         .flat_map(DecodedInstruction::encode)
                         .collect::<Vec<u8>>();
 
+                    if let Some(output_path) = output_path {
+                        let module = BytecodeModule::new(final_bc, constants);
+                        if let Err(error) = module.write_to_file(&output_path) {
+                            eprintln!("Could not write {}: {}", output_path, error);
+                            exit(-1);
+                        }
+                        println!("Wrote {}", output_path);
+                        return;
+                    }
+
                     let mut vm = VmInterpretedExecutor::new(None);
                     dbg!(&constants);
                     vm.set_constants(constants);
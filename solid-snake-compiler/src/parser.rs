@@ -1,7 +1,10 @@
 use pest_derive::Parser;
 
 use crate::{
-    ast::{ASTNode, ASTNodeContainer, Expr, IfGroup, Span, VarName, parse_bin_op, parse_un_op},
+    ast::{
+        ASTNode, ASTNodeContainer, Expr, IfGroup, Span, VarName, parse_bin_op, parse_cast_type,
+        parse_un_op,
+    },
     error_reporting::{CompileError, CompileErrorList},
 };
 
@@ -400,6 +403,19 @@ fn parse_expr(
             }
             Ok(expr)
         }
+        Rule::CastExpr => {
+            let mut inner = pair.into_inner();
+            let mut expr = parse_expr(handle_missing!(inner.next(), span)?, statement_id)?;
+
+            while let Some(_cast_op) = inner.next() {
+                let type_pair = handle_missing!(inner.next(), span)?;
+                let target = parse_cast_type(type_pair.as_str(), Span::from_pair(&type_pair))
+                    .map_err(|error| CompileErrorList::new().with_error(error))?;
+                expr = Expr::cast(expr, target, span, statement_id);
+            }
+
+            Ok(expr)
+        }
         Rule::Primary => parse_expr(
             handle_missing!(pair.into_inner().next(), span)?,
             statement_id,
@@ -18,7 +18,15 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
         panic!("DecodedInstructionEnum can only be derived for enums");
     };
 
-    let jumpy_instructions = ["Jump", "JumpIf", "JumpIfFalse", "CallFunction"];
+    let jumpy_instructions = [
+        "Jump",
+        "JumpIf",
+        "JumpIfFalse",
+        "JumpIfErrorIndirect",
+        "JumpIfErrorImmediate",
+        "CallFunction",
+        "Spawn",
+    ];
 
     let variants_no_jump = if let syn::Data::Enum(data_enum) = &input.data {
         &data_enum
@@ -175,6 +183,14 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
         }
     });
 
+    let opcode_match_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+
+        quote! {
+            DecodedInstruction::#ident(_) => OpCode::#ident,
+        }
+    });
+
     let get_doc_arms = variants.iter().map(|variant| {
         let ident = &variant.ident;
         let instr_ident = syn::Ident::new(&format!("{}Instruction", ident), ident.span());
@@ -233,7 +249,10 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
             Jump((String,)),
             JumpIf((String, RegisterType)),
             JumpIfFalse((String, RegisterType)),
+            JumpIfErrorIndirect((String, RegisterType)),
+            JumpIfErrorImmediate((String, i64)),
             CallFunction((String)),
+            Spawn((String, RegisterType, RegisterType)),
             #(#generated_variants_no_jump,)*
         }
 
@@ -258,9 +277,18 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
                         UnprocessedInstruction::JumpIfFalse((String, RegisterType)) => {
                             byte_count += JumpIfFalseInstruction::instr_size() as u64;
                         },
+                        UnprocessedInstruction::JumpIfErrorIndirect(_) => {
+                            byte_count += JumpIfErrorIndirectInstruction::instr_size() as u64;
+                        },
+                        UnprocessedInstruction::JumpIfErrorImmediate(_) => {
+                            byte_count += JumpIfErrorImmediateInstruction::instr_size() as u64;
+                        },
                         UnprocessedInstruction::CallFunction((String)) => {
                             byte_count += CallFunctionInstruction::instr_size() as u64;
                         },
+                        UnprocessedInstruction::Spawn(_) => {
+                            byte_count += SpawnInstruction::instr_size() as u64;
+                        },
                     }
                 }
                 for instr in instructions {
@@ -280,10 +308,22 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
                             let jump_offset = label_to_byte_offset[label];
                             final_instructions.push(DecodedInstruction::JumpIfFalse((jump_offset, *reg)))
                         },
+                        UnprocessedInstruction::JumpIfErrorIndirect((label, code)) => {
+                            let jump_offset = label_to_byte_offset[label];
+                            final_instructions.push(DecodedInstruction::JumpIfErrorIndirect((jump_offset, *code)))
+                        },
+                        UnprocessedInstruction::JumpIfErrorImmediate((label, code)) => {
+                            let jump_offset = label_to_byte_offset[label];
+                            final_instructions.push(DecodedInstruction::JumpIfErrorImmediate((jump_offset, *code)))
+                        },
                         UnprocessedInstruction::CallFunction((label)) => {
                             let jump_offset = label_to_byte_offset[label];
                             final_instructions.push(DecodedInstruction::CallFunction((jump_offset,)))
                         },
+                        UnprocessedInstruction::Spawn((label, arg, handle)) => {
+                            let jump_offset = label_to_byte_offset[label];
+                            final_instructions.push(DecodedInstruction::Spawn((jump_offset, *arg, *handle)))
+                        },
                     }
                 }
 
@@ -298,6 +338,12 @@ pub fn derive_decoded_instruction_enum(input: TokenStream) -> TokenStream {
                 }
             }
 
+            pub fn opcode(&self) -> OpCode {
+                match self {
+                    #(#opcode_match_arms)*
+                }
+            }
+
             pub fn encode(&self) -> Vec<u8> {
                 match self {
                     #(#encode_match_arms)*
@@ -0,0 +1,563 @@
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+use solid_snake_vm::{
+    bytecode_module::BytecodeModule,
+    bytecode_parser::{Assembled, assemble_file, report_asm_errors},
+    disassembler::disassemble_instructions,
+    executor::{
+        ext::VmExecutionError,
+        interpreted::{
+            debugger::{BreakCondition, CompareOp, StopReason, WatchKind, Watchpoint},
+            implimentation::{
+                ExecutableInstructionFn, MAX_REGISTERS, VmHeapExt, VmInterpretedExecutor,
+                VmMemorySectionExt,
+            },
+        },
+    },
+    opcodes::DecodedInstruction,
+};
+
+const HELP: &str = "\
+Commands:
+  break <label|offset> [if R<n> <op> <value>]   stop before the instruction, op is one of == != < <= > >=
+  delete <label|offset>                          remove a breakpoint
+  watch <section> <start> <end> [read|write]     stop after bytes [start, end) of a section are accessed
+  step | s                                       run one instruction
+  next | n                                       run one instruction, a whole call for CallFunction
+  finish                                         run until the current function returns
+  continue | c                                   run until a breakpoint, watchpoint or Halt
+  regs                                           show the non-zero registers of the selected frame
+  frame <n>                                      select frame n, 0 is the current function
+  heap                                           show heap usage and live sections
+  x/<n>b <section> <offset>                      show n bytes of a section
+  set R<n> <value>                               write a register in the selected frame
+  run | r                                        restart the program, keeping breakpoints and watchpoints
+  quit | q";
+
+type Bytecode = Vec<(DecodedInstruction, ExecutableInstructionFn)>;
+
+type DebugCommand = fn(
+    &mut VmInterpretedExecutor,
+    &[(DecodedInstruction, ExecutableInstructionFn)],
+) -> Result<StopReason, VmExecutionError>;
+
+/// One instruction of the program being debugged, with the text to show for it.
+struct Line {
+    offset: usize,
+    text: String,
+}
+
+struct Session {
+    vm: VmInterpretedExecutor,
+    bc: Bytecode,
+    module: BytecodeModule,
+    lines: Vec<Line>,
+    frame: usize,
+    exited: bool,
+    /// What the commands printed since the last `take_output`.
+    output: String,
+}
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: ssdb <program.sasm | module>");
+        std::process::exit(2);
+    };
+
+    let mut session = match Session::load(&path) {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Loaded {} ({} instructions), type `help` for commands",
+        path,
+        session.lines.len()
+    );
+    session.show_location();
+    print!("{}", session.take_output());
+
+    let stdin = io::stdin();
+    loop {
+        print!("(ssdb) ");
+        io::stdout().flush().ok();
+
+        let mut input = String::new();
+        match stdin.lock().read_line(&mut input) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let keep_going = session.execute(&input);
+        print!("{}", session.take_output());
+        if !keep_going {
+            break;
+        }
+    }
+}
+
+impl Session {
+    fn load(path: &str) -> Result<Self, String> {
+        let (module, source_lines) = if path.ends_with(".sasm") {
+            let assembled = match assemble_file(path) {
+                Ok(assembled) => assembled,
+                Err(errors) => {
+                    let source = std::fs::read_to_string(path).unwrap_or_default();
+                    report_asm_errors(&errors, path, &source);
+                    return Err(format!("Could not assemble {}", path));
+                }
+            };
+            let source_lines = source_lines(&assembled);
+            (assembled.into_module(), source_lines)
+        } else {
+            let module = BytecodeModule::read_from_file(path).map_err(|err| err.to_string())?;
+            (module, Vec::new())
+        };
+
+        Self::new(module, &source_lines)
+    }
+
+    /// A session stopped at the entry point of `module`. `source_lines` are the offsets and text
+    /// of the assembler lines the module was built from, if there is a source.
+    fn new(module: BytecodeModule, source_lines: &[(usize, String)]) -> Result<Self, String> {
+        let lines = disassemble_instructions(&module.code)
+            .map_err(|err| err.to_string())?
+            .into_iter()
+            .map(|instr| {
+                let text = match source_lines
+                    .iter()
+                    .find(|(offset, _)| *offset == instr.offset)
+                {
+                    Some((_, source)) => source.clone(),
+                    None => format!("{:?} {}", instr.opcode, instr.args.join(" ")),
+                };
+                Line {
+                    offset: instr.offset,
+                    text,
+                }
+            })
+            .collect();
+        let (vm, bc) = start(&module)?;
+
+        Ok(Self {
+            vm,
+            bc,
+            module,
+            lines,
+            frame: 0,
+            exited: false,
+            output: String::new(),
+        })
+    }
+
+    /// Runs one line of input. Returns false once the session should end.
+    fn execute(&mut self, input: &str) -> bool {
+        let words = input.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            [] => {}
+            ["quit" | "q"] => return false,
+            ["help" | "h"] => self.say(HELP),
+            words => {
+                if let Err(err) = self.command(words) {
+                    self.say(err);
+                }
+            }
+        }
+        true
+    }
+
+    fn say(&mut self, text: impl Display) {
+        self.output.push_str(&format!("{}\n", text));
+    }
+
+    fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Starts the program over from its entry point with the same breakpoints and watchpoints.
+    fn restart(&mut self) -> Result<(), String> {
+        let (mut vm, bc) = start(&self.module)?;
+        for (pc, condition) in self.vm.breakpoints().iter() {
+            match condition {
+                Some(condition) => vm.breakpoints_mut().set_conditional(pc, *condition),
+                None => vm.breakpoints_mut().set(pc),
+            }
+        }
+        for watchpoint in self.vm.watchpoints().iter() {
+            vm.watchpoints_mut().add(watchpoint.clone());
+        }
+
+        self.vm = vm;
+        self.bc = bc;
+        self.frame = 0;
+        self.exited = false;
+        Ok(())
+    }
+
+    fn command(&mut self, words: &[&str]) -> Result<(), String> {
+        match words {
+            ["break" | "b", target] => {
+                let pc = self.resolve(target)?;
+                self.vm.breakpoints_mut().set(pc);
+                self.say(format!("Breakpoint at {}", self.describe(pc)));
+            }
+            ["break" | "b", target, "if", register, op, value] => {
+                let pc = self.resolve(target)?;
+                let condition = BreakCondition {
+                    register: parse_register(register)?,
+                    op: parse_compare_op(op)?,
+                    value: parse_number(value)?,
+                };
+                self.vm.breakpoints_mut().set_conditional(pc, condition);
+                self.say(format!("Conditional breakpoint at {}", self.describe(pc)));
+            }
+            ["delete" | "d", target] => {
+                let pc = self.resolve(target)?;
+                if !self.vm.breakpoints_mut().remove(pc) {
+                    return Err(format!("No breakpoint at {}", self.describe(pc)));
+                }
+            }
+            ["watch", section, start, end, kind @ ..] => {
+                let kind = match kind {
+                    [] => WatchKind::Access,
+                    ["read"] => WatchKind::Read,
+                    ["write"] => WatchKind::Write,
+                    _ => return Err("Watch kind must be `read` or `write`".to_string()),
+                };
+                let watchpoint = Watchpoint {
+                    section: parse_number(section)? as usize,
+                    range: parse_number(start)? as usize..parse_number(end)? as usize,
+                    kind,
+                };
+                self.say(format!("Watching {:?}", watchpoint));
+                self.vm.watchpoints_mut().add(watchpoint);
+            }
+            ["step" | "s"] => self.run(VmInterpretedExecutor::debug_step)?,
+            ["next" | "n"] => self.run(VmInterpretedExecutor::debug_step_over)?,
+            ["finish"] => self.run(VmInterpretedExecutor::debug_step_out)?,
+            ["continue" | "c"] => self.run(VmInterpretedExecutor::debug_continue)?,
+            ["run" | "r"] => {
+                self.restart()?;
+                self.say("Restarted");
+                self.show_location();
+            }
+            ["regs"] => self.show_registers()?,
+            ["frame" | "f", n] => {
+                let n = parse_number(n)? as usize;
+                let frame = self.vm.frame(n).map_err(|err| err.to_string())?;
+                let text = format!(
+                    "Frame {}, returns to {}",
+                    n,
+                    self.describe(frame.return_address as usize)
+                );
+                self.say(text);
+                self.frame = n;
+                self.show_registers()?;
+            }
+            ["heap"] => self.show_heap(),
+            [examine, section, offset] if examine.starts_with("x/") => {
+                let count = examine
+                    .strip_prefix("x/")
+                    .and_then(|rest| rest.strip_suffix('b'))
+                    .ok_or("Use x/<n>b")?;
+                self.examine(
+                    parse_number(count)? as usize,
+                    parse_number(section)? as usize,
+                    parse_number(offset)? as usize,
+                )?;
+            }
+            ["set", register, value] => {
+                let register = parse_register(register)?;
+                let value = parse_number(value)?;
+                self.vm
+                    .frame_registers_mut(self.frame)
+                    .map_err(|err| err.to_string())?
+                    .raw[register] = value as u64;
+            }
+            _ => return Err(format!("Unknown command `{}`, try `help`", words.join(" "))),
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, command: DebugCommand) -> Result<(), String> {
+        if self.exited {
+            return Err("The program has exited, use `run` to restart it".to_string());
+        }
+        self.frame = 0;
+
+        match command(&mut self.vm, &self.bc) {
+            Ok(StopReason::Halted(exit_code)) => {
+                self.exited = true;
+                self.say(format!("Program halted with exit code {}", exit_code));
+                return Ok(());
+            }
+            Ok(StopReason::Breakpoint(pc)) => {
+                self.say(format!("Breakpoint at {}", self.describe(pc)))
+            }
+            Ok(StopReason::Watchpoint(hit)) => self.say(format!(
+                "Watchpoint: {:?} of section {} bytes {}..{} by {}\n  old {:02x?}\n  new {:02x?}",
+                hit.kind,
+                hit.section,
+                hit.range.start,
+                hit.range.end,
+                self.describe(hit.pc),
+                hit.old,
+                hit.new
+            )),
+            Ok(StopReason::Stepped(_)) => {}
+            Err(err) => self.say(format!("Execution failed: {}", err)),
+        }
+        self.show_location();
+
+        Ok(())
+    }
+
+    /// Turns a label or a byte offset into the index of the instruction there.
+    fn resolve(&self, target: &str) -> Result<usize, String> {
+        let offset = match self.module.symbol(target) {
+            Some(offset) => offset as usize,
+            None => parse_number(target).map_err(|_| format!("No label or offset `{}`", target))?
+                as usize,
+        };
+        self.lines
+            .iter()
+            .position(|line| line.offset == offset)
+            .ok_or_else(|| format!("Offset {:#x} is not the start of an instruction", offset))
+    }
+
+    /// `label+delta (0xOFFSET)` for an instruction index.
+    fn describe(&self, pc: usize) -> String {
+        let Some(line) = self.lines.get(pc) else {
+            return format!("pc {}", pc);
+        };
+        let label = self
+            .module
+            .symbols
+            .iter()
+            .flatten()
+            .filter(|symbol| symbol.offset as usize <= line.offset)
+            .max_by_key(|symbol| symbol.offset);
+        match label {
+            Some(symbol) if symbol.offset as usize == line.offset => {
+                format!("{} ({:#06x})", symbol.name, line.offset)
+            }
+            Some(symbol) => format!(
+                "{}+{} ({:#06x})",
+                symbol.name,
+                line.offset - symbol.offset as usize,
+                line.offset
+            ),
+            None => format!("{:#06x}", line.offset),
+        }
+    }
+
+    fn show_location(&mut self) {
+        let Ok(pc) = self.vm.get_program_counter() else {
+            return;
+        };
+        match self.lines.get(pc) {
+            Some(line) => self.say(format!("=> {}: {}", self.describe(pc), line.text)),
+            None => self.say(format!("=> pc {} is outside the program", pc)),
+        }
+    }
+
+    fn show_registers(&mut self) -> Result<(), String> {
+        let registers = self
+            .vm
+            .frame_registers(self.frame)
+            .map_err(|err| err.to_string())?;
+        let lines = registers
+            .raw
+            .iter()
+            .enumerate()
+            .filter(|(_, raw)| **raw != 0)
+            .map(|(idx, &raw)| format!("  R{:<3} {:#018x} {}", idx, raw, raw as i64))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            self.say("  All registers are zero");
+        }
+        for line in lines {
+            self.say(line);
+        }
+
+        Ok(())
+    }
+
+    fn show_heap(&mut self) {
+        let stats = self.vm.heap().stats();
+        self.say(format!(
+            "{} live sections, {} bytes (peak {}), {} allocations, {} frees",
+            stats.live_sections,
+            stats.live_bytes,
+            stats.peak_bytes,
+            stats.total_allocations,
+            stats.total_frees
+        ));
+        for leak in self.vm.heap().leak_report().0 {
+            let origin = leak
+                .alloc_pc
+                .map(|pc| format!(", allocated at {}", self.describe(pc)))
+                .unwrap_or_default();
+            self.say(format!(
+                "  section {:#x}: {} bytes{}",
+                leak.handle, leak.size, origin
+            ));
+        }
+    }
+
+    fn examine(&mut self, count: usize, section: usize, offset: usize) -> Result<(), String> {
+        let bytes = self
+            .vm
+            .heap()
+            .section(section)
+            .and_then(|section| section.bytes_n_with_offset(count, offset))
+            .map_err(|err| err.to_string())?
+            .to_vec();
+        for (row, chunk) in bytes.chunks(8).enumerate() {
+            let hex = chunk
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>();
+            self.say(format!("  {:#06x}: {}", offset + row * 8, hex.join(" ")));
+        }
+
+        Ok(())
+    }
+}
+
+/// The offset and text of every assembler line that holds code.
+fn source_lines(assembled: &Assembled) -> Vec<(usize, String)> {
+    // A line holds code when the next line starts at a later offset.
+    assembled
+        .lined_code
+        .windows(2)
+        .filter(|pair| pair[1].1 > pair[0].1)
+        .map(|pair| (pair[0].1, pair[0].0.trim().to_string()))
+        .collect()
+}
+
+/// A new executor for `module`, stopped at its entry point.
+fn start(module: &BytecodeModule) -> Result<(VmInterpretedExecutor, Bytecode), String> {
+    let mut vm = VmInterpretedExecutor::new(None);
+    let bc = vm.load_module(module).map_err(|err| err.to_string())?;
+    let entry = vm.get_instr_idx_from_bc(module.entry_point as usize);
+    vm.set_program_counter(if entry == usize::MAX { 0 } else { entry })
+        .map_err(|err| err.to_string())?;
+    Ok((vm, bc))
+}
+
+fn parse_number(text: &str) -> Result<i64, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).map(|value| value as i64),
+        None => text.parse::<i64>(),
+    };
+    parsed.map_err(|_| format!("`{}` is not a number", text))
+}
+
+fn parse_register(text: &str) -> Result<usize, String> {
+    text.strip_prefix(['R', 'r'])
+        .and_then(|idx| idx.parse::<usize>().ok())
+        .filter(|&idx| idx < MAX_REGISTERS)
+        .ok_or_else(|| format!("`{}` is not a register", text))
+}
+
+fn parse_compare_op(text: &str) -> Result<CompareOp, String> {
+    Ok(match text {
+        "==" => CompareOp::Equal,
+        "!=" => CompareOp::NotEqual,
+        "<" => CompareOp::LessThan,
+        "<=" => CompareOp::LessThanOrEqual,
+        ">" => CompareOp::GreaterThan,
+        ">=" => CompareOp::GreaterThanOrEqual,
+        _ => return Err(format!("`{}` is not a comparison", text)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use solid_snake_vm::bytecode_parser::parse_byte_code_from_txt;
+
+    use super::{Session, source_lines};
+
+    fn session() -> Session {
+        let assembled = parse_byte_code_from_txt(
+            "
+                LoadImmediateU64 R1 4
+                LoadImmediateU8 R2 9
+                Allocate R0 R1
+            fill:
+                MemSet R0 R2 R1
+                Halt 0
+            ",
+        )
+        .unwrap();
+        let lines = source_lines(&assembled);
+        Session::new(assembled.into_module(), &lines).unwrap()
+    }
+
+    fn run(session: &mut Session, input: &str) -> String {
+        assert!(session.execute(input));
+        session.take_output()
+    }
+
+    #[test]
+    fn breakpoint_step_and_examine() {
+        let mut session = session();
+
+        assert_eq!(
+            run(&mut session, "break fill"),
+            "Breakpoint at fill (0x0013)\n"
+        );
+        assert_eq!(
+            run(&mut session, "continue"),
+            "Breakpoint at fill (0x0013)\n=> fill (0x0013): MemSet R0 R2 R1\n"
+        );
+        assert_eq!(run(&mut session, "step"), "=> fill+5 (0x0018): Halt 0\n");
+        assert_eq!(run(&mut session, "x/4b 0 0"), "  0x0000: 09 09 09 09\n");
+    }
+
+    #[test]
+    fn restart_after_halt() {
+        let mut session = session();
+        run(&mut session, "break fill");
+
+        assert_eq!(
+            run(&mut session, "c"),
+            "Breakpoint at fill (0x0013)\n=> fill (0x0013): MemSet R0 R2 R1\n"
+        );
+        assert_eq!(run(&mut session, "c"), "Program halted with exit code 0\n");
+        assert_eq!(
+            run(&mut session, "step"),
+            "The program has exited, use `run` to restart it\n"
+        );
+        assert_eq!(
+            run(&mut session, "run"),
+            "Restarted\n=> 0x0000: LoadImmediateU64 R1 4\n"
+        );
+        assert_eq!(
+            run(&mut session, "c"),
+            "Breakpoint at fill (0x0013)\n=> fill (0x0013): MemSet R0 R2 R1\n"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let mut session = session();
+
+        assert_eq!(run(&mut session, "x/4 0 0"), "Use x/<n>b\n");
+        assert_eq!(
+            run(&mut session, "break nowhere"),
+            "No label or offset `nowhere`\n"
+        );
+        assert_eq!(
+            run(&mut session, "set R200 1"),
+            "`R200` is not a register\n"
+        );
+        assert!(!session.execute("quit"));
+    }
+}
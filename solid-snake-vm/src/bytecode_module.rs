@@ -0,0 +1,553 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+// On-disk layout of a `.ssb` module. All integers are big endian, same as the instruction encoding.
+//
+// magic          [u8; 4]   "SSBC"
+// format_version u16
+// isa_version    u16
+// entry_point    u64       byte offset into the code section
+// section_count  u16
+// sections       { kind: u16, length: u64, payload: [u8; length] } * section_count
+//
+// The code section is required, every other section is optional and may appear at most once.
+
+pub const MODULE_MAGIC: [u8; 4] = *b"SSBC";
+
+/// Version of the container layout itself.
+pub const MODULE_FORMAT_VERSION: u16 = 1;
+
+/// Version of the instruction set. Bumped whenever opcodes are added or change meaning,
+/// modules built against a newer instruction set are rejected.
+pub const ISA_VERSION: u16 = 1;
+
+#[repr(u16)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Code = 1,
+    Constants = 2,
+    Symbols = 3,
+    DebugInfo = 4,
+}
+
+impl TryFrom<u16> for SectionKind {
+    type Error = BytecodeModuleError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(SectionKind::Code),
+            2 => Ok(SectionKind::Constants),
+            3 => Ok(SectionKind::Symbols),
+            4 => Ok(SectionKind::DebugInfo),
+            other => Err(BytecodeModuleError::UnknownSection(other)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BytecodeModuleError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedFormatVersion(u16),
+    UnsupportedIsaVersion(u16),
+    Truncated,
+    TrailingBytes,
+    UnknownSection(u16),
+    DuplicateSection(u16),
+    MissingCodeSection,
+    InvalidEntryPoint(u64),
+    InvalidString,
+    /// Strings are length-prefixed with a u16.
+    StringTooLong(usize),
+}
+
+impl Error for BytecodeModuleError {}
+
+impl fmt::Display for BytecodeModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BytecodeModuleError::Io(err) => write!(f, "I/O error: {}", err),
+            BytecodeModuleError::BadMagic => write!(f, "Not a Solid Snake bytecode module"),
+            BytecodeModuleError::UnsupportedFormatVersion(version) => {
+                write!(f, "Unsupported module format version {}", version)
+            }
+            BytecodeModuleError::UnsupportedIsaVersion(version) => write!(
+                f,
+                "Module targets instruction set version {}, this VM supports up to {}",
+                version, ISA_VERSION
+            ),
+            BytecodeModuleError::Truncated => write!(f, "Module is truncated"),
+            BytecodeModuleError::TrailingBytes => write!(f, "Unexpected bytes after last section"),
+            BytecodeModuleError::UnknownSection(kind) => write!(f, "Unknown section kind {}", kind),
+            BytecodeModuleError::DuplicateSection(kind) => {
+                write!(f, "Section kind {} appears more than once", kind)
+            }
+            BytecodeModuleError::MissingCodeSection => write!(f, "Module has no code section"),
+            BytecodeModuleError::InvalidEntryPoint(offset) => {
+                write!(
+                    f,
+                    "Entry point {} is not the start of an instruction",
+                    offset
+                )
+            }
+            BytecodeModuleError::InvalidString => write!(f, "Invalid UTF-8 in module string"),
+            BytecodeModuleError::StringTooLong(length) => write!(
+                f,
+                "Module string of {} bytes is longer than {}",
+                length,
+                u16::MAX
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for BytecodeModuleError {
+    fn from(err: io::Error) -> Self {
+        BytecodeModuleError::Io(err)
+    }
+}
+
+/// A named byte offset into the code section, usually a label from the assembler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub offset: u64,
+}
+
+/// Maps the instruction starting at `offset` back to a position in the source it was built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineMapping {
+    pub offset: u64,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfo {
+    pub source_name: String,
+    pub lines: Vec<LineMapping>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytecodeModule {
+    pub isa_version: u16,
+    pub entry_point: u64,
+    pub code: Vec<u8>,
+    pub constants: Vec<Vec<u8>>,
+    pub symbols: Option<Vec<Symbol>>,
+    pub debug_info: Option<DebugInfo>,
+}
+
+impl BytecodeModule {
+    pub fn new(code: Vec<u8>, constants: Vec<Vec<u8>>) -> Self {
+        Self {
+            isa_version: ISA_VERSION,
+            entry_point: 0,
+            code,
+            constants,
+            symbols: None,
+            debug_info: None,
+        }
+    }
+
+    pub fn with_entry_point(mut self, entry_point: u64) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: Vec<Symbol>) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.debug_info = Some(debug_info);
+        self
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols
+            .as_ref()?
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.offset)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BytecodeModuleError> {
+        let mut sections: Vec<(SectionKind, Vec<u8>)> =
+            vec![(SectionKind::Code, self.code.clone())];
+
+        if !self.constants.is_empty() {
+            let mut payload = Vec::new();
+            payload.extend_from_slice(&(self.constants.len() as u64).to_be_bytes());
+            for constant in &self.constants {
+                payload.extend_from_slice(&(constant.len() as u64).to_be_bytes());
+                payload.extend_from_slice(constant);
+            }
+            sections.push((SectionKind::Constants, payload));
+        }
+
+        if let Some(symbols) = &self.symbols {
+            let mut payload = Vec::new();
+            payload.extend_from_slice(&(symbols.len() as u64).to_be_bytes());
+            for symbol in symbols {
+                payload.extend_from_slice(&symbol.offset.to_be_bytes());
+                write_str(&mut payload, &symbol.name)?;
+            }
+            sections.push((SectionKind::Symbols, payload));
+        }
+
+        if let Some(debug_info) = &self.debug_info {
+            let mut payload = Vec::new();
+            write_str(&mut payload, &debug_info.source_name)?;
+            payload.extend_from_slice(&(debug_info.lines.len() as u64).to_be_bytes());
+            for mapping in &debug_info.lines {
+                payload.extend_from_slice(&mapping.offset.to_be_bytes());
+                payload.extend_from_slice(&mapping.line.to_be_bytes());
+                payload.extend_from_slice(&mapping.column.to_be_bytes());
+            }
+            sections.push((SectionKind::DebugInfo, payload));
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MODULE_MAGIC);
+        bytes.extend_from_slice(&MODULE_FORMAT_VERSION.to_be_bytes());
+        bytes.extend_from_slice(&self.isa_version.to_be_bytes());
+        bytes.extend_from_slice(&self.entry_point.to_be_bytes());
+        bytes.extend_from_slice(&(sections.len() as u16).to_be_bytes());
+        for (kind, payload) in sections {
+            bytes.extend_from_slice(&(kind as u16).to_be_bytes());
+            bytes.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(&payload);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BytecodeModuleError> {
+        let mut reader = ModuleReader::new(bytes);
+
+        if reader.take(MODULE_MAGIC.len())? != MODULE_MAGIC {
+            return Err(BytecodeModuleError::BadMagic);
+        }
+
+        let format_version = reader.u16()?;
+        if format_version != MODULE_FORMAT_VERSION {
+            return Err(BytecodeModuleError::UnsupportedFormatVersion(
+                format_version,
+            ));
+        }
+
+        let isa_version = reader.u16()?;
+        if isa_version > ISA_VERSION {
+            return Err(BytecodeModuleError::UnsupportedIsaVersion(isa_version));
+        }
+
+        let entry_point = reader.u64()?;
+        let section_count = reader.u16()?;
+
+        let mut code = None;
+        let mut constants = None;
+        let mut symbols = None;
+        let mut debug_info = None;
+
+        for _ in 0..section_count {
+            let raw_kind = reader.u16()?;
+            let kind = SectionKind::try_from(raw_kind)?;
+            let length = reader.u64()?;
+            let payload = reader
+                .take(usize::try_from(length).map_err(|_| BytecodeModuleError::Truncated)?)?;
+
+            let already_present = match kind {
+                SectionKind::Code => code.replace(payload.to_vec()).is_some(),
+                SectionKind::Constants => constants.replace(read_constants(payload)?).is_some(),
+                SectionKind::Symbols => symbols.replace(read_symbols(payload)?).is_some(),
+                SectionKind::DebugInfo => debug_info.replace(read_debug_info(payload)?).is_some(),
+            };
+            if already_present {
+                return Err(BytecodeModuleError::DuplicateSection(raw_kind));
+            }
+        }
+
+        if !reader.is_empty() {
+            return Err(BytecodeModuleError::TrailingBytes);
+        }
+
+        let code = code.ok_or(BytecodeModuleError::MissingCodeSection)?;
+        if entry_point != 0 && entry_point >= code.len() as u64 {
+            return Err(BytecodeModuleError::InvalidEntryPoint(entry_point));
+        }
+
+        Ok(Self {
+            isa_version,
+            entry_point,
+            code,
+            constants: constants.unwrap_or_default(),
+            symbols,
+            debug_info,
+        })
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), BytecodeModuleError> {
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: impl AsRef<Path>) -> Result<Self, BytecodeModuleError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+}
+
+fn write_str(bytes: &mut Vec<u8>, s: &str) -> Result<(), BytecodeModuleError> {
+    let length = u16::try_from(s.len()).map_err(|_| BytecodeModuleError::StringTooLong(s.len()))?;
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn read_constants(payload: &[u8]) -> Result<Vec<Vec<u8>>, BytecodeModuleError> {
+    let mut reader = ModuleReader::new(payload);
+    let count = reader.u64()?;
+    let mut constants = Vec::new();
+    for _ in 0..count {
+        let length = reader.u64()?;
+        let bytes =
+            reader.take(usize::try_from(length).map_err(|_| BytecodeModuleError::Truncated)?)?;
+        constants.push(bytes.to_vec());
+    }
+    reader.finish()?;
+    Ok(constants)
+}
+
+fn read_symbols(payload: &[u8]) -> Result<Vec<Symbol>, BytecodeModuleError> {
+    let mut reader = ModuleReader::new(payload);
+    let count = reader.u64()?;
+    let mut symbols = Vec::new();
+    for _ in 0..count {
+        let offset = reader.u64()?;
+        let name = reader.str()?;
+        symbols.push(Symbol { name, offset });
+    }
+    reader.finish()?;
+    Ok(symbols)
+}
+
+fn read_debug_info(payload: &[u8]) -> Result<DebugInfo, BytecodeModuleError> {
+    let mut reader = ModuleReader::new(payload);
+    let source_name = reader.str()?;
+    let count = reader.u64()?;
+    let mut lines = Vec::new();
+    for _ in 0..count {
+        let offset = reader.u64()?;
+        let line = reader.u32()?;
+        let column = reader.u32()?;
+        lines.push(LineMapping {
+            offset,
+            line,
+            column,
+        });
+    }
+    reader.finish()?;
+    Ok(DebugInfo { source_name, lines })
+}
+
+struct ModuleReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ModuleReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn finish(&self) -> Result<(), BytecodeModuleError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(BytecodeModuleError::TrailingBytes)
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BytecodeModuleError> {
+        if n > self.bytes.len() {
+            return Err(BytecodeModuleError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(n);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, BytecodeModuleError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, BytecodeModuleError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, BytecodeModuleError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> Result<String, BytecodeModuleError> {
+        let length = self.u16()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| BytecodeModuleError::InvalidString)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{RegisterFileExt, VmInterpretedExecutor};
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn sample_module() -> BytecodeModule {
+        let code = [
+            LoadImmediateI64Instruction::encode((R!(1), 7i64)),
+            HaltInstruction::encode((0,)),
+            LoadImmediateI64Instruction::encode((R!(1), 42i64)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        BytecodeModule::new(code, vec![b"hello".to_vec(), vec![1, 2, 3]])
+            .with_entry_point(21)
+            .with_symbols(vec![
+                Symbol {
+                    name: "start".to_string(),
+                    offset: 0,
+                },
+                Symbol {
+                    name: "main".to_string(),
+                    offset: 21,
+                },
+            ])
+            .with_debug_info(DebugInfo {
+                source_name: "sample.sasm".to_string(),
+                lines: vec![
+                    LineMapping {
+                        offset: 0,
+                        line: 1,
+                        column: 1,
+                    },
+                    LineMapping {
+                        offset: 21,
+                        line: 4,
+                        column: 5,
+                    },
+                ],
+            })
+    }
+
+    #[test]
+    fn module_round_trip() {
+        let module = sample_module();
+        let bytes = module.to_bytes().unwrap();
+
+        assert_eq!(&bytes[..4], b"SSBC");
+        assert_eq!(BytecodeModule::from_bytes(&bytes).unwrap(), module);
+    }
+
+    #[test]
+    fn module_round_trip_minimal() {
+        let module = BytecodeModule::new(HaltInstruction::encode((0,)), Vec::new());
+        let decoded = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(decoded, module);
+        assert_eq!(decoded.symbols, None);
+        assert_eq!(decoded.debug_info, None);
+    }
+
+    #[test]
+    fn module_symbol_lookup() {
+        let module = sample_module();
+        assert_eq!(module.symbol("main"), Some(21));
+        assert_eq!(module.symbol("missing"), None);
+    }
+
+    #[test]
+    fn module_rejects_bad_magic() {
+        let mut bytes = sample_module().to_bytes().unwrap();
+        bytes[0] = b'X';
+        assert!(matches!(
+            BytecodeModule::from_bytes(&bytes),
+            Err(BytecodeModuleError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn module_rejects_newer_isa() {
+        let mut module = sample_module();
+        module.isa_version = ISA_VERSION + 1;
+        assert!(matches!(
+            BytecodeModule::from_bytes(&module.to_bytes().unwrap()),
+            Err(BytecodeModuleError::UnsupportedIsaVersion(_))
+        ));
+    }
+
+    #[test]
+    fn module_rejects_truncation() {
+        let bytes = sample_module().to_bytes().unwrap();
+        for len in 0..bytes.len() {
+            assert!(
+                BytecodeModule::from_bytes(&bytes[..len]).is_err(),
+                "Truncation at {len} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn module_rejects_trailing_bytes() {
+        let mut bytes = sample_module().to_bytes().unwrap();
+        bytes.push(0);
+        assert!(matches!(
+            BytecodeModule::from_bytes(&bytes),
+            Err(BytecodeModuleError::TrailingBytes)
+        ));
+    }
+
+    #[test]
+    fn module_rejects_long_strings() {
+        let module = sample_module().with_symbols(vec![Symbol {
+            name: "a".repeat(u16::MAX as usize + 1),
+            offset: 0,
+        }]);
+        assert!(matches!(
+            module.to_bytes(),
+            Err(BytecodeModuleError::StringTooLong(65536))
+        ));
+    }
+
+    #[test]
+    fn module_runs_from_entry_point() {
+        let module = BytecodeModule::from_bytes(&sample_module().to_bytes().unwrap()).unwrap();
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.load_module(&module).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let val: i64 = vm.registers().get_register_value(R!(1)).unwrap();
+        assert_eq!(val, 42);
+        assert_eq!(vm.get_constant(0), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn module_entry_point_must_be_instruction_boundary() {
+        let module = sample_module().with_entry_point(3);
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        let result = vm.load_module(&module);
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::InvalidModule(
+                BytecodeModuleError::InvalidEntryPoint(3)
+            ))
+        ));
+    }
+}
@@ -1,268 +1,993 @@
 use std::{
-    borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::HashMap,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
+use ariadne::{Color, Label, Report, ReportKind, Source};
+
 use crate::{
-    executor::interpreted::opcode_decoder::{SIZE_OF_REGISTER, initialize_dispatch_table},
+    bytecode_module::{BytecodeModule, Symbol},
+    docs::ArgType,
+    executor::interpreted::opcode_decoder::{
+        RegisterType, VmParse, VmParseError, initialize_dispatch_table,
+    },
     opcodes::OpCode,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum JumpWithLabel<'a> {
-    Jump(&'a str),
-    JumpIf(&'a str, u8),
-    JumpIfFalse(&'a str, u8),
-    CallFunction(&'a str),
+pub struct Assembled {
+    pub bytecode: Vec<u8>,
+    /// Every source line with the byte offset its code starts at, followed by the total length.
+    /// Included files are spliced in where their `.include` is.
+    pub lined_code: Vec<(String, usize)>,
+    /// Label names, without the trailing `:`, and the byte offset they point to.
+    pub labels: Vec<(String, usize)>,
+    /// Data declared with `.const` and `.bytes`, indexed by constant id.
+    pub constants: Vec<Vec<u8>>,
+    /// Constant names and the id an `@name` argument resolves to.
+    pub constant_ids: Vec<(String, u64)>,
+    /// Byte offset chosen with `.entry`, 0 when there is none.
+    pub entry_point: usize,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum ProcessedLinePassOne<'a> {
-    Jump(JumpWithLabel<'a>),
-    Compiled(Vec<u8>),
-    Label(Cow<'a, str>),
+impl Assembled {
+    /// Packs the code, constants and entry point into a module, with the labels as its symbols.
+    pub fn into_module(self) -> BytecodeModule {
+        let symbols = self
+            .labels
+            .into_iter()
+            .map(|(name, offset)| Symbol {
+                name,
+                offset: offset as u64,
+            })
+            .collect();
+
+        BytecodeModule::new(self.bytecode, self.constants)
+            .with_entry_point(self.entry_point as u64)
+            .with_symbols(symbols)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-enum ProcessedLinePassTwo<'a> {
-    Jump(JumpWithLabel<'a>),
-    Compiled(Vec<u8>),
+/// A problem in assembler source. `line` and `column` are 1-based, `len` is the length of the offending token.
+/// `file` is set when the problem is in a file read from disk rather than the source passed in.
+#[derive(Debug)]
+pub struct AsmError {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+    pub error: VmParseError,
 }
 
-pub fn parse_byte_code_from_txt(code: &str) -> (Vec<u8>, Vec<(String, usize)>) {
-    let dispatch_table = initialize_dispatch_table();
-    // remove comments
-    let code: String = code
-        .lines()
-        .map(|line| {
-            if let Some((code, _comment)) = line.split_once(';') {
-                code
-            } else {
-                line
-            }
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    let mut labels_to_line: HashMap<&str, u64> = HashMap::new();
-
-    let labels: HashSet<&str> = code
-        .lines()
-        .filter_map(|line| {
-            let tokens: Vec<&str> = line
-                .split_whitespace()
-                .filter(|token| token.ends_with(':'))
-                .collect();
-            tokens.first().copied()
-        })
-        .collect();
+impl AsmError {
+    /// Renders the error against its source the same way the compiler reports its errors.
+    pub fn report(&self, source_name: &str, source: &str) -> String {
+        let start = char_offset(source, self.line, self.column);
+        let span = start..start + self.len.max(1);
 
-    let mut first_pass: Vec<(ProcessedLinePassOne, usize)> = Vec::new();
+        let mut out = Vec::new();
+        Report::build(ReportKind::Error, (source_name, span.clone()))
+            .with_message(self.error.to_string())
+            .with_label(
+                Label::new((source_name, span))
+                    .with_message(self.error.label())
+                    .with_color(Color::Red),
+            )
+            .finish()
+            .write((source_name, Source::from(source)), &mut out)
+            .unwrap();
 
-    let mut byte_count = 0;
-    let mut lined_code: Vec<(String, usize)> = Vec::new();
-    let mut counter = 0;
-    for (line_idx, line) in code.lines().enumerate() {
-        lined_code.push((line.to_string(), byte_count));
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        String::from_utf8_lossy(&out).into_owned()
+    }
+}
 
-        if tokens.is_empty() {
-            continue;
+impl Error for AsmError {}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
         }
+        write!(f, "{}:{}: {}", self.line, self.column, self.error)
+    }
+}
 
-        if labels.contains((tokens[0].to_string()).as_str()) {
-            first_pass.push((
-                ProcessedLinePassOne::Label((tokens[0].to_string()).into()),
-                line_idx,
-            ));
-            tokens = tokens[1..].to_vec();
+impl VmParseError {
+    fn label(&self) -> &'static str {
+        match self {
+            VmParseError::InvalidRegister(_) => "Not a register between R0 and R127",
+            VmParseError::InvalidArgument(_) => "Does not fit the argument's type",
+            VmParseError::UnknownInstruction(_) => "Not an instruction",
+            VmParseError::WrongArgumentCount { .. } => "Wrong number of arguments",
+            VmParseError::DuplicateLabel(_) => "Defined again here",
+            VmParseError::UndefinedLabel(_) => "Label is never defined",
+            VmParseError::UnknownDirective(_) => "Not a directive",
+            VmParseError::InvalidDirective(_) => "Malformed directive",
+            VmParseError::DuplicateConstant(_) => "Defined again here",
+            VmParseError::UndefinedConstant(_) => "Constant is never defined",
+            VmParseError::IncludeFailed(_) => "Included here",
         }
+    }
+}
 
-        if tokens.is_empty() {
+/// Prints every error to stderr, errors from included files are shown against that file.
+pub fn report_asm_errors(errors: &[AsmError], source_name: &str, source: &str) {
+    for error in errors {
+        let Some(file) = &error.file else {
+            eprint!("{}", error.report(source_name, source));
             continue;
+        };
+
+        match std::fs::read_to_string(file) {
+            Ok(text) => eprint!("{}", error.report(&file.display().to_string(), &text)),
+            Err(_) => eprintln!("{}", error),
         }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    column: usize,
+    byte: usize,
+    text: &'a str,
+}
+
+struct SourceInstruction<'a> {
+    seq: usize,
+    opcode: OpCode,
+    mnemonic: Token<'a>,
+    args: Vec<Token<'a>>,
+}
+
+struct SourceFile {
+    path: Option<PathBuf>,
+    text: String,
+}
+
+/// One line of the program once includes are spliced in.
+#[derive(Debug, Clone, Copy)]
+struct SourceLine {
+    file: usize,
+    line: usize,
+}
+
+/// Errors are keyed by their position in the spliced program so they come out in source order.
+struct Diagnostics<'a> {
+    files: &'a [SourceFile],
+    lines: &'a [SourceLine],
+    errors: Vec<(usize, AsmError)>,
+}
+
+impl Diagnostics<'_> {
+    fn push(&mut self, seq: usize, token: Token, error: VmParseError) {
+        self.push_at(seq, token.column, token.text.chars().count(), error);
+    }
+
+    fn push_at(&mut self, seq: usize, column: usize, len: usize, error: VmParseError) {
+        let SourceLine { file, line } = self.lines[seq];
+        let error = AsmError {
+            file: self.files[file].path.clone(),
+            line,
+            column,
+            len,
+            error,
+        };
+        self.errors.push((seq, error));
+    }
+
+    fn finish(mut self) -> Result<(), Vec<AsmError>> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        self.errors.sort_by_key(|(seq, error)| (*seq, error.column));
+        Err(self.errors.into_iter().map(|(_, error)| error).collect())
+    }
+}
+
+/// Assembles text into bytecode. A line is an optional `label:`, then an instruction and its arguments,
+/// anything after `;` is a comment. Branch targets can be a label or a byte offset.
+///
+/// Lines starting with `.` are directives:
+/// - `.const name "text"` declares a string constant, stored length-prefixed like the compiler's strings
+/// - `.bytes name 0x01 0x02 ..` declares a constant from raw bytes
+/// - `.entry label` starts execution at `label` instead of the first instruction
+/// - `.include "file.sasm"` assembles another file in place of the directive
+///
+/// An argument written as `@name` is replaced with the id of that constant.
+/// Every error in the source is collected instead of stopping at the first one.
+pub fn parse_byte_code_from_txt(code: &str) -> Result<Assembled, Vec<AsmError>> {
+    assemble(SourceFile {
+        path: None,
+        text: code.to_string(),
+    })
+}
+
+/// Like `parse_byte_code_from_txt`, with includes resolved relative to the file instead of the working directory.
+pub fn assemble_file(path: impl AsRef<Path>) -> Result<Assembled, Vec<AsmError>> {
+    let path = path.as_ref();
+    match std::fs::read_to_string(path) {
+        Ok(text) => assemble(SourceFile {
+            path: Some(path.to_path_buf()),
+            text,
+        }),
+        Err(err) => Err(vec![AsmError {
+            file: Some(path.to_path_buf()),
+            line: 1,
+            column: 1,
+            len: 0,
+            error: VmParseError::IncludeFailed(format!("{}: {}", path.display(), err)),
+        }]),
+    }
+}
+
+fn assemble(root: SourceFile) -> Result<Assembled, Vec<AsmError>> {
+    let mut files = vec![root];
+    let mut lines = Vec::new();
+    let mut include_errors = Vec::new();
+    let mut include_stack: Vec<PathBuf> = files[0]
+        .path
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect();
+    load_includes(
+        &mut files,
+        0,
+        &mut lines,
+        &mut include_errors,
+        &mut include_stack,
+    );
+
+    let file_lines: Vec<Vec<&str>> = files
+        .iter()
+        .map(|file| file.text.lines().collect())
+        .collect();
+    let mut diagnostics = Diagnostics {
+        files: &files,
+        lines: &lines,
+        errors: Vec::new(),
+    };
+    for (seq, column, len, error) in include_errors {
+        diagnostics.push_at(seq, column, len, error);
+    }
 
-        let opcode_str = tokens[0];
+    let dispatch_table = initialize_dispatch_table();
+
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut label_list = Vec::new();
+    let mut constant_ids: HashMap<&str, u64> = HashMap::new();
+    let mut constant_list = Vec::new();
+    let mut constants = Vec::new();
+    let mut entry = None;
+    let mut instructions = Vec::new();
+    let mut lined_code = Vec::new();
+    let mut byte_count = 0;
 
-        let opcode =
-            OpCode::from_str(opcode_str).unwrap_or_else(|_| panic!("Wrong opcode {opcode_str}"));
-        let (op_instr, parse_handler, opcode_size) = dispatch_table[opcode as usize];
-        byte_count += size_of::<u16>() + opcode_size;
+    for (seq, &SourceLine { file, line }) in lines.iter().enumerate() {
+        let code = strip_comment(file_lines[file][line - 1]);
+        lined_code.push((code.to_string(), byte_count));
 
-        if (opcode_str.starts_with("Jump") || opcode_str.starts_with("CallFunction"))
-            && labels.contains((tokens[1].to_string() + ":").as_str())
+        let mut tokens = tokenize(code);
+        if let Some(&directive) = tokens.first()
+            && directive.text.starts_with('.')
         {
-            match opcode {
-                OpCode::Jump => {
-                    if tokens.len() > 2 {
-                        panic!("too many Jump arguments")
-                    }
-                    first_pass.push((
-                        ProcessedLinePassOne::Jump(JumpWithLabel::Jump(tokens[1])),
-                        line_idx,
-                    ));
-                }
-                OpCode::JumpIf => {
-                    if tokens.len() > 3 {
-                        panic!("too many JumpIf arguments")
-                    }
-                    first_pass.push((
-                        ProcessedLinePassOne::Jump(JumpWithLabel::JumpIf(
-                            tokens[1],
-                            u8::from_str(tokens[2].strip_prefix('R').unwrap()).unwrap(),
-                        )),
-                        line_idx,
-                    ));
-                }
-                OpCode::JumpIfFalse => {
-                    if tokens.len() > 3 {
-                        panic!("too many JumpIfFalse arguments")
+            match directive.text {
+                // Already spliced in by `load_includes`
+                ".include" => {}
+                ".entry" => match tokens[..] {
+                    [_, label] if entry.is_none() => entry = Some((seq, label)),
+                    [_, label] => diagnostics.push(
+                        seq,
+                        label,
+                        VmParseError::InvalidDirective("entry point is already set".to_string()),
+                    ),
+                    _ => diagnostics.push(
+                        seq,
+                        directive,
+                        VmParseError::WrongArgumentCount {
+                            expected: 1,
+                            got: tokens.len() - 1,
+                        },
+                    ),
+                },
+                ".const" | ".bytes" => {
+                    let Some(&name) = tokens.get(1) else {
+                        diagnostics.push(
+                            seq,
+                            directive,
+                            VmParseError::InvalidDirective("missing constant name".to_string()),
+                        );
+                        continue;
+                    };
+
+                    let data = if directive.text == ".const" {
+                        parse_string_constant(code, &tokens)
+                    } else {
+                        parse_bytes_constant(&tokens[2..])
+                    };
+
+                    match data {
+                        Err((token, error)) => diagnostics.push(seq, token, error),
+                        Ok(_) if constant_ids.contains_key(name.text) => diagnostics.push(
+                            seq,
+                            name,
+                            VmParseError::DuplicateConstant(name.text.to_string()),
+                        ),
+                        Ok(data) => {
+                            let id = constants.len() as u64;
+                            constants.push(data);
+                            constant_ids.insert(name.text, id);
+                            constant_list.push((name.text.to_string(), id));
+                        }
                     }
-                    first_pass.push((
-                        ProcessedLinePassOne::Jump(JumpWithLabel::JumpIfFalse(
-                            tokens[1],
-                            u8::from_str(tokens[2].strip_prefix('R').unwrap()).unwrap(),
-                        )),
-                        line_idx,
-                    ));
                 }
-                OpCode::CallFunction => {
-                    if tokens.len() > 2 {
-                        panic!("too many CallFunction arguments")
-                    }
-                    first_pass.push((
-                        ProcessedLinePassOne::Jump(JumpWithLabel::CallFunction(tokens[1])),
-                        line_idx,
-                    ));
+                _ => diagnostics.push(
+                    seq,
+                    directive,
+                    VmParseError::UnknownDirective(directive.text.to_string()),
+                ),
+            }
+            continue;
+        }
+
+        if let Some(&first) = tokens.first()
+            && let Some(name) = first.text.strip_suffix(':')
+        {
+            if labels.insert(name, byte_count).is_some() {
+                diagnostics.push(seq, first, VmParseError::DuplicateLabel(name.to_string()));
+            } else {
+                label_list.push((name.to_string(), byte_count));
+            }
+            tokens.remove(0);
+        }
+
+        let Some((&mnemonic, args)) = tokens.split_first() else {
+            continue;
+        };
+
+        let Ok(opcode) = OpCode::from_str(mnemonic.text) else {
+            diagnostics.push(
+                seq,
+                mnemonic,
+                VmParseError::UnknownInstruction(mnemonic.text.to_string()),
+            );
+            continue;
+        };
+
+        byte_count += opcode.instr_size();
+        instructions.push(SourceInstruction {
+            seq,
+            opcode,
+            mnemonic,
+            args: args.to_vec(),
+        });
+    }
+
+    lined_code.push((String::new(), byte_count));
+
+    let entry_point = match entry {
+        Some((seq, label)) => labels.get(label.text).copied().unwrap_or_else(|| {
+            diagnostics.push(
+                seq,
+                label,
+                VmParseError::UndefinedLabel(label.text.to_string()),
+            );
+            0
+        }),
+        None => 0,
+    };
+
+    let mut bytecode = Vec::with_capacity(byte_count);
+    for instr in instructions {
+        let docs = instr.opcode.get_doc();
+        if instr.args.len() != docs.args.len() {
+            diagnostics.push(
+                instr.seq,
+                instr.mnemonic,
+                VmParseError::WrongArgumentCount {
+                    expected: docs.args.len(),
+                    got: instr.args.len(),
+                },
+            );
+            continue;
+        }
+
+        let mut resolved = Vec::with_capacity(instr.args.len());
+        let mut line_errors = false;
+        for (idx, (token, arg)) in instr.args.iter().zip(&docs.args).enumerate() {
+            let is_label =
+                idx == 0 && instr.opcode.has_branch_target() && u64::parse_vm(token.text).is_err();
+
+            let checked = if let Some(name) = token.text.strip_prefix('@') {
+                constant_ids
+                    .get(name)
+                    .ok_or_else(|| VmParseError::UndefinedConstant(name.to_string()))
+                    .and_then(|id| {
+                        let id = id.to_string();
+                        check_arg(arg.typ, &id).map(|_| id)
+                    })
+            } else if is_label {
+                labels
+                    .get(token.text)
+                    .map(|offset| offset.to_string())
+                    .ok_or_else(|| VmParseError::UndefinedLabel(token.text.to_string()))
+            } else {
+                check_arg(arg.typ, token.text).map(|_| token.text.to_string())
+            };
+
+            match checked {
+                Ok(arg) => resolved.push(arg),
+                Err(error) => {
+                    diagnostics.push(instr.seq, *token, error);
+                    line_errors = true;
                 }
+            }
+        }
+
+        if line_errors {
+            continue;
+        }
 
-                _ => {}
+        let strs: Vec<&str> = resolved.iter().map(String::as_str).collect();
+        let (_, parse_handler, _) = dispatch_table[instr.opcode as usize];
+        match parse_handler(&strs) {
+            Ok(args) => {
+                bytecode.extend_from_slice(&(instr.opcode as u16).to_be_bytes());
+                bytecode.extend_from_slice(&args);
             }
+            Err(error) => diagnostics.push(instr.seq, instr.mnemonic, error),
+        }
+    }
+
+    diagnostics.finish()?;
+
+    Ok(Assembled {
+        bytecode,
+        lined_code,
+        labels: label_list,
+        constants,
+        constant_ids: constant_list,
+        entry_point,
+    })
+}
 
+/// Appends the lines of `files[file]` to `lines`, loading each `.include` and splicing its lines in after the directive.
+/// Problems are recorded as (line index, column, length, error), pointing at the include's path.
+/// `stack` holds the canonical paths of the files currently being included, to catch cycles.
+fn load_includes(
+    files: &mut Vec<SourceFile>,
+    file: usize,
+    lines: &mut Vec<SourceLine>,
+    errors: &mut Vec<(usize, usize, usize, VmParseError)>,
+    stack: &mut Vec<PathBuf>,
+) {
+    let text = files[file].text.clone();
+    let base_dir = files[file]
+        .path
+        .as_deref()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf);
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let seq = lines.len();
+        lines.push(SourceLine {
+            file,
+            line: line_idx + 1,
+        });
+
+        let code = strip_comment(line);
+        let tokens = tokenize(code);
+        let [directive, ref rest @ ..] = tokens[..] else {
+            continue;
+        };
+        if directive.text != ".include" {
             continue;
         }
+        let Some(&path_token) = rest.first() else {
+            errors.push((
+                seq,
+                directive.column,
+                directive.text.len(),
+                VmParseError::InvalidDirective("missing file to include".to_string()),
+            ));
+            continue;
+        };
 
-        tokens = tokens[1..].to_vec();
+        let path_text = code[path_token.byte..].trim_end();
+        let mut fail = |error| {
+            errors.push((seq, path_token.column, path_text.chars().count(), error));
+        };
 
-        let mut byte_code = Vec::new();
-        byte_code.extend_from_slice(&(opcode as u16).to_be_bytes());
-        byte_code.extend_from_slice(&parse_handler(&tokens).unwrap());
+        let include = match parse_quoted(path_text) {
+            Ok(include) => include,
+            Err(error) => {
+                fail(error);
+                continue;
+            }
+        };
 
-        first_pass.push((ProcessedLinePassOne::Compiled(byte_code), line_idx));
+        let path = match &base_dir {
+            Some(dir) => dir.join(&include),
+            None => PathBuf::from(&include),
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) => {
+                fail(VmParseError::IncludeFailed(format!(
+                    "{}: {}",
+                    path.display(),
+                    err
+                )));
+                continue;
+            }
+        };
+
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if stack.contains(&canonical) {
+            fail(VmParseError::IncludeFailed(format!(
+                "{}: file includes itself",
+                path.display()
+            )));
+            continue;
+        }
+
+        files.push(SourceFile {
+            path: Some(path),
+            text,
+        });
+        stack.push(canonical);
+        load_includes(files, files.len() - 1, lines, errors, stack);
+        stack.pop();
     }
+}
 
-    lined_code.push((String::new(), byte_count));
+/// Stores a `.const` string the way the compiler lays out string constants, a u64 length followed by UTF-8.
+fn parse_string_constant<'a>(
+    code: &'a str,
+    tokens: &[Token<'a>],
+) -> Result<Vec<u8>, (Token<'a>, VmParseError)> {
+    let Some(&start) = tokens.get(2) else {
+        let error = VmParseError::InvalidDirective("expected a quoted string".to_string());
+        return Err((tokens[1], error));
+    };
+
+    let value = Token {
+        text: code[start.byte..].trim_end(),
+        ..start
+    };
+    let text = parse_quoted(value.text).map_err(|error| (value, error))?;
 
-    let mut program_counter_second_pass = 0;
-    let second_pass: Vec<(ProcessedLinePassTwo, usize)> = first_pass
+    let mut data = (text.len() as u64).to_be_bytes().to_vec();
+    data.extend_from_slice(text.as_bytes());
+    Ok(data)
+}
+
+fn parse_bytes_constant<'a>(tokens: &[Token<'a>]) -> Result<Vec<u8>, (Token<'a>, VmParseError)> {
+    tokens
         .iter()
-        .filter_map(|(line, line_idx)| {
-            match line {
-                ProcessedLinePassOne::Compiled(bc) => {
-                    counter += 1;
-                    program_counter_second_pass += bc.len();
-                    Some((ProcessedLinePassTwo::Compiled(bc.clone()), *line_idx))
-                }
-                ProcessedLinePassOne::Jump(jump_with_label) => {
-                    // Add enough instructions for the jumps + args
-                    match &jump_with_label {
-                        JumpWithLabel::Jump(_) | JumpWithLabel::CallFunction(_) => {
-                            program_counter_second_pass += size_of::<u16>() + size_of::<u64>();
-                        }
-                        JumpWithLabel::JumpIf(_, _) | JumpWithLabel::JumpIfFalse(_, _) => {
-                            program_counter_second_pass +=
-                                size_of::<u16>() + size_of::<u64>() + SIZE_OF_REGISTER;
-                        }
-                    }
+        .map(|token| u8::parse_vm(token.text).map_err(|error| (*token, error)))
+        .collect()
+}
 
-                    Some((
-                        ProcessedLinePassTwo::Jump(jump_with_label.clone()),
-                        *line_idx,
-                    ))
-                }
-                ProcessedLinePassOne::Label(label) => {
-                    labels_to_line.insert(label, program_counter_second_pass as u64);
-                    None
-                }
-            }
-        })
-        .collect::<Vec<_>>();
-
-    let bc = second_pass
-        .into_iter()
-        .flat_map(|(line, _line_idx)| match line {
-            ProcessedLinePassTwo::Jump(jump_with_label) => {
-                let mut bc = Vec::new();
-
-                match jump_with_label {
-                    JumpWithLabel::Jump(label) => {
-                        bc.extend_from_slice(&(OpCode::Jump as u16).to_be_bytes());
-                        bc.extend_from_slice(
-                            &labels_to_line[(label.to_string() + ":").as_str()].to_be_bytes(),
-                        );
-                    }
-                    JumpWithLabel::JumpIf(label, if_reg) => {
-                        bc.extend_from_slice(&(OpCode::JumpIf as u16).to_be_bytes());
-                        bc.extend_from_slice(
-                            &labels_to_line[(label.to_string() + ":").as_str()].to_be_bytes(),
-                        );
-                        bc.extend_from_slice(&if_reg.to_be_bytes());
-                    }
-                    JumpWithLabel::JumpIfFalse(label, if_reg) => {
-                        bc.extend_from_slice(&(OpCode::JumpIfFalse as u16).to_be_bytes());
-                        bc.extend_from_slice(
-                            &labels_to_line[(label.to_string() + ":").as_str()].to_be_bytes(),
-                        );
-                        bc.extend_from_slice(&if_reg.to_be_bytes());
-                    }
-                    JumpWithLabel::CallFunction(label) => {
-                        bc.extend_from_slice(&(OpCode::CallFunction as u16).to_be_bytes());
-                        bc.extend_from_slice(
-                            &labels_to_line[(label.to_string() + ":").as_str()].to_be_bytes(),
-                        );
-                    }
+/// Parses a double quoted string, supporting `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\xNN` escapes.
+fn parse_quoted(s: &str) -> Result<String, VmParseError> {
+    let invalid = |reason: &str| VmParseError::InvalidDirective(reason.to_string());
+
+    let mut chars = s
+        .strip_prefix('"')
+        .ok_or_else(|| invalid("expected a quoted string"))?
+        .chars();
+    let mut out = String::new();
+
+    loop {
+        match chars.next() {
+            None => return Err(invalid("unterminated string")),
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('0') => out.push('\0'),
+                Some('\\') => out.push('\\'),
+                Some('"') => out.push('"'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    let byte = u8::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|byte| byte.is_ascii())
+                        .ok_or_else(|| invalid("\\x escapes must be two hex digits up to 7F"))?;
+                    out.push(byte as char);
                 }
+                _ => return Err(invalid("unknown escape sequence")),
+            },
+            Some(ch) => out.push(ch),
+        }
+    }
 
-                bc.into_iter()
-            }
-            ProcessedLinePassTwo::Compiled(items) => items.into_iter(),
-        })
-        .collect();
+    if !chars.as_str().trim().is_empty() {
+        return Err(invalid("unexpected text after string"));
+    }
 
-    (bc, lined_code)
+    Ok(out)
 }
-fn parse_int_with_radix<T: FromStrRadix + FromStr>(s: &str) -> T
-where
-    <T as std::str::FromStr>::Err: std::fmt::Debug,
-{
-    if let Some(stripped) = s.strip_prefix("0x") {
-        T::from_str_radix(stripped, 16).unwrap()
-    } else if let Some(stripped) = s.strip_prefix("0b") {
-        T::from_str_radix(stripped, 2).unwrap()
-    } else if let Some(stripped) = s.strip_prefix("0o") {
-        T::from_str_radix(stripped, 8).unwrap()
-    } else {
-        s.parse().unwrap()
+
+/// Cuts the `;` comment off a line, ignoring semicolons inside quoted strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (byte, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..byte],
+            _ => {}
+        }
     }
+
+    line
 }
 
-trait FromStrRadix: Sized + FromStr {
-    fn from_str_radix(s: &str, radix: u32) -> Result<Self, <Self as std::str::FromStr>::Err>;
+fn check_arg(typ: ArgType, s: &str) -> Result<(), VmParseError> {
+    match typ {
+        ArgType::I8 => i8::parse_vm(s).map(drop),
+        ArgType::I16 => i16::parse_vm(s).map(drop),
+        ArgType::I32 => i32::parse_vm(s).map(drop),
+        ArgType::I64 => i64::parse_vm(s).map(drop),
+        ArgType::U8 | ArgType::Bool => u8::parse_vm(s).map(drop),
+        ArgType::U16 => u16::parse_vm(s).map(drop),
+        ArgType::U32 => u32::parse_vm(s).map(drop),
+        ArgType::U64 => u64::parse_vm(s).map(drop),
+        ArgType::F32 => f32::parse_vm(s).map(drop),
+        ArgType::F64 => f64::parse_vm(s).map(drop),
+        ArgType::Register => RegisterType::parse_vm(s).map(drop),
+    }
 }
 
-macro_rules! impl_from_str_radix {
-    ($($t:ty),+) => {
-        $(impl FromStrRadix for $t {
-            fn from_str_radix(s: &str, radix: u32) -> Result<Self, <Self as std::str::FromStr>::Err> {
-                <$t>::from_str_radix(s, radix)
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (column, (byte, ch)) in line.char_indices().enumerate() {
+        match (ch.is_whitespace(), start) {
+            (false, None) => start = Some((byte, column + 1)),
+            (true, Some((start_byte, start_column))) => {
+                tokens.push(Token {
+                    column: start_column,
+                    byte: start_byte,
+                    text: &line[start_byte..byte],
+                });
+                start = None;
             }
-        })+
-    };
+            _ => {}
+        }
+    }
+
+    if let Some((start_byte, start_column)) = start {
+        tokens.push(Token {
+            column: start_column,
+            byte: start_byte,
+            text: &line[start_byte..],
+        });
+    }
+
+    tokens
+}
+
+/// Character offset of a 1-based line and column, which is how ariadne indexes sources.
+fn char_offset(source: &str, line: usize, column: usize) -> usize {
+    source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(|line| line.chars().count())
+        .sum::<usize>()
+        + column
+        - 1
 }
 
-impl_from_str_radix!(u8, u16, u32, u64, i8, i16, i32, i64);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::R;
+    use crate::executor::ext::VmExecutorExt;
+    use crate::executor::interpreted::implimentation::{
+        RegisterFileExt, VmHeapExt, VmInterpretedExecutor, VmMemorySectionExt,
+    };
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn errors(code: &str) -> Vec<(usize, usize, String)> {
+        parse_byte_code_from_txt(code)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.line, error.column, format!("{:?}", error.error)))
+            .collect()
+    }
+
+    #[test]
+    fn assembles_labels_and_numeric_targets() {
+        let code = "
+            LoadImmediateI64 R1 5   ; counter
+        loop:
+            DecrementI64 R1 1
+            JumpIf loop R1
+            Jump 33
+        end: Halt 0
+        ";
+
+        let assembled = parse_byte_code_from_txt(code).unwrap();
+        let expected = [
+            LoadImmediateI64Instruction::encode((R!(1), 5i64)),
+            DecrementI64Instruction::encode((R!(1), 1i64)),
+            JumpIfInstruction::encode((11u64, R!(1))),
+            JumpInstruction::encode((33u64,)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        assert_eq!(assembled.bytecode, expected);
+        assert_eq!(
+            assembled.labels,
+            vec![("loop".to_string(), 11), ("end".to_string(), 43)]
+        );
+        assert_eq!(assembled.lined_code.last(), Some(&(String::new(), 53)));
+    }
+
+    #[test]
+    fn reports_unknown_instruction_with_position() {
+        assert_eq!(
+            errors("Halt 0\n  LoadImediateI64 R1 5\n"),
+            vec![(2, 3, "UnknownInstruction(\"LoadImediateI64\")".to_string())]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_arguments_with_position() {
+        assert_eq!(
+            errors("AddI64 R1 X2 R300\nLoadImmediateU8 R1 256\nHalt 0"),
+            vec![
+                (1, 11, "InvalidRegister(\"X2\")".to_string()),
+                (1, 14, "InvalidRegister(\"R300\")".to_string()),
+                (2, 20, "InvalidArgument(\"256\")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_wrong_argument_count() {
+        assert_eq!(
+            errors("Jump a b\nHalt"),
+            vec![
+                (
+                    1,
+                    1,
+                    "WrongArgumentCount { expected: 1, got: 2 }".to_string()
+                ),
+                (
+                    2,
+                    1,
+                    "WrongArgumentCount { expected: 1, got: 0 }".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_and_undefined_labels() {
+        let code = "start:\n  Jump nowhere\nstart: Halt 0\n  CallFunction start\n";
+        assert_eq!(
+            errors(code),
+            vec![
+                (2, 8, "UndefinedLabel(\"nowhere\")".to_string()),
+                (3, 1, "DuplicateLabel(\"start\")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn collects_every_error() {
+        let code = "Nope\nAddI64 R1 R2\nJump missing\nHalt x\n";
+        assert_eq!(errors(code).len(), 4);
+    }
+
+    #[test]
+    fn report_points_at_token() {
+        let code = "Halt 0\nJumpIf done R1\n";
+        let errors = parse_byte_code_from_txt(code).unwrap_err();
+        assert_eq!(errors[0].to_string(), "2:8: Undefined label 'done'");
+
+        let report = errors[0].report("test.sasm", code);
+        assert!(report.contains("test.sasm"));
+        assert!(report.contains("Undefined label 'done'"));
+        assert!(report.contains("Label is never defined"));
+    }
+
+    /// Writes assembler files into a fresh directory under the system temp dir.
+    fn write_sources(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ssasm-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn constants_are_referenced_by_name() {
+        let code = r#"
+            .const greeting "hi; there\n"
+            .bytes table 0x01 2 0b11
+            StoreConstantArray R1 @greeting
+            StoreConstantArray R2 @table ; comment
+            Halt 0
+        "#;
+
+        let assembled = parse_byte_code_from_txt(code).unwrap();
+        let expected = [
+            StoreConstantArrayInstruction::encode((R!(1), 0u64)),
+            StoreConstantArrayInstruction::encode((R!(2), 1u64)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        assert_eq!(assembled.bytecode, expected);
+        assert_eq!(
+            assembled.constant_ids,
+            vec![("greeting".to_string(), 0), ("table".to_string(), 1)]
+        );
+
+        let mut greeting = 10u64.to_be_bytes().to_vec();
+        greeting.extend_from_slice(b"hi; there\n");
+        assert_eq!(assembled.constants, vec![greeting.clone(), vec![1, 2, 3]]);
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.load_module(&assembled.into_module()).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let section: u64 = vm.registers().get_register_value(R!(1)).unwrap();
+        let section = vm.heap().section(section as usize).unwrap();
+        assert_eq!(section.bytes_n(8 + greeting.len()).unwrap()[8..], greeting);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let assembled = parse_byte_code_from_txt(r#".const s "a\"b\\\t\x41\0""#).unwrap();
+        assert_eq!(&assembled.constants[0][8..], b"a\"b\\\tA\0");
+
+        let code = ".const a \"open\n.const b \"x\" extra\n.const c \"\\q\"\n.const d bare\n";
+        let errors = errors(code);
+        assert_eq!(errors.len(), 4);
+        assert!(
+            errors
+                .iter()
+                .all(|(_, column, error)| *column == 10 && error.starts_with("InvalidDirective"))
+        );
+    }
+
+    #[test]
+    fn reports_constant_errors() {
+        let code = ".const a \"x\"\n.bytes a 1\n.bytes b 256\nStoreConstantArray R1 @missing\n.data x\nHalt 0";
+        assert_eq!(
+            errors(code),
+            vec![
+                (2, 8, "DuplicateConstant(\"a\")".to_string()),
+                (3, 10, "InvalidArgument(\"256\")".to_string()),
+                (4, 23, "UndefinedConstant(\"missing\")".to_string()),
+                (5, 1, "UnknownDirective(\".data\")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_directive_sets_entry_point() {
+        let code = "
+            .entry main
+        skipped:
+            LoadImmediateI64 R1 2
+            Halt 0
+        main:
+            LoadImmediateI64 R1 1
+            Halt 0
+        ";
+
+        let assembled = parse_byte_code_from_txt(code).unwrap();
+        assert_eq!(assembled.entry_point, 21);
+
+        let module = assembled.into_module();
+        assert_eq!(module.entry_point, 21);
+        assert_eq!(module.symbol("main"), Some(21));
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.load_module(&module).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+        let val: i64 = vm.registers().get_register_value(R!(1)).unwrap();
+        assert_eq!(val, 1);
+
+        assert_eq!(
+            errors(".entry nowhere\n.entry again\nHalt 0"),
+            vec![
+                (1, 8, "UndefinedLabel(\"nowhere\")".to_string()),
+                (
+                    2,
+                    8,
+                    "InvalidDirective(\"entry point is already set\")".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_are_spliced_in_place() {
+        let dir = write_sources(
+            "include",
+            &[
+                (
+                    "main.sasm",
+                    ".include \"lib/helpers.sasm\"\n.entry main\nmain: CallFunction inc\nHalt 0\n",
+                ),
+                (
+                    "lib/helpers.sasm",
+                    ".const name \"lib\"\ninc: IncrementI64 R1 1\nReturn\n",
+                ),
+            ],
+        );
+        let assembled = assemble_file(dir.join("main.sasm"));
+        let _ = std::fs::remove_dir_all(&dir);
+        let assembled = assembled.unwrap();
+
+        let expected = [
+            IncrementI64Instruction::encode((R!(1), 1i64)),
+            ReturnInstruction::encode(()),
+            CallFunctionInstruction::encode((0u64,)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        assert_eq!(assembled.bytecode, expected);
+        assert_eq!(assembled.entry_point, 13);
+        assert_eq!(assembled.constant_ids, vec![("name".to_string(), 0)]);
+    }
+
+    #[test]
+    fn include_errors_point_at_their_file() {
+        let dir = write_sources(
+            "include-errors",
+            &[
+                (
+                    "main.sasm",
+                    "Halt 0\n.include \"other.sasm\"\n.include \"gone.sasm\"\n",
+                ),
+                ("other.sasm", "\n  Bogus\n.include \"main.sasm\"\n"),
+            ],
+        );
+        let result = assemble_file(dir.join("main.sasm"));
+        let _ = std::fs::remove_dir_all(&dir);
+        let errors = result.unwrap_err();
+
+        let found: Vec<_> = errors
+            .iter()
+            .map(|error| {
+                let file = error.file.as_ref().unwrap().file_name().unwrap();
+                (file.to_str().unwrap(), error.line, error.column)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("other.sasm", 2, 3),
+                ("other.sasm", 3, 10),
+                ("main.sasm", 3, 10)
+            ]
+        );
+        assert!(
+            matches!(&errors[1].error, VmParseError::IncludeFailed(reason) if reason.contains("includes itself"))
+        );
+        assert!(
+            errors[0]
+                .to_string()
+                .ends_with("other.sasm:2:3: Unknown instruction 'Bogus'")
+        );
+    }
+}
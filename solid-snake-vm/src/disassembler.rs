@@ -0,0 +1,286 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::docs::ArgType;
+use crate::executor::interpreted::verifier::{VerificationError, VerificationErrorKind};
+use crate::opcodes::{DecodedInstruction, OpCode};
+
+/// A single decoded instruction along with where it was found in the bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembledInstruction {
+    pub offset: usize,
+    pub opcode: OpCode,
+    pub decoded: DecodedInstruction,
+    /// Arguments in the assembler's syntax, branch targets as plain byte offsets.
+    pub args: Vec<String>,
+}
+
+impl DisassembledInstruction {
+    pub fn branch_target(&self) -> Option<u64> {
+        self.decoded.branch_target()
+    }
+}
+
+/// Decodes every instruction in the bytecode, stopping at the first one that can't be decoded.
+pub fn disassemble_instructions(
+    bytecode: &[u8],
+) -> Result<Vec<DisassembledInstruction>, VerificationError> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytecode.len() {
+        let remaining = &bytecode[offset..];
+        if remaining.len() < size_of::<u16>() {
+            return Err(VerificationError {
+                offset,
+                kind: VerificationErrorKind::TruncatedInstruction {
+                    expected: size_of::<u16>(),
+                    available: remaining.len(),
+                },
+            });
+        }
+
+        let raw_opcode = u16::from_be_bytes([remaining[0], remaining[1]]);
+        let opcode = OpCode::try_from(raw_opcode).map_err(|_| VerificationError {
+            offset,
+            kind: VerificationErrorKind::UnknownOpcode(raw_opcode),
+        })?;
+
+        let instr_size = opcode.instr_size();
+        if remaining.len() < instr_size {
+            return Err(VerificationError {
+                offset,
+                kind: VerificationErrorKind::TruncatedInstruction {
+                    expected: instr_size,
+                    available: remaining.len(),
+                },
+            });
+        }
+
+        let arg_bytes = &remaining[size_of::<u16>()..instr_size];
+        let args = opcode
+            .get_doc()
+            .arg_layout()
+            .map(|(arg_offset, arg)| {
+                format_arg(arg.typ, &arg_bytes[arg_offset..arg_offset + arg.bytes])
+            })
+            .collect();
+
+        instructions.push(DisassembledInstruction {
+            offset,
+            opcode,
+            decoded: DecodedInstruction::decode(opcode, arg_bytes),
+            args,
+        });
+        offset += instr_size;
+    }
+
+    Ok(instructions)
+}
+
+/// Produces assembler text that `parse_byte_code_from_txt` turns back into the same bytes.
+/// Branch targets that land on an instruction get an `L_XXXX:` label, each line is annotated with its byte offset.
+pub fn disassemble(bytecode: &[u8]) -> Result<String, VerificationError> {
+    let instructions = disassemble_instructions(bytecode)?;
+
+    let starts: BTreeSet<u64> = instructions
+        .iter()
+        .map(|instr| instr.offset as u64)
+        .collect();
+    let labels: BTreeSet<u64> = instructions
+        .iter()
+        .filter_map(DisassembledInstruction::branch_target)
+        .filter(|target| starts.contains(target))
+        .collect();
+
+    let mut out = String::new();
+    for instr in &instructions {
+        if labels.contains(&(instr.offset as u64)) {
+            writeln!(out, "{}:", label_name(instr.offset as u64)).unwrap();
+        }
+
+        let mut line = format!("{:?}", instr.opcode);
+        for (idx, arg) in instr.args.iter().enumerate() {
+            line.push(' ');
+            match instr.branch_target() {
+                Some(target) if idx == 0 && labels.contains(&target) => {
+                    line.push_str(&label_name(target))
+                }
+                _ => line.push_str(arg),
+            }
+        }
+
+        writeln!(out, "    {:<40} ; 0x{:04X}", line, instr.offset).unwrap();
+    }
+
+    Ok(out)
+}
+
+fn label_name(offset: u64) -> String {
+    format!("L_{:04X}", offset)
+}
+
+fn format_arg(typ: ArgType, bytes: &[u8]) -> String {
+    macro_rules! be {
+        ($ty:ty) => {
+            <$ty>::from_be_bytes(bytes.try_into().unwrap())
+        };
+    }
+
+    match typ {
+        ArgType::I8 => be!(i8).to_string(),
+        ArgType::I16 => be!(i16).to_string(),
+        ArgType::I32 => be!(i32).to_string(),
+        ArgType::I64 => be!(i64).to_string(),
+        ArgType::U8 | ArgType::Bool => be!(u8).to_string(),
+        ArgType::U16 => be!(u16).to_string(),
+        ArgType::U32 => be!(u32).to_string(),
+        ArgType::U64 => be!(u64).to_string(),
+        // NaN payloads are lost in decimal, so those are written as their bit pattern
+        ArgType::F32 => match be!(f32) {
+            value if value.is_nan() => format!("0x{:08X}", value.to_bits()),
+            value => value.to_string(),
+        },
+        ArgType::F64 => match be!(f64) {
+            value if value.is_nan() => format!("0x{:016X}", value.to_bits()),
+            value => value.to_string(),
+        },
+        ArgType::Register => format!("R{}", be!(u8)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::R;
+    use crate::bytecode_parser::parse_byte_code_from_txt;
+    use crate::executor::interpreted::implimentation::MAX_REGISTERS;
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    /// Small deterministic generator so every run covers the same argument patterns.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn encode_with_random_args(opcode: OpCode, rng: &mut XorShift, target: u64) -> Vec<u8> {
+        let mut bc = (opcode as u16).to_be_bytes().to_vec();
+        for (idx, (_, arg)) in opcode.get_doc().arg_layout().enumerate() {
+            match arg.typ {
+                ArgType::Register => bc.push((rng.next() % MAX_REGISTERS as u64) as u8),
+                ArgType::U64 if idx == 0 && opcode.has_branch_target() => {
+                    bc.extend_from_slice(&target.to_be_bytes())
+                }
+                _ => bc.extend_from_slice(&rng.next().to_be_bytes()[..arg.bytes]),
+            }
+        }
+        bc
+    }
+
+    fn assert_round_trips(bc: &[u8]) {
+        let text = disassemble(bc).unwrap();
+        let reassembled = parse_byte_code_from_txt(&text).unwrap().bytecode;
+        assert_eq!(reassembled, bc, "round trip failed for:\n{}", text);
+    }
+
+    #[test]
+    fn every_opcode_round_trips() {
+        let mut rng = XorShift(0x5EED_CAFE_F00D_BEEF);
+        for opcode in OpCode::variant_iter() {
+            let bc: Vec<u8> = (0..64)
+                .flat_map(|_| encode_with_random_args(opcode, &mut rng, 0))
+                .collect();
+            assert_round_trips(&bc);
+        }
+    }
+
+    #[test]
+    fn program_with_every_opcode_round_trips() {
+        let mut rng = XorShift(0x0123_4567_89AB_CDEF);
+        let mut bc = Vec::new();
+        let mut starts = vec![0u64];
+        for opcode in OpCode::variant_iter() {
+            let target = starts[(rng.next() % starts.len() as u64) as usize];
+            bc.extend(encode_with_random_args(opcode, &mut rng, target));
+            starts.push(bc.len() as u64);
+        }
+
+        assert_round_trips(&bc);
+    }
+
+    #[test]
+    fn labels_are_synthesized_for_targets() {
+        let bc = [
+            LoadImmediateI64Instruction::encode((R!(1), 5i64)),
+            JumpIfInstruction::encode((22u64, R!(1))),
+            HaltInstruction::encode((0,)),
+            CallFunctionInstruction::encode((0u64,)),
+        ]
+        .concat();
+
+        let text = disassemble(&bc).unwrap();
+        let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+        assert_eq!(lines[0], "L_0000:");
+        assert!(lines[1].starts_with("    LoadImmediateI64 R1 5 "));
+        assert!(lines[1].ends_with("; 0x0000"));
+        assert!(lines[2].starts_with("    JumpIf L_0016 R1 "));
+        assert_eq!(lines[3], "L_0016:");
+        assert!(lines[4].ends_with("; 0x0016"));
+        assert!(lines[5].starts_with("    CallFunction L_0000 "));
+
+        assert_round_trips(&bc);
+    }
+
+    #[test]
+    fn targets_off_instruction_boundaries_stay_numeric() {
+        let bc = [
+            JumpInstruction::encode((3u64,)),
+            JumpInstruction::encode((u64::MAX,)),
+        ]
+        .concat();
+
+        let text = disassemble(&bc).unwrap();
+        assert!(!text.contains("L_"));
+        assert!(text.contains("Jump 3 "));
+        assert_round_trips(&bc);
+    }
+
+    #[test]
+    fn float_edge_cases_round_trip() {
+        let bc = [
+            LoadImmediateF64Instruction::encode((R!(1), f64::from_bits(0x7FF8_0000_DEAD_BEEF))),
+            LoadImmediateF64Instruction::encode((R!(1), -0.0)),
+            LoadImmediateF64Instruction::encode((R!(1), f64::NEG_INFINITY)),
+            LoadImmediateF64Instruction::encode((R!(1), f64::MIN_POSITIVE / 3.0)),
+            LoadImmediateF32Instruction::encode((R!(1), f32::from_bits(0xFFC0_0001))),
+            LoadImmediateF32Instruction::encode((R!(1), 0.1)),
+        ]
+        .concat();
+
+        assert_round_trips(&bc);
+    }
+
+    #[test]
+    fn undecodable_bytecode_is_reported() {
+        let mut bc = HaltInstruction::encode((0,));
+        bc.extend_from_slice(&[0xFF, 0xFF]);
+        let err = disassemble(&bc).unwrap_err();
+        assert_eq!(err.offset, 10);
+        assert_eq!(err.kind, VerificationErrorKind::UnknownOpcode(0xFFFF));
+
+        let err = disassemble(&bc[..5]).unwrap_err();
+        assert_eq!(
+            err.kind,
+            VerificationErrorKind::TruncatedInstruction {
+                expected: 10,
+                available: 5
+            }
+        );
+    }
+}
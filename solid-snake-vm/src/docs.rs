@@ -73,6 +73,17 @@ pub struct InstructionDocsEntry {
     pub tags: Box<[InstructionTag]>,
 }
 
+impl InstructionDocsEntry {
+    /// Byte offset of each argument within the instruction's argument bytes, paired with its docs.
+    pub fn arg_layout(&self) -> impl Iterator<Item = (usize, &ArgDocsEntry)> {
+        self.args.iter().scan(0, |offset, arg| {
+            let arg_offset = *offset;
+            *offset += arg.bytes;
+            Some((arg_offset, arg))
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Docs {
     pub instructions: Vec<InstructionDocsEntry>,
@@ -1,8 +1,12 @@
 use std::{error::Error, fmt};
 
-use crate::opcodes::DecodedInstruction;
+use crate::bytecode_module::BytecodeModuleError;
+use crate::opcodes::{DecodedInstruction, OpCode};
 
+use super::interpreted::debugger::WatchHit;
 use super::interpreted::implimentation::VmInterpretedExecutor;
+use super::interpreted::snapshot::SnapshotError;
+use super::interpreted::verifier::VerificationError;
 
 #[derive(Debug)]
 pub enum VmExecutionError {
@@ -13,6 +17,51 @@ pub enum VmExecutionError {
     SegmentationFault,
     InvalidOpCode,
     UnexpectedEOF,
+    InvalidModule(BytecodeModuleError),
+    InvalidSnapshot(SnapshotError),
+    VerificationFailed(Vec<VerificationError>),
+    InvalidRegister(usize),
+    InvalidFrame(usize),
+    NonAtomicSharedAccess(usize),
+    NotSharedSegment(usize),
+    MisalignedAtomicAccess(usize),
+    InvalidThreadHandle(usize),
+    ThreadPanicked,
+    ThreadFailed {
+        handle: usize,
+        error: Box<VmExecutionError>,
+    },
+    InvalidChannelHandle(usize),
+    ChannelDisconnected,
+    ArithmeticTrap {
+        code: i64,
+        pc: usize,
+        opcode: OpCode,
+    },
+    OutOfFuel {
+        pc: usize,
+    },
+    Interrupted {
+        pc: usize,
+    },
+    WatchpointHit(Box<WatchHit>),
+    UseAfterFree(usize),
+    DoubleFree(usize),
+    OutOfMemory {
+        requested: usize,
+        limit: usize,
+    },
+    UnresolvedNatives(Vec<u32>),
+    NativeAlreadyRegistered {
+        id: u32,
+        name: String,
+    },
+    InvalidNativeArity {
+        name: String,
+        arity: u8,
+    },
+    CapabilityDenied(String),
+    InvalidFileHandle(usize),
 }
 
 impl Error for VmExecutionError {}
@@ -27,10 +76,103 @@ impl fmt::Display for VmExecutionError {
             VmExecutionError::SegmentationFault => write!(f, "Segmantation Fault"),
             VmExecutionError::InvalidOpCode => write!(f, "Invalid OpCode"),
             VmExecutionError::UnexpectedEOF => write!(f, "Unexpected End of File"),
+            VmExecutionError::InvalidModule(err) => write!(f, "Invalid module: {}", err),
+            VmExecutionError::InvalidSnapshot(err) => write!(f, "Invalid snapshot: {}", err),
+            VmExecutionError::VerificationFailed(errors) => {
+                write!(f, "Bytecode verification failed")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            VmExecutionError::InvalidRegister(idx) => write!(f, "Invalid register R{}", idx),
+            VmExecutionError::InvalidFrame(n) => write!(f, "No call frame {}", n),
+            VmExecutionError::NonAtomicSharedAccess(idx) => {
+                write!(f, "Non-atomic access to shared segment {}", idx)
+            }
+            VmExecutionError::NotSharedSegment(idx) => {
+                write!(f, "Atomic access to section {} which is not shared", idx)
+            }
+            VmExecutionError::MisalignedAtomicAccess(offset) => {
+                write!(f, "Misaligned atomic access at offset {}", offset)
+            }
+            VmExecutionError::InvalidThreadHandle(idx) => {
+                write!(f, "Invalid or already joined thread handle {}", idx)
+            }
+            VmExecutionError::ThreadPanicked => write!(f, "Spawned thread panicked"),
+            VmExecutionError::ThreadFailed { handle, error } => {
+                write!(f, "Spawned thread {} failed: {}", handle, error)
+            }
+            VmExecutionError::InvalidChannelHandle(idx) => {
+                write!(f, "Invalid channel handle {}", idx)
+            }
+            VmExecutionError::ChannelDisconnected => {
+                write!(f, "Channel is empty and no other executor can send to it")
+            }
+            VmExecutionError::ArithmeticTrap { code, pc, opcode } => {
+                write!(
+                    f,
+                    "Arithmetic trap: error code {} raised by {:?} at pc {}",
+                    code, opcode, pc
+                )
+            }
+            VmExecutionError::OutOfFuel { pc } => write!(f, "Out of fuel at pc {}", pc),
+            VmExecutionError::Interrupted { pc } => write!(f, "Interrupted at pc {}", pc),
+            VmExecutionError::WatchpointHit(hit) => write!(
+                f,
+                "Watchpoint: {:?} of section {:#x} bytes {}..{} at pc {}, {:02x?} -> {:02x?}",
+                hit.kind, hit.section, hit.range.start, hit.range.end, hit.pc, hit.old, hit.new
+            ),
+            VmExecutionError::UseAfterFree(handle) => {
+                write!(f, "Use of freed heap section (handle {:#x})", handle)
+            }
+            VmExecutionError::DoubleFree(handle) => {
+                write!(f, "Heap section freed twice (handle {:#x})", handle)
+            }
+            VmExecutionError::OutOfMemory { requested, limit } => {
+                write!(
+                    f,
+                    "Out of memory: requested {} with a limit of {}",
+                    requested, limit
+                )
+            }
+            VmExecutionError::UnresolvedNatives(ids) => {
+                write!(f, "Unresolved native functions: {:?}", ids)
+            }
+            VmExecutionError::NativeAlreadyRegistered { id, name } => {
+                write!(
+                    f,
+                    "Native function id {} or name {} is already registered",
+                    id, name
+                )
+            }
+            VmExecutionError::InvalidNativeArity { name, arity } => write!(
+                f,
+                "Native function {} takes {} arguments, at most 3 fit in R1..R3",
+                name, arity
+            ),
+            VmExecutionError::CapabilityDenied(what) => {
+                write!(f, "No capability granted for {}", what)
+            }
+            VmExecutionError::InvalidFileHandle(idx) => {
+                write!(f, "Invalid or already closed file handle {}", idx)
+            }
         }
     }
 }
 
+impl From<BytecodeModuleError> for VmExecutionError {
+    fn from(err: BytecodeModuleError) -> Self {
+        VmExecutionError::InvalidModule(err)
+    }
+}
+
+impl From<SnapshotError> for VmExecutionError {
+    fn from(err: SnapshotError) -> Self {
+        VmExecutionError::InvalidSnapshot(err)
+    }
+}
+
 pub trait VmExecutorExt {
     fn preprocess_bytecode(
         &mut self,
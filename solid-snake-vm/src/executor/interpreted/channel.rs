@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::executor::ext::VmExecutionError;
+
+use super::implimentation::VmMemorySection;
+use super::interrupt::InterruptHandle;
+
+// How often a blocked receiver checks for an interrupt and for senders that went away.
+const RECV_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A queue of heap sections that executors hand to each other. Every executor holding a
+/// `ChannelHandle` to it counts as a sender, so a receiver that is the only holder left knows
+/// nothing more can arrive.
+#[derive(Debug, Default)]
+pub struct VmChannel {
+    queue: Mutex<VecDeque<VmMemorySection>>,
+    ready: Condvar,
+    senders: AtomicUsize,
+}
+
+impl VmChannel {
+    pub fn send(&self, section: VmMemorySection) {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push_back(section);
+        self.ready.notify_one();
+    }
+
+    pub fn try_recv(&self) -> Option<VmMemorySection> {
+        self.queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop_front()
+    }
+
+    /// Waits for a section. Fails once the queue is empty and the receiver holds the only handle
+    /// left, or with `Interrupted { pc }` once `interrupt` is triggered.
+    pub fn recv(
+        &self,
+        interrupt: &InterruptHandle,
+        pc: usize,
+    ) -> Result<VmMemorySection, VmExecutionError> {
+        let mut queue = self
+            .queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        loop {
+            if let Some(section) = queue.pop_front() {
+                return Ok(section);
+            }
+            if self.senders.load(Ordering::Acquire) <= 1 {
+                return Err(VmExecutionError::ChannelDisconnected);
+            }
+            if interrupt.is_interrupted() {
+                return Err(VmExecutionError::Interrupted { pc });
+            }
+            queue = self
+                .ready
+                .wait_timeout(queue, RECV_POLL_INTERVAL)
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .0;
+        }
+    }
+}
+
+/// An executor's hold on a channel. The channel counts a sender for every handle until it is
+/// dropped, which happens when the executor closes the channel or its program ends.
+#[derive(Debug)]
+pub struct ChannelHandle(Arc<VmChannel>);
+
+impl ChannelHandle {
+    pub fn new(channel: Arc<VmChannel>) -> Self {
+        channel.senders.fetch_add(1, Ordering::AcqRel);
+        Self(channel)
+    }
+
+    pub fn channel(&self) -> &Arc<VmChannel> {
+        &self.0
+    }
+}
+
+impl Clone for ChannelHandle {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl Drop for ChannelHandle {
+    fn drop(&mut self) {
+        self.0.senders.fetch_sub(1, Ordering::AcqRel);
+        self.0.ready.notify_all();
+    }
+}
@@ -0,0 +1,617 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::executor::ext::VmExecutionError;
+use crate::opcodes::DecodedInstruction;
+
+use super::implimentation::{
+    CallFrame, ExecutableInstructionFn, RegisterFile, VmHeapExt, VmInterpretedExecutor,
+    VmMemorySectionExt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+}
+
+/// Only stop at a breakpoint when a register of the current frame compares true against `value`.
+/// The register is read as an `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakCondition {
+    pub register: usize,
+    pub op: CompareOp,
+    pub value: i64,
+}
+
+impl BreakCondition {
+    fn holds(&self, registers: &RegisterFile) -> bool {
+        let Some(&raw) = registers.raw.get(self.register) else {
+            return false;
+        };
+        let current = raw as i64;
+        match self.op {
+            CompareOp::Equal => current == self.value,
+            CompareOp::NotEqual => current != self.value,
+            CompareOp::LessThan => current < self.value,
+            CompareOp::LessThanOrEqual => current <= self.value,
+            CompareOp::GreaterThan => current > self.value,
+            CompareOp::GreaterThanOrEqual => current >= self.value,
+        }
+    }
+}
+
+/// Breakpoints by instruction index, the same pc that errors like `OutOfFuel` report.
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    points: BTreeMap<usize, Option<BreakCondition>>,
+    /// Where the last debugger command stopped. A breakpoint there was already reported, so
+    /// resuming runs its instruction instead of stopping again.
+    resumed_from: Option<usize>,
+}
+
+impl Breakpoints {
+    pub fn set(&mut self, pc: usize) {
+        self.points.insert(pc, None);
+    }
+
+    pub fn set_conditional(&mut self, pc: usize, condition: BreakCondition) {
+        self.points.insert(pc, Some(condition));
+    }
+
+    pub fn remove(&mut self, pc: usize) -> bool {
+        self.points.remove(&pc).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, Option<&BreakCondition>)> {
+        self.points
+            .iter()
+            .map(|(&pc, condition)| (pc, condition.as_ref()))
+    }
+
+    fn hit(&self, pc: usize, registers: &RegisterFile) -> bool {
+        match self.points.get(&pc) {
+            Some(Some(condition)) => condition.holds(registers),
+            Some(None) => true,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Reads and writes, only used when setting a watchpoint.
+    Access,
+}
+
+impl WatchKind {
+    fn covers(self, access: WatchKind) -> bool {
+        self == WatchKind::Access || self == access
+    }
+}
+
+/// Pauses execution after an instruction reads or writes bytes `range` of the section `section`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub section: usize,
+    pub range: Range<usize>,
+    pub kind: WatchKind,
+}
+
+/// Checked by the load, store, `Memcpy` and `MemSet` handlers.
+#[derive(Debug, Clone, Default)]
+pub struct Watchpoints {
+    points: Vec<Watchpoint>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.points.push(watchpoint);
+    }
+
+    pub fn remove(&mut self, watchpoint: &Watchpoint) -> bool {
+        let len = self.points.len();
+        self.points.retain(|point| point != watchpoint);
+        self.points.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.points.iter()
+    }
+
+    /// The watched bytes an access touches, if any watchpoint covers it.
+    fn hit(&self, section: usize, access: Range<usize>, kind: WatchKind) -> Option<Range<usize>> {
+        self.points
+            .iter()
+            .filter(|point| point.section == section && point.kind.covers(kind))
+            .map(|point| point.range.start.max(access.start)..point.range.end.min(access.end))
+            .find(|overlap| overlap.start < overlap.end)
+    }
+}
+
+/// A watched access. The instruction that made it has completed, so execution can carry on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The instruction that made the access.
+    pub pc: usize,
+    pub section: usize,
+    /// The watched bytes that were accessed.
+    pub range: Range<usize>,
+    pub kind: WatchKind,
+    /// Bytes in `range` before and after the access, the same for reads.
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// A write that touched watched bytes, waiting for the new bytes.
+pub struct PendingWrite {
+    range: Range<usize>,
+    old: Vec<u8>,
+}
+
+/// Why a debugger command gave control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// About to run the instruction at a breakpoint.
+    Breakpoint(usize),
+    /// The step finished, the next instruction to run is at this pc.
+    Stepped(usize),
+    Watchpoint(Box<WatchHit>),
+    Halted(i64),
+}
+
+impl VmInterpretedExecutor {
+    /// Runs until a breakpoint is reached or the program halts. A breakpoint at the current pc
+    /// stops before anything runs, unless the last debugger command already stopped there.
+    pub fn debug_continue(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<StopReason, VmExecutionError> {
+        self.debug_run_while(processed_bytecode, |_| true)
+    }
+
+    /// Runs a single instruction.
+    pub fn debug_step(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<StopReason, VmExecutionError> {
+        let stop = match self.step_processeded_bytecode(processed_bytecode) {
+            Ok(Some(exit_code)) => Ok(StopReason::Halted(exit_code)),
+            Ok(None) => Ok(StopReason::Stepped(self.get_program_counter()?)),
+            Err(VmExecutionError::WatchpointHit(hit)) => Ok(StopReason::Watchpoint(hit)),
+            Err(err) => Err(err),
+        };
+        self.breakpoints_mut().resumed_from = Some(self.get_program_counter()?);
+        stop
+    }
+
+    /// Like `debug_step`, but runs a `CallFunction` until the call returns. Stops early at breakpoints
+    /// inside the call.
+    pub fn debug_step_over(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<StopReason, VmExecutionError> {
+        let is_call = matches!(
+            processed_bytecode.get(self.get_program_counter()?),
+            Some((DecodedInstruction::CallFunction(_), _))
+        );
+        if !is_call {
+            return self.debug_step(processed_bytecode);
+        }
+
+        let depth = self.stack_top;
+        self.debug_run_while(processed_bytecode, |executor| executor.stack_top > depth)
+    }
+
+    /// Runs until the current function returns to its caller's `return_address`. Stops early at
+    /// breakpoints, and runs to the end when there is no caller.
+    pub fn debug_step_out(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<StopReason, VmExecutionError> {
+        let depth = self.stack_top;
+        self.debug_run_while(processed_bytecode, |executor| executor.stack_top >= depth)
+    }
+
+    fn debug_run_while(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+        keep_going: impl Fn(&VmInterpretedExecutor) -> bool,
+    ) -> Result<StopReason, VmExecutionError> {
+        let pc = self.get_program_counter()?;
+        let resumed = self.breakpoints_mut().resumed_from.take() == Some(pc);
+        let stop = if !resumed && self.breakpoints().hit(pc, self.registers()) {
+            Ok(StopReason::Breakpoint(pc))
+        } else {
+            self.debug_run_until_stop(processed_bytecode, keep_going)
+        };
+        self.breakpoints_mut().resumed_from = Some(self.get_program_counter()?);
+        stop
+    }
+
+    fn debug_run_until_stop(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+        keep_going: impl Fn(&VmInterpretedExecutor) -> bool,
+    ) -> Result<StopReason, VmExecutionError> {
+        loop {
+            match self.step_processeded_bytecode(processed_bytecode) {
+                Ok(Some(exit_code)) => return Ok(StopReason::Halted(exit_code)),
+                Ok(None) => {}
+                Err(VmExecutionError::WatchpointHit(hit)) => {
+                    return Ok(StopReason::Watchpoint(hit));
+                }
+                Err(err) => return Err(err),
+            }
+            let pc = self.get_program_counter()?;
+            if !keep_going(self) {
+                return Ok(StopReason::Stepped(pc));
+            }
+            if self.breakpoints().hit(pc, self.registers()) {
+                return Ok(StopReason::Breakpoint(pc));
+            }
+        }
+    }
+
+    /// Fails with `WatchpointHit` if a read of `len` bytes at `offset` in `section` was watched.
+    #[inline(always)]
+    pub fn watch_read(
+        &self,
+        section: usize,
+        offset: usize,
+        len: usize,
+    ) -> Result<(), VmExecutionError> {
+        if self.watchpoints().is_empty() {
+            return Ok(());
+        }
+        let Some(range) =
+            self.watchpoints()
+                .hit(section, offset..offset.saturating_add(len), WatchKind::Read)
+        else {
+            return Ok(());
+        };
+
+        let bytes = self.watched_bytes(section, &range)?;
+        self.watch_hit(section, range, WatchKind::Read, bytes.clone(), bytes)
+    }
+
+    /// Call before a write of `len` bytes at `offset` in `section`, and pass the result to
+    /// `watch_write_done` once the bytes are written.
+    #[inline(always)]
+    pub fn watch_write(
+        &self,
+        section: usize,
+        offset: usize,
+        len: usize,
+    ) -> Result<Option<PendingWrite>, VmExecutionError> {
+        if self.watchpoints().is_empty() {
+            return Ok(None);
+        }
+        let Some(range) = self.watchpoints().hit(
+            section,
+            offset..offset.saturating_add(len),
+            WatchKind::Write,
+        ) else {
+            return Ok(None);
+        };
+
+        let old = self.watched_bytes(section, &range)?;
+        Ok(Some(PendingWrite { range, old }))
+    }
+
+    #[inline(always)]
+    pub fn watch_write_done(
+        &self,
+        section: usize,
+        pending: Option<PendingWrite>,
+    ) -> Result<(), VmExecutionError> {
+        let Some(PendingWrite { range, old }) = pending else {
+            return Ok(());
+        };
+
+        let new = self.watched_bytes(section, &range)?;
+        self.watch_hit(section, range, WatchKind::Write, old, new)
+    }
+
+    fn watched_bytes(
+        &self,
+        section: usize,
+        range: &Range<usize>,
+    ) -> Result<Vec<u8>, VmExecutionError> {
+        Ok(self
+            .heap()
+            .section(section)?
+            .bytes_n_with_offset(range.len(), range.start)?
+            .to_vec())
+    }
+
+    fn watch_hit(
+        &self,
+        section: usize,
+        range: Range<usize>,
+        kind: WatchKind,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    ) -> Result<(), VmExecutionError> {
+        // The program counter already points past the accessing instruction.
+        let pc = self.get_program_counter()? - 1;
+        Err(VmExecutionError::WatchpointHit(Box::new(WatchHit {
+            pc,
+            section,
+            range,
+            kind,
+            old,
+            new,
+        })))
+    }
+
+    /// Number of active call frames, the current one included.
+    pub fn frame_count(&self) -> usize {
+        self.stack_top + 1
+    }
+
+    /// Frame 0 is the current function, frame 1 its caller and so on.
+    pub fn frame(&self, n: usize) -> Result<&CallFrame, VmExecutionError> {
+        self.stack_top
+            .checked_sub(n)
+            .map(|idx| &self.frame_stack[idx])
+            .ok_or(VmExecutionError::InvalidFrame(n))
+    }
+
+    pub fn frame_registers(&self, n: usize) -> Result<&RegisterFile, VmExecutionError> {
+        Ok(&self.frame(n)?.registers)
+    }
+
+    pub fn frame_registers_mut(&mut self, n: usize) -> Result<&mut RegisterFile, VmExecutionError> {
+        let idx = self
+            .stack_top
+            .checked_sub(n)
+            .ok_or(VmExecutionError::InvalidFrame(n))?;
+        Ok(&mut self.frame_stack[idx].registers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreakCondition, CompareOp, StopReason, WatchHit, WatchKind, Watchpoint};
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{
+        ExecutableInstructionFn, RegisterFileExt, VmHeapExt, VmInterpretedExecutor,
+        VmMemorySectionExt,
+    };
+    use crate::executor::interpreted::opcode_impl::all::*;
+    use crate::opcodes::DecodedInstruction;
+
+    fn program() -> Vec<u8> {
+        [
+            LoadImmediateU64Instruction::encode((R!(1), 5u64)), // 0: 0..11
+            CallFunctionInstruction::encode((31u64,)),          // 1: 11..21
+            HaltInstruction::encode((0,)),                      // 2: 21..31
+            // function at offset 31, counts R0 up to R1
+            IncrementU8Instruction::encode((R!(0), 1u8)), // 3: 31..35
+            LessThanU8Instruction::encode((R!(2), R!(0), R!(1))), // 4: 35..39
+            JumpIfInstruction::encode((31u64, R!(2))),    // 5: 39..50
+            ReturnInstruction::encode(()),                // 6
+        ]
+        .concat()
+    }
+
+    fn load() -> (
+        VmInterpretedExecutor,
+        Vec<(DecodedInstruction, ExecutableInstructionFn)>,
+    ) {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program()).unwrap();
+        (vm, bc)
+    }
+
+    #[test]
+    fn breakpoints_and_steps() {
+        let (mut vm, bc) = load();
+        vm.breakpoints_mut().set(4);
+
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(4));
+        assert_eq!(vm.frame_count(), 2);
+        assert_eq!(vm.debug_step(&bc).unwrap(), StopReason::Stepped(5));
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(4));
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 2);
+
+        vm.breakpoints_mut().clear();
+        assert_eq!(vm.debug_step_out(&bc).unwrap(), StopReason::Stepped(2));
+        assert_eq!(vm.frame_count(), 1);
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Halted(0));
+    }
+
+    #[test]
+    fn breakpoint_at_current_pc() {
+        let (mut vm, bc) = load();
+        vm.breakpoints_mut().set(0);
+        vm.breakpoints_mut().set(3);
+
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(0));
+        assert_eq!(vm.get_program_counter().unwrap(), 0);
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(3));
+
+        // Stepping onto a breakpoint reports it, continuing from there does not stop again.
+        assert_eq!(vm.debug_step(&bc).unwrap(), StopReason::Stepped(4));
+        assert_eq!(vm.debug_step(&bc).unwrap(), StopReason::Stepped(5));
+        assert_eq!(vm.debug_step(&bc).unwrap(), StopReason::Stepped(3));
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(3));
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 2);
+    }
+
+    #[test]
+    fn conditional_breakpoint() {
+        let (mut vm, bc) = load();
+        vm.breakpoints_mut().set_conditional(
+            4,
+            BreakCondition {
+                register: 0,
+                op: CompareOp::GreaterThanOrEqual,
+                value: 3,
+            },
+        );
+
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(4));
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 3);
+    }
+
+    #[test]
+    fn step_over_call() {
+        let (mut vm, bc) = load();
+
+        assert_eq!(vm.debug_step_over(&bc).unwrap(), StopReason::Stepped(1));
+        assert_eq!(vm.debug_step_over(&bc).unwrap(), StopReason::Stepped(2));
+        let result: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(result, 5);
+    }
+
+    #[test]
+    fn edit_caller_frame() {
+        let (mut vm, bc) = load();
+        vm.breakpoints_mut().set(3);
+
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Breakpoint(3));
+        vm.frame_registers_mut(1).unwrap().raw[7] = 42;
+        vm.frame_registers_mut(0).unwrap().raw[1] = 2;
+        assert!(matches!(
+            vm.frame_registers(2),
+            Err(VmExecutionError::InvalidFrame(2))
+        ));
+
+        vm.breakpoints_mut().clear();
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Halted(0));
+        let caller: u64 = vm.registers().get_register_value(R!(7)).unwrap();
+        let result: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!((caller, result), (42, 2));
+    }
+
+    fn memory_program() -> (
+        VmInterpretedExecutor,
+        Vec<(DecodedInstruction, ExecutableInstructionFn)>,
+    ) {
+        let program = [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            StoreIndirectWithOffsetU8Instruction::encode((R!(0), R!(2), R!(3))),
+            LoadIndirectWithOffsetU8Instruction::encode((R!(4), R!(0), R!(3))),
+            MemSetInstruction::encode((R!(0), R!(5), R!(1))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.registers_mut().set_register_value(R!(1), 8u64).unwrap();
+        vm.registers_mut().set_register_value(R!(2), 7u8).unwrap();
+        vm.registers_mut().set_register_value(R!(3), 3u64).unwrap();
+        vm.registers_mut().set_register_value(R!(5), 9u8).unwrap();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        (vm, bc)
+    }
+
+    #[test]
+    fn write_watchpoint() {
+        let (mut vm, bc) = memory_program();
+        vm.watchpoints_mut().add(Watchpoint {
+            section: 0,
+            range: 2..4,
+            kind: WatchKind::Write,
+        });
+
+        let Err(VmExecutionError::WatchpointHit(hit)) = vm.execute_processeded_bytecode(&bc) else {
+            panic!("expected a watchpoint hit");
+        };
+        assert_eq!(
+            *hit,
+            WatchHit {
+                pc: 1,
+                section: 0,
+                range: 3..4,
+                kind: WatchKind::Write,
+                old: vec![0],
+                new: vec![7],
+            }
+        );
+
+        // The load is not watched, MemSet is.
+        let Err(VmExecutionError::WatchpointHit(hit)) = vm.resume_processeded_bytecode(&bc) else {
+            panic!("expected a watchpoint hit");
+        };
+        assert_eq!(
+            (hit.pc, hit.range, hit.old, hit.new),
+            (3, 2..4, vec![0, 7], vec![9, 9])
+        );
+
+        assert_eq!(vm.resume_processeded_bytecode(&bc).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_watchpoint_stops_debugger() {
+        let (mut vm, bc) = memory_program();
+        vm.watchpoints_mut().add(Watchpoint {
+            section: 0,
+            range: 0..8,
+            kind: WatchKind::Read,
+        });
+
+        let StopReason::Watchpoint(hit) = vm.debug_continue(&bc).unwrap() else {
+            panic!("expected a watchpoint stop");
+        };
+        assert_eq!((hit.pc, hit.kind, hit.new), (2, WatchKind::Read, vec![7]));
+        assert_eq!(vm.debug_continue(&bc).unwrap(), StopReason::Halted(0));
+    }
+
+    #[test]
+    fn memcpy_reports_watched_read_before_write() {
+        let program = [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateInstruction::encode((R!(2), R!(1))),
+            MemSetInstruction::encode((R!(0), R!(5), R!(1))),
+            MemcpyInstruction::encode((R!(2), R!(3), R!(0), R!(3), R!(1))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.registers_mut().set_register_value(R!(1), 4u64).unwrap();
+        vm.registers_mut().set_register_value(R!(5), 9u8).unwrap();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.watchpoints_mut().add(Watchpoint {
+            section: 0,
+            range: 0..4,
+            kind: WatchKind::Read,
+        });
+        vm.watchpoints_mut().add(Watchpoint {
+            section: 1,
+            range: 0..4,
+            kind: WatchKind::Write,
+        });
+
+        let StopReason::Watchpoint(hit) = vm.debug_continue(&bc).unwrap() else {
+            panic!("expected a watchpoint stop");
+        };
+        assert_eq!((hit.pc, hit.section, hit.kind), (3, 0, WatchKind::Read));
+        // The copy still happened.
+        let copied = vm.heap().section(1).unwrap().dereference_bytes().unwrap();
+        assert_eq!(copied, [9; 4]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::docs::InstructionTag;
+use crate::opcodes::OpCode;
+
+/// How much fuel each opcode consumes. Without weights every instruction costs 1.
+#[derive(Debug, Clone)]
+pub struct FuelWeights {
+    costs: Box<[u64]>,
+}
+
+impl FuelWeights {
+    /// Gives each opcode the largest weight among its tags. Every instruction costs at least 1,
+    /// so a loop always burns fuel.
+    pub fn from_tags(weight: impl Fn(InstructionTag) -> u64) -> Self {
+        let len = OpCode::variant_iter()
+            .map(|opcode| opcode as usize + 1)
+            .max()
+            .unwrap_or_default();
+        let mut costs = vec![1; len];
+
+        for opcode in OpCode::variant_iter() {
+            let doc = OpCode::get_doc(opcode);
+            costs[opcode as usize] = doc
+                .tags
+                .iter()
+                .copied()
+                .map(&weight)
+                .max()
+                .unwrap_or(1)
+                .max(1);
+        }
+
+        Self {
+            costs: costs.into_boxed_slice(),
+        }
+    }
+
+    pub fn cost(&self, opcode: OpCode) -> u64 {
+        self.costs[opcode as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FuelWeights;
+    use crate::R;
+    use crate::docs::InstructionTag;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{RegisterFileExt, VmInterpretedExecutor};
+    use crate::executor::interpreted::opcode_impl::all::*;
+    use crate::opcodes::OpCode;
+
+    fn infinite_loop() -> Vec<u8> {
+        [
+            IncrementU8Instruction::encode((R!(0), 1u8)), // 0..4
+            JumpInstruction::encode((0u64,)),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn runs_out_of_fuel_in_loop() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&infinite_loop()).unwrap();
+        vm.set_fuel(Some(5));
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+
+        assert!(matches!(err, VmExecutionError::OutOfFuel { pc: 1 }));
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 3);
+        assert_eq!(vm.remaining_fuel(), Some(0));
+    }
+
+    #[test]
+    fn resumes_after_refuel() {
+        let program = [
+            IncrementU8Instruction::encode((R!(0), 1u8)),
+            IncrementU8Instruction::encode((R!(0), 1u8)),
+            IncrementU8Instruction::encode((R!(0), 1u8)),
+            HaltInstruction::encode((7,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_fuel(Some(2));
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+        assert!(matches!(err, VmExecutionError::OutOfFuel { pc: 2 }));
+
+        vm.add_fuel(1);
+        assert_eq!(vm.resume_processeded_bytecode(&bc).unwrap(), 7);
+
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 3);
+    }
+
+    #[test]
+    fn weights_from_tags() {
+        let weights = FuelWeights::from_tags(|tag| match tag {
+            InstructionTag::Arithmetic => 10,
+            _ => 0,
+        });
+        assert_eq!(weights.cost(OpCode::IncrementU8), 10);
+        assert_eq!(weights.cost(OpCode::Jump), 1);
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&infinite_loop()).unwrap();
+        vm.set_fuel(Some(25));
+        vm.set_fuel_weights(Some(weights));
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+
+        assert!(matches!(err, VmExecutionError::OutOfFuel { pc: 0 }));
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 2);
+    }
+
+    #[test]
+    fn threads_share_the_budget() {
+        let program = [
+            SpawnInstruction::encode((26u64, R!(1), R!(2))), // 0..12
+            JoinInstruction::encode((R!(2), R!(3))),         // 12..16
+            HaltInstruction::encode((0,)),                   // 16..26
+            // thread function at offset 26, counts R0 up to R1
+            IncrementU8Instruction::encode((R!(0), 1u8)), // 26..30
+            LessThanU8Instruction::encode((R!(4), R!(0), R!(1))), // 30..35
+            JumpIfInstruction::encode((26u64, R!(4))),    // 35..46
+            ReturnInstruction::encode(()),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.registers_mut().set_register_value(R!(1), 10u8).unwrap();
+        vm.set_fuel(Some(20));
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+
+        // The thread ran out of the fuel left after Spawn and Join, not a fresh copy of it.
+        match err {
+            VmExecutionError::ThreadFailed { handle, error } => {
+                assert_eq!(handle, 0);
+                assert!(matches!(*error, VmExecutionError::OutOfFuel { .. }));
+            }
+            other => panic!("Expected the thread to fail, got {:?}", other),
+        }
+        assert_eq!(vm.remaining_fuel(), Some(0));
+        assert_eq!(vm.get_program_counter().unwrap(), 1);
+
+        // Resuming joins again, the thread carries on and its result still arrives.
+        vm.add_fuel(100);
+        assert_eq!(vm.resume_processeded_bytecode(&bc).unwrap(), 0);
+        let result: u64 = vm.registers().get_register_value(R!(3)).unwrap();
+        assert_eq!(result, 10);
+    }
+}
@@ -0,0 +1,382 @@
+#[cfg(unix)]
+use std::ffi::{CString, OsStr};
+use std::fmt;
+use std::fs::File;
+#[cfg(not(unix))]
+use std::fs::OpenOptions;
+use std::io::{self, BufRead};
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use crate::executor::ext::VmExecutionError;
+
+/// How `FileOpen` opens a file, taken from its mode register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Read = 0,
+    /// Creates the file or truncates it.
+    Write = 1,
+    /// Creates the file or appends to it.
+    Append = 2,
+}
+
+impl FileMode {
+    pub fn from_u64(mode: u64) -> Option<Self> {
+        match mode {
+            0 => Some(FileMode::Read),
+            1 => Some(FileMode::Write),
+            2 => Some(FileMode::Append),
+            _ => None,
+        }
+    }
+
+    fn writes(self) -> bool {
+        self != FileMode::Read
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DirectoryGrant {
+    root: PathBuf,
+    writable: bool,
+}
+
+/// A path `resolve` allowed, as the root of the grant it falls under and the components below it.
+#[derive(Debug)]
+struct ResolvedPath {
+    root: PathBuf,
+    relative: PathBuf,
+}
+
+/// What a program may reach outside the VM through the host I/O instructions. Nothing is granted
+/// by default, and an instruction that needs a missing grant fails with `CapabilityDenied`.
+#[derive(Default)]
+pub struct HostCapabilities {
+    input: Option<Mutex<Box<dyn BufRead + Send>>>,
+    args: Option<Vec<String>>,
+    directories: Vec<DirectoryGrant>,
+}
+
+impl fmt::Debug for HostCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HostCapabilities")
+            .field("input", &self.input.is_some())
+            .field("args", &self.args)
+            .field("directories", &self.directories)
+            .finish()
+    }
+}
+
+impl HostCapabilities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets `ReadLine` read from the process stdin.
+    pub fn with_stdin(self) -> Self {
+        self.with_input(io::BufReader::new(io::stdin()))
+    }
+
+    /// Lets `ReadLine` read from `input` instead of stdin.
+    pub fn with_input(mut self, input: impl BufRead + Send + 'static) -> Self {
+        self.input = Some(Mutex::new(Box::new(input)));
+        self
+    }
+
+    /// The arguments `ArgCount` and `ArgGet` see, usually not the host's own.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = Some(args);
+        self
+    }
+
+    /// Lets `FileOpen` read files anywhere below `dir`. Fails if `dir` does not exist.
+    pub fn with_read_dir(self, dir: impl AsRef<Path>) -> io::Result<Self> {
+        self.with_dir(dir.as_ref(), false)
+    }
+
+    /// Lets `FileOpen` read, create and write files anywhere below `dir`. Fails if `dir` does not exist.
+    pub fn with_write_dir(self, dir: impl AsRef<Path>) -> io::Result<Self> {
+        self.with_dir(dir.as_ref(), true)
+    }
+
+    fn with_dir(mut self, dir: &Path, writable: bool) -> io::Result<Self> {
+        self.directories.push(DirectoryGrant {
+            root: dir.canonicalize()?,
+            writable,
+        });
+        Ok(self)
+    }
+
+    /// Reads one line, including its newline. Returns an empty buffer at the end of the input.
+    pub fn read_line(&self) -> Result<io::Result<Vec<u8>>, VmExecutionError> {
+        let input = self
+            .input
+            .as_ref()
+            .ok_or_else(|| VmExecutionError::CapabilityDenied("input".to_string()))?;
+
+        let mut line = Vec::new();
+        let result = input
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .read_until(b'\n', &mut line);
+        Ok(result.map(|_| line))
+    }
+
+    pub fn args(&self) -> Result<&[String], VmExecutionError> {
+        self.args
+            .as_deref()
+            .ok_or_else(|| VmExecutionError::CapabilityDenied("arguments".to_string()))
+    }
+
+    /// Opens `path` if a directory grant covers it. A path outside every grant is denied, while
+    /// I/O failures inside a grant, like a missing file, are left for the program to handle.
+    pub fn open(&self, path: &str, mode: FileMode) -> Result<io::Result<File>, VmExecutionError> {
+        let path = self.resolve(path, mode)?;
+        Ok(open_resolved(&path, mode))
+    }
+
+    fn resolve(&self, path: &str, mode: FileMode) -> Result<ResolvedPath, VmExecutionError> {
+        let denied = || {
+            let access = if mode.writes() { "write" } else { "read" };
+            VmExecutionError::CapabilityDenied(format!("{} access to {}", access, path))
+        };
+
+        // The parent must exist to be resolved, the file itself may not when it is being created.
+        let requested = Path::new(path);
+        let Some(Component::Normal(name)) = requested.components().next_back() else {
+            return Err(denied());
+        };
+        let parent = match requested.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let resolved = parent.canonicalize().map_err(|_| denied())?.join(name);
+        // A symlink could point outside the grant, even a dangling one once the file is created
+        // through it, so none are followed.
+        match resolved.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => return Err(denied()),
+            Ok(_) => {
+                if resolved.canonicalize().ok().as_ref() != Some(&resolved) {
+                    return Err(denied());
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(_) => return Err(denied()),
+        }
+
+        self.directories
+            .iter()
+            .filter(|grant| grant.writable || !mode.writes())
+            .find_map(|grant| {
+                resolved
+                    .strip_prefix(&grant.root)
+                    .ok()
+                    .map(|relative| (grant, relative))
+            })
+            .filter(|(_, relative)| !relative.as_os_str().is_empty())
+            .map(|(grant, relative)| ResolvedPath {
+                root: grant.root.clone(),
+                relative: relative.to_path_buf(),
+            })
+            .ok_or_else(denied)
+    }
+}
+
+/// Opens a path `resolve` allowed. Starting from the grant's root, every directory below it and
+/// the file itself are opened relative to the one before without following symlinks, so a
+/// component swapped for a symlink after the check fails to open instead of escaping the grant.
+#[cfg(unix)]
+fn open_resolved(path: &ResolvedPath, mode: FileMode) -> io::Result<File> {
+    let flags = match mode {
+        FileMode::Read => libc::O_RDONLY,
+        FileMode::Write => libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+        FileMode::Append => libc::O_WRONLY | libc::O_CREAT | libc::O_APPEND,
+    };
+    let names = path.relative.iter().collect::<Vec<_>>();
+    let Some((name, directories)) = names.split_last() else {
+        return Err(io::ErrorKind::InvalidInput.into());
+    };
+
+    let mut dir = open_at(
+        libc::AT_FDCWD,
+        path.root.as_os_str(),
+        libc::O_RDONLY | libc::O_DIRECTORY,
+    )?;
+    for directory in directories {
+        dir = open_at(
+            dir.as_raw_fd(),
+            directory,
+            libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW,
+        )?;
+    }
+    open_at(dir.as_raw_fd(), name, flags | libc::O_NOFOLLOW).map(File::from)
+}
+
+#[cfg(unix)]
+fn open_at(dir: RawFd, name: &OsStr, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let name =
+        CString::new(name.as_bytes()).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    // `name` is a valid C string for the duration of the call, and a descriptor `openat`
+    // returns belongs to no one else.
+    let fd = unsafe {
+        libc::openat(
+            dir,
+            name.as_ptr(),
+            flags | libc::O_CLOEXEC,
+            0o666 as libc::c_uint,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Opens a path `resolve` allowed. Without `openat`, a directory below the grant's root that is
+/// swapped for a symlink between the check and the open is still followed.
+#[cfg(not(unix))]
+fn open_resolved(path: &ResolvedPath, mode: FileMode) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    match mode {
+        FileMode::Read => options.read(true),
+        FileMode::Write => options.write(true).create(true).truncate(true),
+        FileMode::Append => options.append(true).create(true),
+    };
+    options.open(path.root.join(&path.relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use super::{FileMode, HostCapabilities, open_resolved};
+    use crate::executor::ext::VmExecutionError;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solid-snake-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn nothing_granted_by_default() {
+        let host = HostCapabilities::new();
+
+        assert!(matches!(
+            host.read_line(),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+        assert!(matches!(
+            host.args(),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+        assert!(matches!(
+            host.open("Cargo.toml", FileMode::Read),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+    }
+
+    #[test]
+    fn read_only_directory() {
+        let dir = temp_dir("read-only");
+        std::fs::write(dir.join("data.txt"), "data").unwrap();
+        let host = HostCapabilities::new().with_read_dir(&dir).unwrap();
+
+        let path = dir.join("data.txt");
+        assert!(
+            host.open(path.to_str().unwrap(), FileMode::Read)
+                .unwrap()
+                .is_ok()
+        );
+        assert!(matches!(
+            host.open(path.to_str().unwrap(), FileMode::Write),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+
+        let escape = dir.join("..").join("data.txt");
+        assert!(matches!(
+            host.open(escape.to_str().unwrap(), FileMode::Read),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+
+        // Inside the grant, a missing file is an I/O error and not a denial.
+        let missing = dir.join("missing.txt");
+        assert!(
+            host.open(missing.to_str().unwrap(), FileMode::Read)
+                .unwrap()
+                .is_err()
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reads_lines_from_input() {
+        let host = HostCapabilities::new().with_input(Cursor::new("one\ntwo"));
+
+        assert_eq!(host.read_line().unwrap().unwrap(), b"one\n");
+        assert_eq!(host.read_line().unwrap().unwrap(), b"two");
+        assert_eq!(host.read_line().unwrap().unwrap(), b"");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_escape() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("symlinks");
+        let outside = temp_dir("symlinks-outside");
+        let host = HostCapabilities::new().with_write_dir(&dir).unwrap();
+
+        // A dangling link would create its target outside the grant.
+        let dangling = dir.join("dangling");
+        symlink(outside.join("created.txt"), &dangling).unwrap();
+        assert!(matches!(
+            host.open(dangling.to_str().unwrap(), FileMode::Write),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+        assert!(!outside.join("created.txt").exists());
+
+        // A file swapped for a link between the check and the open.
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        let swapped = dir.join("swapped.txt");
+        std::fs::write(&swapped, "data").unwrap();
+        let resolved = host
+            .resolve(swapped.to_str().unwrap(), FileMode::Write)
+            .unwrap();
+        std::fs::remove_file(&swapped).unwrap();
+        symlink(outside.join("secret.txt"), &swapped).unwrap();
+        assert!(matches!(
+            host.open(swapped.to_str().unwrap(), FileMode::Read),
+            Err(VmExecutionError::CapabilityDenied(_))
+        ));
+        assert!(open_resolved(&resolved, FileMode::Write).is_err());
+        assert_eq!(
+            std::fs::read_to_string(outside.join("secret.txt")).unwrap(),
+            "secret"
+        );
+
+        // A directory swapped for a link between the check and the open.
+        let sub = dir.join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(sub.join("secret.txt"), "data").unwrap();
+        let resolved = host
+            .resolve(sub.join("secret.txt").to_str().unwrap(), FileMode::Write)
+            .unwrap();
+        std::fs::rename(&sub, dir.join("moved")).unwrap();
+        symlink(&outside, &sub).unwrap();
+        assert!(open_resolved(&resolved, FileMode::Write).is_err());
+        assert_eq!(
+            std::fs::read_to_string(outside.join("secret.txt")).unwrap(),
+            "secret"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(outside).unwrap();
+    }
+}
@@ -1,11 +1,36 @@
-use super::opcode_decoder::RegisterValue;
+use super::channel::{ChannelHandle, VmChannel};
+use super::debugger::{Breakpoints, Watchpoints};
+use super::fuel::FuelWeights;
+use super::host::HostCapabilities;
+use super::interrupt::InterruptHandle;
+use super::native::NativeRegistry;
+use super::opcode_decoder::{RegisterValue, VmErrorCode};
+use super::output::{OutputSink, StdoutSink};
+use super::profile::Profiler;
+use super::snapshot::{
+    FrameSnapshot, HeapSnapshot, SNAPSHOT_VERSION, SectionSnapshot, SnapshotError, VmSnapshot,
+    program_fingerprint,
+};
+use super::trace::{TraceRecord, Tracer};
+use super::verifier::verify_bytecode;
+use crate::bytecode_module::{BytecodeModule, BytecodeModuleError};
 use crate::executor::ext::{VmExecutionError, VmExecutorExt};
 use crate::opcodes::{DecodedInstruction, OpCode};
-
-// TODO : thread related logic. Shared memory with atomic access. Ability to fork and run more instances on threads.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::sync::atomic::{
+    AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicU8, AtomicU16, AtomicU32, AtomicU64,
+    AtomicUsize, Ordering,
+};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
 
 pub const INITIAL_FRAMES_CAPACITY: usize = usize::pow(2, 16);
 
+/// Return address given to the function a spawned executor starts in. Returning to it ends the thread.
+pub const RETURN_TO_HOST: u64 = u64::MAX;
+
 pub trait VmMemorySectionExt {
     fn is_empty(&self) -> bool;
     fn len(&self) -> usize;
@@ -23,12 +48,9 @@ pub trait VmMemorySectionExt {
         n: usize,
         offset: usize,
     ) -> Result<&mut [u8], VmExecutionError>;
-
-    // TODO: stats method
-    // section, allocated bytes, freed slots, allocations, etc
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct VmMemorySection {
     bytes: Vec<u8>,
 }
@@ -105,22 +127,304 @@ impl VmMemorySectionExt for VmMemorySection {
     }
 }
 
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Atomic integer types that can be laid over the bytes of a shared segment. Sealed, since
+/// `SharedSegment::atomic` casts to them without further checks.
+pub trait SharedAtomic: sealed::Sealed {}
+
+macro_rules! shared_atomic {
+    ($($atomic:ty),*) => {
+        $(
+            impl sealed::Sealed for $atomic {}
+            impl SharedAtomic for $atomic {}
+        )*
+    };
+}
+
+shared_atomic!(
+    AtomicU8, AtomicU16, AtomicU32, AtomicU64, AtomicI8, AtomicI16, AtomicI32, AtomicI64
+);
+
+/// Heap memory that can be held by several executors at once. It is only reachable through
+/// atomic instructions, so every access is a single aligned atomic operation.
+/// Mixing access widths on the same bytes from different threads is left to the program to avoid.
+#[derive(Debug, Clone)]
+pub struct SharedSegment {
+    words: Arc<[AtomicU64]>,
+    len: usize,
+}
+
+impl SharedSegment {
+    fn new(len: usize) -> Result<Self, VmExecutionError> {
+        let words = try_filled(len.div_ceil(size_of::<u64>()), len, || AtomicU64::new(0))?;
+        Ok(Self {
+            words: words.into(),
+            len,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let words = bytes
+            .chunks(size_of::<u64>())
+            .map(|chunk| {
+                let mut word_bytes = [0; size_of::<u64>()];
+                word_bytes[..chunk.len()].copy_from_slice(chunk);
+                AtomicU64::new(u64::from_ne_bytes(word_bytes))
+            })
+            .collect();
+        Self {
+            words,
+            len: bytes.len(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self
+            .words
+            .iter()
+            .flat_map(|word| word.load(Ordering::SeqCst).to_ne_bytes())
+            .collect::<Vec<_>>();
+        bytes.truncate(self.len);
+        bytes
+    }
+
+    /// Views the bytes at `offset` as the atomic `A`. The offset must be aligned to the size of `A`.
+    pub fn atomic<A: SharedAtomic>(&self, offset: usize) -> Result<&A, VmExecutionError> {
+        let size = size_of::<A>();
+        match offset.checked_add(size) {
+            Some(end) if end <= self.len => {}
+            _ => return Err(VmExecutionError::SegmentationFault),
+        }
+        if !offset.is_multiple_of(size) {
+            return Err(VmExecutionError::MisalignedAtomicAccess(offset));
+        }
+
+        // The words are 8-byte aligned and live in `UnsafeCell`s, so any aligned, in-bounds
+        // integer atomic over them is valid for as long as `self` is borrowed.
+        let ptr = self.words.as_ptr().cast::<u8>().wrapping_add(offset);
+        Ok(unsafe { &*ptr.cast::<A>() })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum HeapSection {
+    Private(VmMemorySection),
+    Shared(SharedSegment),
+}
+
+impl HeapSection {
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            HeapSection::Private(section) => section.len(),
+            HeapSection::Shared(segment) => segment.len,
+        }
+    }
+}
+
+/// Caps on what a program can allocate. Every limit defaults to unlimited. The limits cover an
+/// executor and every thread it spawns together: each live section of each executor counts, so a
+/// shared segment counts once for every executor that holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeapLimits {
+    pub max_section_bytes: usize,
+    pub max_live_bytes: usize,
+    pub max_live_sections: usize,
+}
+
+impl Default for HeapLimits {
+    fn default() -> Self {
+        Self {
+            max_section_bytes: usize::MAX,
+            max_live_bytes: usize::MAX,
+            max_live_sections: usize::MAX,
+        }
+    }
+}
+
 pub trait VmHeapExt {
-    // Allocates a memory section of n size and returns a "pointer" to it, the index of that sectiom
+    // Allocates a memory section of n size and returns a "pointer" to it, a handle to that section
     fn alloc(&mut self, n: usize) -> Result<usize, VmExecutionError>;
 
+    // Allocates a shared segment of n size, only accessible through atomic instructions
+    fn alloc_shared(&mut self, n: usize) -> Result<usize, VmExecutionError>;
+
     // Frees memory section inhabiting "point" (index)
     fn free(&mut self, idx: usize) -> Result<(), VmExecutionError>;
 
     // Returns a reference to the section
     fn section(&self, idx: usize) -> Result<&VmMemorySection, VmExecutionError>;
     fn section_mut(&mut self, idx: usize) -> Result<&mut VmMemorySection, VmExecutionError>;
+
+    // Returns the shared segment at the index
+    fn shared(&self, idx: usize) -> Result<&SharedSegment, VmExecutionError>;
+
+    // Removes a private section, freeing its index, so it can be moved to another heap
+    fn take(&mut self, idx: usize) -> Result<VmMemorySection, VmExecutionError>;
+
+    // Places a section taken from another heap and returns its new handle
+    fn adopt(&mut self, section: VmMemorySection) -> Result<usize, VmExecutionError>;
 }
 
-#[derive(Debug, Clone)]
+// What the executors sharing a set of limits hold between them, the sum of their live counters.
+#[derive(Debug, Default)]
+struct HeapUsage {
+    live_bytes: AtomicUsize,
+    live_sections: AtomicUsize,
+}
+
+impl HeapUsage {
+    /// Counts a new section of `n` bytes. Fails with `OutOfMemory` if that would break a limit,
+    /// `requested` and `limit` are in sections for the section count and in bytes otherwise.
+    fn acquire(&self, limits: &HeapLimits, n: usize) -> Result<(), VmExecutionError> {
+        if n > limits.max_section_bytes {
+            return Err(VmExecutionError::OutOfMemory {
+                requested: n,
+                limit: limits.max_section_bytes,
+            });
+        }
+        self.live_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                live.checked_add(n)
+                    .filter(|&live| live <= limits.max_live_bytes)
+            })
+            .map_err(|live| VmExecutionError::OutOfMemory {
+                requested: live.saturating_add(n),
+                limit: limits.max_live_bytes,
+            })?;
+        if let Err(live) =
+            self.live_sections
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                    (live < limits.max_live_sections).then_some(live + 1)
+                })
+        {
+            self.live_bytes.fetch_sub(n, Ordering::Relaxed);
+            return Err(VmExecutionError::OutOfMemory {
+                requested: live.saturating_add(1),
+                limit: limits.max_live_sections,
+            });
+        }
+        Ok(())
+    }
+
+    fn release(&self, bytes: usize, sections: usize) {
+        self.live_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.live_sections.fetch_sub(sections, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of heap usage, see `VmHeap::stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub live_sections: usize,
+    pub live_bytes: usize,
+    pub peak_bytes: usize,
+    pub total_allocations: usize,
+    pub total_frees: usize,
+    pub free_list_len: usize,
+}
+
+/// A section that was still allocated when the report was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapLeak {
+    pub handle: usize,
+    pub size: usize,
+    /// Instruction index of the `Allocate` that created the section, if it came from one.
+    pub alloc_pc: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeakReport(pub Vec<HeapLeak>);
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total: usize = self.0.iter().map(|leak| leak.size).sum();
+        writeln!(
+            f,
+            "{} section(s) still allocated, {} bytes",
+            self.0.len(),
+            total
+        )?;
+        for leak in &self.0 {
+            match leak.alloc_pc {
+                Some(pc) => writeln!(
+                    f,
+                    "  handle {:#x}: {} bytes, allocated at pc {}",
+                    leak.handle, leak.size, pc
+                )?,
+                None => writeln!(f, "  handle {:#x}: {} bytes", leak.handle, leak.size)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Handles keep the section index in the low bits and the generation of its slot in the high bits.
+// Freeing a slot bumps its generation, so handles kept from before no longer match it.
+const HANDLE_INDEX_BITS: u32 = 32;
+const HANDLE_INDEX_MASK: usize = (1 << HANDLE_INDEX_BITS) - 1;
+
+fn make_handle(idx: usize, generation: u32) -> usize {
+    ((generation as usize) << HANDLE_INDEX_BITS) | idx
+}
+
+fn split_handle(handle: usize) -> (usize, u32) {
+    (
+        handle & HANDLE_INDEX_MASK,
+        (handle >> HANDLE_INDEX_BITS) as u32,
+    )
+}
+
+/// A vector of `len` items, failing with `OutOfMemory` instead of aborting when the host cannot
+/// provide the `bytes` it takes.
+fn try_filled<T>(
+    len: usize,
+    bytes: usize,
+    item: impl FnMut() -> T,
+) -> Result<Vec<T>, VmExecutionError> {
+    let mut items = Vec::new();
+    items
+        .try_reserve_exact(len)
+        .map_err(|_| VmExecutionError::OutOfMemory {
+            requested: bytes,
+            limit: isize::MAX as usize,
+        })?;
+    items.resize_with(len, item);
+    Ok(items)
+}
+
+#[derive(Debug)]
 pub struct VmHeap {
-    memory_sections: Vec<VmMemorySection>,
+    memory_sections: Vec<HeapSection>,
+    generations: Vec<u32>,
+    alloc_pcs: Vec<Option<usize>>,
+    // Empty sections can be live too, so freed slots are told apart by this.
+    allocated: Vec<bool>,
     freed_sections: Vec<usize>,
+    limits: HeapLimits,
+    // Shared with spawned executors, the limits apply to their sum.
+    usage: Arc<HeapUsage>,
+    live_bytes: usize,
+    live_sections: usize,
+    peak_bytes: usize,
+    total_allocations: usize,
+    total_frees: usize,
 }
 
 impl VmHeap {
@@ -128,55 +432,326 @@ impl VmHeap {
         Self {
             freed_sections: Vec::new(),
             memory_sections: Vec::new(),
+            generations: Vec::new(),
+            alloc_pcs: Vec::new(),
+            allocated: Vec::new(),
+            limits: HeapLimits::default(),
+            usage: Arc::default(),
+            live_bytes: 0,
+            live_sections: 0,
+            peak_bytes: 0,
+            total_allocations: 0,
+            total_frees: 0,
         }
     }
-}
 
-impl VmHeapExt for VmHeap {
-    fn alloc(&mut self, n: usize) -> Result<usize, VmExecutionError> {
-        let memory_block: Vec<u8> = vec![0; n];
-        let new_section = VmMemorySection::new_with_bytes(memory_block);
-        if let Some(idx) = self.freed_sections.pop() {
+    /// Applies to allocations from now on, sections that are already live are kept.
+    pub fn set_limits(&mut self, limits: HeapLimits) {
+        self.limits = limits;
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            live_sections: self.live_sections,
+            live_bytes: self.live_bytes,
+            peak_bytes: self.peak_bytes,
+            total_allocations: self.total_allocations,
+            total_frees: self.total_frees,
+            free_list_len: self.freed_sections.len(),
+        }
+    }
+
+    /// Every section that is still allocated, in handle order.
+    pub fn leak_report(&self) -> LeakReport {
+        LeakReport(
+            self.memory_sections
+                .iter()
+                .enumerate()
+                .filter(|&(idx, _)| self.allocated[idx])
+                .map(|(idx, section)| HeapLeak {
+                    handle: make_handle(idx, self.generations[idx]),
+                    size: section.len(),
+                    alloc_pc: self.alloc_pcs[idx],
+                })
+                .collect(),
+        )
+    }
+
+    /// Remembers which instruction allocated the section, for the leak report.
+    pub fn record_alloc_pc(&mut self, handle: usize, pc: usize) -> Result<(), VmExecutionError> {
+        let idx = self.slot(handle)?;
+        self.alloc_pcs[idx] = Some(pc);
+        Ok(())
+    }
+
+    /// Counts a section of `n` bytes against the limits and places the one `new_section` makes,
+    /// so nothing is allocated for a section the limits refuse.
+    fn insert(
+        &mut self,
+        n: usize,
+        new_section: impl FnOnce() -> Result<HeapSection, VmExecutionError>,
+    ) -> Result<usize, VmExecutionError> {
+        self.usage.acquire(&self.limits, n)?;
+        let handle = new_section().and_then(|section| self.place(section));
+        if handle.is_err() {
+            self.usage.release(n, 1);
+        }
+        handle
+    }
+
+    fn place(&mut self, new_section: HeapSection) -> Result<usize, VmExecutionError> {
+        let len = new_section.len();
+        let idx = if let Some(idx) = self.freed_sections.pop() {
             self.memory_sections[idx] = new_section;
-            Ok(idx)
+            self.alloc_pcs[idx] = None;
+            self.allocated[idx] = true;
+            idx
         } else {
+            if self.memory_sections.len() > HANDLE_INDEX_MASK {
+                return Err(VmExecutionError::OutOfMemory {
+                    requested: self.memory_sections.len() + 1,
+                    limit: HANDLE_INDEX_MASK + 1,
+                });
+            }
             self.memory_sections.push(new_section);
-            Ok(self.memory_sections.len() - 1)
+            self.generations.push(0);
+            self.alloc_pcs.push(None);
+            self.allocated.push(true);
+            self.memory_sections.len() - 1
+        };
+        self.live_bytes += len;
+        self.live_sections += 1;
+        self.peak_bytes = self.peak_bytes.max(self.live_bytes);
+        self.total_allocations += 1;
+        Ok(make_handle(idx, self.generations[idx]))
+    }
+
+    /// The slot a handle points to. Fails with `UseAfterFree` if the slot was freed since the
+    /// handle was made.
+    fn slot(&self, handle: usize) -> Result<usize, VmExecutionError> {
+        let (idx, generation) = split_handle(handle);
+        match self.generations.get(idx) {
+            None => Err(VmExecutionError::NullPointerException),
+            Some(&current) if current != generation => Err(VmExecutionError::UseAfterFree(handle)),
+            Some(_) => Ok(idx),
         }
     }
 
-    fn free(&mut self, idx: usize) -> Result<(), VmExecutionError> {
-        // Erase section and add its "address" (index) to freed section for reuse
-        *self
+    /// A heap for another executor, with this heap's shared segments at the same handles and
+    /// every other slot free. It shares this heap's limits, and the segments count against them
+    /// once more for as long as the new heap holds them.
+    pub fn shared_view(&self) -> Self {
+        let mut freed_sections = Vec::new();
+        let mut allocated = vec![false; self.allocated.len()];
+        let mut live_bytes = 0;
+        let mut live_sections = 0;
+        let memory_sections = self
             .memory_sections
-            .get_mut(idx)
-            .ok_or(VmExecutionError::NullPointerException)? =
-            VmMemorySection::new_with_bytes(Vec::new());
-        self.freed_sections.push(idx);
-        Ok(())
+            .iter()
+            .enumerate()
+            .map(|(idx, section)| match section {
+                HeapSection::Shared(segment) => {
+                    allocated[idx] = true;
+                    live_bytes += segment.len;
+                    live_sections += 1;
+                    HeapSection::Shared(segment.clone())
+                }
+                HeapSection::Private(_) => {
+                    freed_sections.push(idx);
+                    HeapSection::Private(VmMemorySection::new_with_bytes(Vec::new()))
+                }
+            })
+            .collect();
+        // The segments are live already, so they are counted even past a limit.
+        self.usage
+            .live_bytes
+            .fetch_add(live_bytes, Ordering::Relaxed);
+        self.usage
+            .live_sections
+            .fetch_add(live_sections, Ordering::Relaxed);
+
+        Self {
+            memory_sections,
+            generations: self.generations.clone(),
+            alloc_pcs: vec![None; self.alloc_pcs.len()],
+            allocated,
+            freed_sections,
+            limits: self.limits,
+            usage: self.usage.clone(),
+            live_bytes,
+            live_sections,
+            peak_bytes: live_bytes,
+            total_allocations: 0,
+            total_frees: 0,
+        }
     }
 
-    fn section(&self, idx: usize) -> Result<&VmMemorySection, VmExecutionError> {
-        let section = self
-            .memory_sections
-            .get(idx)
-            .ok_or(VmExecutionError::NullPointerException)?;
+    pub fn to_snapshot(&self) -> HeapSnapshot {
+        HeapSnapshot {
+            sections: self
+                .memory_sections
+                .iter()
+                .map(|section| match section {
+                    HeapSection::Private(section) => {
+                        SectionSnapshot::Private(section.bytes.clone())
+                    }
+                    HeapSection::Shared(segment) => SectionSnapshot::Shared(segment.to_bytes()),
+                })
+                .collect(),
+            generations: self.generations.clone(),
+            alloc_pcs: self.alloc_pcs.clone(),
+            freed_sections: self.freed_sections.clone(),
+            limits: self.limits,
+            peak_bytes: self.peak_bytes,
+            total_allocations: self.total_allocations,
+            total_frees: self.total_frees,
+        }
+    }
+
+    pub fn from_snapshot(snapshot: HeapSnapshot) -> Result<Self, SnapshotError> {
+        let slots = snapshot.sections.len();
+        if snapshot.generations.len() != slots || snapshot.alloc_pcs.len() != slots {
+            return Err(SnapshotError::Malformed(
+                "heap slot tables differ in length",
+            ));
+        }
+        let mut allocated = vec![true; slots];
+        for &idx in &snapshot.freed_sections {
+            // Freed slots hold an empty private section, see `free`.
+            match snapshot.sections.get(idx) {
+                None => return Err(SnapshotError::Malformed("free list points past the heap")),
+                Some(SectionSnapshot::Private(bytes)) if bytes.is_empty() => {}
+                Some(_) => return Err(SnapshotError::Malformed("free list holds a live section")),
+            }
+            if !std::mem::replace(&mut allocated[idx], false) {
+                return Err(SnapshotError::Malformed("free list holds a slot twice"));
+            }
+        }
+
+        let memory_sections = snapshot
+            .sections
+            .into_iter()
+            .map(|section| match section {
+                SectionSnapshot::Private(bytes) => {
+                    HeapSection::Private(VmMemorySection::new_with_bytes(bytes))
+                }
+                SectionSnapshot::Shared(bytes) => {
+                    HeapSection::Shared(SharedSegment::from_bytes(&bytes))
+                }
+            })
+            .collect::<Vec<_>>();
+        let live_bytes = memory_sections.iter().map(HeapSection::len).sum();
+        let live_sections = allocated.iter().filter(|&&allocated| allocated).count();
+
+        Ok(Self {
+            usage: Arc::new(HeapUsage {
+                live_bytes: AtomicUsize::new(live_bytes),
+                live_sections: AtomicUsize::new(live_sections),
+            }),
+            live_bytes,
+            live_sections,
+            memory_sections,
+            generations: snapshot.generations,
+            alloc_pcs: snapshot.alloc_pcs,
+            allocated,
+            freed_sections: snapshot.freed_sections,
+            limits: snapshot.limits,
+            peak_bytes: snapshot.peak_bytes,
+            total_allocations: snapshot.total_allocations,
+            total_frees: snapshot.total_frees,
+        })
+    }
+
+    fn get(&self, handle: usize) -> Result<&HeapSection, VmExecutionError> {
+        let section = &self.memory_sections[self.slot(handle)?];
         if section.is_empty() {
             return Err(VmExecutionError::NullPointerException);
         }
         Ok(section)
     }
+}
 
-    fn section_mut(&mut self, idx: usize) -> Result<&mut VmMemorySection, VmExecutionError> {
-        let section = self
-            .memory_sections
-            .get_mut(idx)
-            .ok_or(VmExecutionError::NullPointerException)?;
-        if section.is_empty() {
-            return Err(VmExecutionError::NullPointerException);
+// Whatever a heap still holds stops counting against the limits it shares with other executors.
+impl Drop for VmHeap {
+    fn drop(&mut self) {
+        self.usage.release(self.live_bytes, self.live_sections);
+    }
+}
+
+impl VmHeapExt for VmHeap {
+    fn alloc(&mut self, n: usize) -> Result<usize, VmExecutionError> {
+        self.insert(n, || {
+            let memory_block = try_filled(n, n, || 0)?;
+            Ok(HeapSection::Private(VmMemorySection::new_with_bytes(
+                memory_block,
+            )))
+        })
+    }
+
+    fn alloc_shared(&mut self, n: usize) -> Result<usize, VmExecutionError> {
+        self.insert(n, || Ok(HeapSection::Shared(SharedSegment::new(n)?)))
+    }
+
+    fn free(&mut self, handle: usize) -> Result<(), VmExecutionError> {
+        let idx = match self.slot(handle) {
+            Err(VmExecutionError::UseAfterFree(handle)) => {
+                return Err(VmExecutionError::DoubleFree(handle));
+            }
+            result => result?,
+        };
+        if !self.allocated[idx] {
+            return Err(VmExecutionError::DoubleFree(handle));
+        }
+        // Erase section and add its "address" (index) to freed section for reuse
+        let section = std::mem::replace(
+            &mut self.memory_sections[idx],
+            HeapSection::Private(VmMemorySection::new_with_bytes(Vec::new())),
+        );
+        self.generations[idx] = self.generations[idx].wrapping_add(1);
+        self.allocated[idx] = false;
+        self.total_frees += 1;
+        self.live_bytes -= section.len();
+        self.live_sections -= 1;
+        self.usage.release(section.len(), 1);
+        self.freed_sections.push(idx);
+        Ok(())
+    }
+
+    fn section(&self, idx: usize) -> Result<&VmMemorySection, VmExecutionError> {
+        match self.get(idx)? {
+            HeapSection::Private(section) => Ok(section),
+            HeapSection::Shared(_) => Err(VmExecutionError::NonAtomicSharedAccess(idx)),
+        }
+    }
+
+    fn section_mut(&mut self, handle: usize) -> Result<&mut VmMemorySection, VmExecutionError> {
+        let idx = self.slot(handle)?;
+        match &mut self.memory_sections[idx] {
+            HeapSection::Private(section) if !section.is_empty() => Ok(section),
+            HeapSection::Private(_) => Err(VmExecutionError::NullPointerException),
+            HeapSection::Shared(_) => Err(VmExecutionError::NonAtomicSharedAccess(handle)),
+        }
+    }
+
+    fn shared(&self, idx: usize) -> Result<&SharedSegment, VmExecutionError> {
+        match self.get(idx)? {
+            HeapSection::Shared(segment) => Ok(segment),
+            HeapSection::Private(_) => Err(VmExecutionError::NotSharedSegment(idx)),
         }
+    }
+
+    fn take(&mut self, idx: usize) -> Result<VmMemorySection, VmExecutionError> {
+        let section = std::mem::take(self.section_mut(idx)?);
+        // The slot is empty now, so `free` no longer sees these bytes as live.
+        self.live_bytes -= section.len();
+        self.usage.release(section.len(), 0);
+        self.free(idx)?;
         Ok(section)
     }
+
+    fn adopt(&mut self, section: VmMemorySection) -> Result<usize, VmExecutionError> {
+        self.insert(section.len(), || Ok(HeapSection::Private(section)))
+    }
 }
 
 pub trait RegisterFileExt<T: RegisterValue, R: Into<usize>> {
@@ -225,14 +800,20 @@ impl_register_value_ext!(i64);
 impl<T: RegisterValue, R: Into<usize>> RegisterFileExt<T, R> for RegisterFile {
     fn get_register_value(&self, idx: R) -> Result<T, VmExecutionError> {
         let idx: usize = idx.into();
-        assert!(self.raw.len() > idx);
-        Ok(RegisterValue::from_u64(self.raw[idx]))
+        let raw = self
+            .raw
+            .get(idx)
+            .ok_or(VmExecutionError::InvalidRegister(idx))?;
+        Ok(RegisterValue::from_u64(*raw))
     }
 
     fn set_register_value(&mut self, idx: R, value: T) -> Result<(), VmExecutionError> {
         let idx: usize = idx.into();
-        assert!(self.raw.len() > idx);
-        self.raw[idx] = value.to_u64();
+        let raw = self
+            .raw
+            .get_mut(idx)
+            .ok_or(VmExecutionError::InvalidRegister(idx))?;
+        *raw = value.to_u64();
         Ok(())
     }
 }
@@ -279,16 +860,42 @@ impl CallFrame {
     }
 }
 
+/// What a spawned thread hands back when it ends: its executor, and R0 or the error it stopped with.
+pub type ThreadExit = (Box<VmInterpretedExecutor>, Result<u64, VmExecutionError>);
+
+pub enum VmThread {
+    Running(JoinHandle<ThreadExit>),
+    /// Stopped with an error, joining again resumes it where it stopped.
+    Stopped(Box<VmInterpretedExecutor>),
+}
+
 pub struct VmInterpretedExecutor {
     pub frame_stack: Vec<CallFrame>,
     pub stack_top: usize,
     pub error_code: i64,
     pub prev_error_code: i64,
+    trap_on_error: bool,
     max_stack_depth: usize,
     program_counter: usize,
+    entry_point: usize,
     heap: VmHeap,
     bytecode_pc_to_instr_index: Vec<usize>,
     constants: Vec<Vec<u8>>,
+    program: Arc<[DecodedInstruction]>,
+    threads: Vec<Option<VmThread>>,
+    channels: Vec<Option<ChannelHandle>>,
+    fuel: Option<Arc<AtomicU64>>,
+    fuel_weights: Option<Arc<FuelWeights>>,
+    interrupt: InterruptHandle,
+    program_fingerprint: u64,
+    breakpoints: Breakpoints,
+    watchpoints: Watchpoints,
+    tracer: Option<Box<Tracer>>,
+    profiler: Option<Box<Profiler>>,
+    natives: Arc<NativeRegistry>,
+    output: Arc<Mutex<dyn OutputSink>>,
+    capabilities: Arc<HostCapabilities>,
+    files: Vec<Option<File>>,
 }
 
 impl VmInterpretedExecutor {
@@ -298,11 +905,186 @@ impl VmInterpretedExecutor {
             stack_top: 0,
             error_code: 0,
             prev_error_code: 0,
+            trap_on_error: false,
             program_counter: 0,
+            entry_point: 0,
             heap: VmHeap::new(),
             max_stack_depth: (u32::MAX / 2048) as usize,
             bytecode_pc_to_instr_index: Vec::new(),
             constants: constants.unwrap_or_default(),
+            program: Arc::new([]),
+            threads: Vec::new(),
+            channels: Vec::new(),
+            fuel: None,
+            fuel_weights: None,
+            interrupt: InterruptHandle::default(),
+            program_fingerprint: program_fingerprint(&[]),
+            breakpoints: Breakpoints::default(),
+            watchpoints: Watchpoints::default(),
+            tracer: None,
+            profiler: None,
+            natives: Arc::new(NativeRegistry::default()),
+            output: Arc::new(Mutex::new(StdoutSink)),
+            capabilities: Arc::new(HostCapabilities::default()),
+            files: Vec::new(),
+        }
+    }
+
+    /// An executor for a spawned thread. It runs the same preprocessed program with the same
+    /// constants and channels, sees only the shared segments of this heap, and starts in a call to `entry`
+    /// with `arg` in R1. The thread ends when that call returns.
+    pub fn spawn_child(&self, entry: usize, arg: u64) -> Self {
+        // Frames grow on demand, the host frame and the entry call are all a new thread needs.
+        let mut frame_stack = vec![CallFrame::new(); 2];
+        frame_stack[1].return_address = RETURN_TO_HOST;
+        frame_stack[1].registers.raw[1] = arg;
+
+        Self {
+            frame_stack,
+            stack_top: 1,
+            error_code: 0,
+            prev_error_code: 0,
+            trap_on_error: self.trap_on_error,
+            program_counter: entry,
+            entry_point: entry,
+            heap: self.heap.shared_view(),
+            max_stack_depth: self.max_stack_depth,
+            bytecode_pc_to_instr_index: self.bytecode_pc_to_instr_index.clone(),
+            constants: self.constants.clone(),
+            program: self.program.clone(),
+            threads: Vec::new(),
+            channels: self.channels.clone(),
+            // Threads draw on the same budget, spawning does not hand out more fuel.
+            fuel: self.fuel.clone(),
+            fuel_weights: self.fuel_weights.clone(),
+            interrupt: self.interrupt.clone(),
+            program_fingerprint: self.program_fingerprint,
+            breakpoints: Breakpoints::default(),
+            watchpoints: Watchpoints::default(),
+            tracer: None,
+            profiler: None,
+            natives: self.natives.clone(),
+            output: self.output.clone(),
+            capabilities: self.capabilities.clone(),
+            files: Vec::new(),
+        }
+    }
+
+    /// What the host I/O instructions may access, nothing unless granted. Spawned executors get
+    /// the same grants but open their own files.
+    pub fn set_capabilities(&mut self, capabilities: HostCapabilities) {
+        self.capabilities = Arc::new(capabilities);
+    }
+
+    pub fn capabilities(&self) -> &HostCapabilities {
+        &self.capabilities
+    }
+
+    pub fn add_file(&mut self, file: File) -> usize {
+        self.files.push(Some(file));
+        self.files.len() - 1
+    }
+
+    pub fn file_mut(&mut self, idx: usize) -> Result<&mut File, VmExecutionError> {
+        self.files
+            .get_mut(idx)
+            .and_then(Option::as_mut)
+            .ok_or(VmExecutionError::InvalidFileHandle(idx))
+    }
+
+    pub fn close_file(&mut self, idx: usize) -> Result<(), VmExecutionError> {
+        self.files
+            .get_mut(idx)
+            .and_then(Option::take)
+            .map(drop)
+            .ok_or(VmExecutionError::InvalidFileHandle(idx))
+    }
+
+    /// Replaces where `Print` and `DebugPrint*` write, stdout by default.
+    pub fn set_output(&mut self, sink: impl OutputSink + 'static) {
+        self.output = Arc::new(Mutex::new(sink));
+    }
+
+    pub fn write_output(&self, text: &str) -> Result<(), VmExecutionError> {
+        self.output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_str(text)
+            .map_err(|err| VmExecutionError::InternalError(Box::new(err)))
+    }
+
+    pub fn flush_output(&self) -> Result<(), VmExecutionError> {
+        self.output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+            .map_err(|err| VmExecutionError::InternalError(Box::new(err)))
+    }
+
+    /// Makes `function` callable with `CallNative id`. Register natives before loading a program,
+    /// loading fails for programs that call ids nothing is registered under.
+    pub fn register_native(
+        &mut self,
+        id: u32,
+        name: &str,
+        arity: u8,
+        function: impl Fn(&mut VmInterpretedExecutor) -> Result<(), VmExecutionError>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Result<(), VmExecutionError> {
+        Arc::make_mut(&mut self.natives).register(id, name, arity, Arc::new(function))
+    }
+
+    pub fn natives(&self) -> &NativeRegistry {
+        &self.natives
+    }
+
+    pub fn add_channel(&mut self, channel: Arc<VmChannel>) -> usize {
+        self.channels.push(Some(ChannelHandle::new(channel)));
+        self.channels.len() - 1
+    }
+
+    pub fn channel(&self, idx: usize) -> Result<&Arc<VmChannel>, VmExecutionError> {
+        self.channels
+            .get(idx)
+            .and_then(Option::as_ref)
+            .map(ChannelHandle::channel)
+            .ok_or(VmExecutionError::InvalidChannelHandle(idx))
+    }
+
+    /// Gives up this executor's hold on a channel, so it no longer counts as a sender.
+    pub fn close_channel(&mut self, idx: usize) -> Result<(), VmExecutionError> {
+        self.channels
+            .get_mut(idx)
+            .and_then(Option::take)
+            .map(drop)
+            .ok_or(VmExecutionError::InvalidChannelHandle(idx))
+    }
+
+    /// The preprocessed program, shared with any spawned executors.
+    pub fn program(&self) -> Arc<[DecodedInstruction]> {
+        self.program.clone()
+    }
+
+    pub fn add_thread(&mut self, thread: VmThread) -> usize {
+        self.threads.push(Some(thread));
+        self.threads.len() - 1
+    }
+
+    /// Takes the thread behind a handle so it can be joined. Each thread can only be taken once,
+    /// unless it is put back with `return_thread`.
+    pub fn take_thread(&mut self, idx: usize) -> Result<VmThread, VmExecutionError> {
+        self.threads
+            .get_mut(idx)
+            .and_then(Option::take)
+            .ok_or(VmExecutionError::InvalidThreadHandle(idx))
+    }
+
+    /// Puts a taken thread back under its handle, so it can be joined again.
+    pub fn return_thread(&mut self, idx: usize, thread: VmThread) {
+        if let Some(slot) = self.threads.get_mut(idx) {
+            *slot = Some(thread);
         }
     }
 
@@ -314,6 +1096,344 @@ impl VmInterpretedExecutor {
         self.constants = constants;
     }
 
+    /// Sets the constants, preprocesses the module's code and makes its entry point the place
+    /// `execute_processeded_bytecode` starts from.
+    pub fn load_module(
+        &mut self,
+        module: &BytecodeModule,
+    ) -> Result<Vec<(DecodedInstruction, ExecutableInstructionFn)>, VmExecutionError> {
+        self.constants = module.constants.clone();
+        let processed_bytecode = self.preprocess_bytecode(&module.code)?;
+
+        let entry_point = match self
+            .bytecode_pc_to_instr_index
+            .get(module.entry_point as usize)
+        {
+            Some(&idx) if idx != usize::MAX => idx,
+            None if module.entry_point == 0 => 0,
+            _ => return Err(BytecodeModuleError::InvalidEntryPoint(module.entry_point).into()),
+        };
+        self.entry_point = entry_point;
+
+        Ok(processed_bytecode)
+    }
+
+    /// When enabled, an instruction that sets an arithmetic error flag stops execution with
+    /// `VmExecutionError::ArithmeticTrap` instead of leaving the flag for the program to check.
+    /// Other flags, like `IoError`, are still left to the program.
+    pub fn set_trap_on_error(&mut self, trap_on_error: bool) {
+        self.trap_on_error = trap_on_error;
+    }
+
+    /// Limits how many instructions can run before execution stops with
+    /// `VmExecutionError::OutOfFuel`. `None` runs without a limit. Spawned threads share the
+    /// budget of the executor that spawned them.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel.map(|fuel| Arc::new(AtomicU64::new(fuel)));
+    }
+
+    /// Tops up the remaining fuel, so a run that stopped with `OutOfFuel` can be resumed.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(remaining) = &self.fuel {
+            let _ = remaining.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                Some(remaining.saturating_add(fuel))
+            });
+        }
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel
+            .as_ref()
+            .map(|remaining| remaining.load(Ordering::Relaxed))
+    }
+
+    /// Per-opcode fuel costs. Without weights every instruction costs 1.
+    pub fn set_fuel_weights(&mut self, weights: Option<FuelWeights>) {
+        self.fuel_weights = weights.map(Arc::new);
+    }
+
+    /// A handle another thread can use to stop this executor with `VmExecutionError::Interrupted`.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Captures the state needed to resume later, e.g. after `OutOfFuel` or `Interrupted`.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            version: SNAPSHOT_VERSION,
+            program_fingerprint: self.program_fingerprint,
+            frames: self.frame_stack[..=self.stack_top]
+                .iter()
+                .map(|frame| FrameSnapshot {
+                    return_address: frame.return_address,
+                    registers: frame.registers.raw.to_vec(),
+                })
+                .collect(),
+            program_counter: self.program_counter,
+            error_code: self.error_code,
+            prev_error_code: self.prev_error_code,
+            heap: self.heap.to_snapshot(),
+            constants: self.constants.clone(),
+        }
+    }
+
+    /// Replaces this executor's state with a snapshot taken from the same program. The program
+    /// has to be preprocessed first, `resume_processeded_bytecode` then carries on from the snapshot.
+    pub fn restore(&mut self, snapshot: VmSnapshot) -> Result<(), VmExecutionError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version).into());
+        }
+        if snapshot.program_fingerprint != self.program_fingerprint {
+            return Err(SnapshotError::ProgramMismatch {
+                expected: self.program_fingerprint,
+                found: snapshot.program_fingerprint,
+            }
+            .into());
+        }
+        if snapshot.frames.is_empty() {
+            return Err(SnapshotError::Malformed("no call frames").into());
+        }
+
+        let frame_stack = snapshot
+            .frames
+            .into_iter()
+            .map(|frame| {
+                let raw = frame
+                    .registers
+                    .try_into()
+                    .map_err(|_| SnapshotError::Malformed("wrong register count"))?;
+                Ok(CallFrame::new_from(
+                    frame.return_address,
+                    RegisterFile { raw },
+                ))
+            })
+            .collect::<Result<Vec<_>, SnapshotError>>()?;
+
+        self.heap = VmHeap::from_snapshot(snapshot.heap)?;
+        self.stack_top = frame_stack.len() - 1;
+        self.frame_stack = frame_stack;
+        self.program_counter = snapshot.program_counter;
+        self.error_code = snapshot.error_code;
+        self.prev_error_code = snapshot.prev_error_code;
+        self.constants = snapshot.constants;
+
+        Ok(())
+    }
+
+    /// Continues from the current program counter, e.g. after `OutOfFuel` once more fuel was added.
+    pub fn resume_processeded_bytecode(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<i64, VmExecutionError> {
+        if self.profiler.is_some() {
+            return self.resume_profiled(processed_bytecode);
+        }
+        loop {
+            if let Some(exit_code) = self.step_processeded_bytecode(processed_bytecode)? {
+                return Ok(exit_code);
+            }
+        }
+    }
+
+    // A separate loop, so counting costs nothing when no profiler is set.
+    #[inline(never)]
+    fn resume_profiled(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<i64, VmExecutionError> {
+        let Some(mut profiler) = self.profiler.take() else {
+            return self.resume_processeded_bytecode(processed_bytecode);
+        };
+        profiler.enter_run(self.program_counter);
+
+        let result = loop {
+            let pc = self.program_counter;
+            let step = self.step_processeded_bytecode(processed_bytecode);
+            if let Some((decoded, _)) = processed_bytecode.get(pc) {
+                // A failing instruction ran too, only running out of fuel stops one before it starts.
+                if !matches!(step, Err(VmExecutionError::OutOfFuel { .. })) {
+                    profiler.count(pc, decoded.opcode());
+                }
+                // These stop execution once the instruction is done, a call or return still happened.
+                if matches!(
+                    step,
+                    Ok(_)
+                        | Err(VmExecutionError::ArithmeticTrap { .. }
+                            | VmExecutionError::Interrupted { .. }
+                            | VmExecutionError::WatchpointHit(_))
+                ) {
+                    match decoded {
+                        DecodedInstruction::CallFunction(_) => profiler.enter(self.program_counter),
+                        DecodedInstruction::Return(_) => profiler.exit(),
+                        _ => {}
+                    }
+                }
+            }
+            match step {
+                Ok(Some(exit_code)) => break Ok(exit_code),
+                Ok(None) => {}
+                Err(err) => break Err(err),
+            }
+        };
+
+        match result {
+            Ok(_) => profiler.exit_run(),
+            Err(_) => profiler.suspend_run(),
+        }
+        self.profiler = Some(profiler);
+        result
+    }
+
+    /// Runs the instruction at the program counter. Returns the exit code once the program has
+    /// halted, in which case the program counter stays on the `Halt`.
+    #[inline(always)]
+    pub fn step_processeded_bytecode(
+        &mut self,
+        processed_bytecode: &[(DecodedInstruction, ExecutableInstructionFn)],
+    ) -> Result<Option<i64>, VmExecutionError> {
+        let Some((decoded, exec_instr_fn)) = processed_bytecode.get(self.program_counter) else {
+            if self.program_counter as u64 == RETURN_TO_HOST {
+                // The program has ended, it can no longer send to anyone.
+                self.channels.clear();
+                return Ok(Some(self.registers().get_register_value(0usize)?));
+            }
+            return Err(VmExecutionError::SegmentationFault);
+        };
+
+        let trace = self.tracer.as_mut().map(|tracer| {
+            tracer.begin(
+                self.program_counter,
+                decoded,
+                self.stack_top,
+                &self.frame_stack[self.stack_top].registers,
+            )
+        });
+        if let DecodedInstruction::Halt((exit_code,)) = decoded {
+            if let Some(record) = trace {
+                self.finish_trace(record, decoded, None)?;
+            }
+            self.flush_output()?;
+            self.channels.clear();
+            return Ok(Some(*exit_code));
+        }
+        if let Some(fuel) = &self.fuel {
+            let cost = self
+                .fuel_weights
+                .as_ref()
+                .map_or(1, |weights| weights.cost(decoded.opcode()));
+            // Stop before touching any state so resuming runs this instruction again.
+            if fuel
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |fuel| {
+                    fuel.checked_sub(cost)
+                })
+                .is_err()
+            {
+                return Err(VmExecutionError::OutOfFuel {
+                    pc: self.program_counter,
+                });
+            }
+        }
+        // A flag is visible to the next instruction only; the ErrorFlag/JumpIfError opcodes carry it forward.
+        self.prev_error_code = self.error_code;
+        self.error_code = 0;
+        let pc = self.program_counter;
+        self.program_counter += 1;
+        let result = exec_instr_fn(self);
+        if let Some(record) = trace {
+            self.finish_trace(record, decoded, result.as_ref().err())?;
+        }
+        // A watchpoint stops after its instruction ran, so that instruction is checked like any other.
+        if let Err(err) = &result
+            && !matches!(err, VmExecutionError::WatchpointHit(_))
+        {
+            return result.map(|_| None);
+        }
+
+        if self.trap_on_error && self.error_code != 0 && VmErrorCode::is_arithmetic(self.error_code)
+        {
+            return Err(VmExecutionError::ArithmeticTrap {
+                code: self.error_code,
+                pc,
+                opcode: decoded.opcode(),
+            });
+        }
+        // An interrupt stays requested behind a watchpoint hit, the next loop or call picks it up.
+        result?;
+        // Only loops and calls can run unbounded, checking there keeps straight-line code fast.
+        if (self.program_counter <= pc || matches!(decoded, DecodedInstruction::CallFunction(_)))
+            && self.interrupt.is_interrupted()
+        {
+            return Err(VmExecutionError::Interrupted {
+                pc: self.program_counter,
+            });
+        }
+
+        Ok(None)
+    }
+
+    #[cold]
+    fn finish_trace(
+        &mut self,
+        record: TraceRecord,
+        decoded: &DecodedInstruction,
+        error: Option<&VmExecutionError>,
+    ) -> Result<(), VmExecutionError> {
+        let Some(tracer) = self.tracer.as_mut() else {
+            return Ok(());
+        };
+        tracer
+            .finish(
+                record,
+                decoded,
+                &self.frame_stack[self.stack_top].registers,
+                error,
+            )
+            .map_err(|err| VmExecutionError::InternalError(Box::new(err)))
+    }
+
+    /// Counts executed instructions and times calls while running, see `Profiler`. Spawned
+    /// executors and the debugger's stepping are not profiled.
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler.map(Box::new);
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take().map(|profiler| *profiler)
+    }
+
+    /// Records executed instructions and calls, see `Tracer`. Spawned executors are not traced.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer.map(Box::new);
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_deref()
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take().map(|tracer| *tracer)
+    }
+
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        &mut self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
+    }
+
     pub fn get_max_stack_depth(&self) -> usize {
         self.max_stack_depth
     }
@@ -360,8 +1480,12 @@ impl VmExecutorExt for VmInterpretedExecutor {
         )>,
         VmExecutionError,
     > {
+        verify_bytecode(bytecode).map_err(VmExecutionError::VerificationFailed)?;
+        self.program_fingerprint = program_fingerprint(bytecode);
+
         let mut processed_bytecode = Vec::with_capacity(bytecode.len());
         let mut bc_counter = 0;
+        self.entry_point = 0;
         self.bytecode_pc_to_instr_index = vec![usize::MAX; bytecode.len()];
         loop {
             let jump_bc_counter = bc_counter;
@@ -389,29 +1513,32 @@ impl VmExecutorExt for VmInterpretedExecutor {
             bc_counter += look_ahead_bytes;
         }
 
+        let mut unresolved: Vec<u32> = processed_bytecode
+            .iter()
+            .filter_map(|instr| match instr {
+                DecodedInstruction::CallNative((fn_id,)) if self.natives.get(*fn_id).is_none() => {
+                    Some(*fn_id)
+                }
+                _ => None,
+            })
+            .collect();
+        if !unresolved.is_empty() {
+            unresolved.sort_unstable();
+            unresolved.dedup();
+            return Err(VmExecutionError::UnresolvedNatives(unresolved));
+        }
+
         // Preprocess jump address to the processed bytecode
         let processed_bytecode_second_pass = processed_bytecode
             .into_iter()
-            .map(|instr| match instr {
-                DecodedInstruction::Jump((target,)) => {
-                    let resolved = self.get_instr_idx_from_bc(target as usize) as u64;
-                    DecodedInstruction::Jump((resolved,))
-                }
-                DecodedInstruction::JumpIf((target, reg)) => {
-                    let resolved = self.get_instr_idx_from_bc(target as usize) as u64;
-                    DecodedInstruction::JumpIf((resolved, reg))
+            .map(|instr| match instr.branch_target() {
+                Some(target) => {
+                    instr.with_branch_target(self.get_instr_idx_from_bc(target as usize) as u64)
                 }
-                DecodedInstruction::JumpIfFalse((target, reg)) => {
-                    let resolved = self.get_instr_idx_from_bc(target as usize) as u64;
-                    DecodedInstruction::JumpIfFalse((resolved, reg))
-                }
-                DecodedInstruction::CallFunction((target,)) => {
-                    let resolved = self.get_instr_idx_from_bc(target as usize) as u64;
-                    DecodedInstruction::CallFunction((resolved,))
-                }
-                _ => instr,
+                None => instr,
             })
             .collect::<Vec<_>>();
+        self.program = processed_bytecode_second_pass.clone().into();
 
         let final_pass = processed_bytecode_second_pass
             .into_iter()
@@ -428,19 +1555,12 @@ impl VmExecutorExt for VmInterpretedExecutor {
             Box<dyn Fn(&mut VmInterpretedExecutor) -> Result<(), VmExecutionError>>,
         )],
     ) -> Result<i64, VmExecutionError> {
-        self.program_counter = 0;
-
-        loop {
-            let (decoded, exec_instr_fn) = &processed_bytecode[self.program_counter];
-
-            if let DecodedInstruction::Halt((exit_code,)) = decoded {
-                return Ok(*exit_code);
-            }
-            self.prev_error_code = self.error_code;
-            self.error_code = 0; // TODO: examine if any instruction will need it kept set between multiple instructions.
-            self.program_counter += 1;
-            exec_instr_fn(self)?;
+        self.program_counter = self.entry_point;
+        // A fresh run, calls left open by one that stopped early are over.
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.exit_run();
         }
+        self.resume_processeded_bytecode(processed_bytecode)
     }
 
     fn set_error(&mut self, error_code: i64) {
@@ -461,3 +1581,84 @@ pub trait ExecutableInstruction {
 
 pub type ExecutableInstructionFn =
     Box<dyn Fn(&mut VmInterpretedExecutor) -> Result<(), VmExecutionError>>;
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::ext::VmExecutionError;
+    use crate::executor::interpreted::opcode_decoder::VmErrorCode;
+    use crate::executor::interpreted::opcode_impl::all::*;
+    use crate::opcodes::OpCode;
+
+    #[test]
+    fn trap_stops_at_faulting_instruction() {
+        let mut test = crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(0u8, R!(1))
+            .with_program(vec![
+                IncrementU8Instruction::encode((R!(3), 1u8)),
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                IncrementU8Instruction::encode((R!(3), 1u8)),
+                HaltInstruction::encode((0,)),
+            ]);
+        test.vm_mut().set_trap_on_error(true);
+
+        match test.run() {
+            Err(VmExecutionError::ArithmeticTrap { code, pc, opcode }) => {
+                assert_eq!(code, VmErrorCode::DivisionByZero as i64);
+                assert_eq!(pc, 1);
+                assert_eq!(opcode, OpCode::DivideU8);
+            }
+            other => panic!("Expected an arithmetic trap, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn trap_mode_runs_clean_program() {
+        let mut test = crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(2u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(2), 5u8);
+        test.vm_mut().set_trap_on_error(true);
+
+        test.run().unwrap();
+    }
+
+    #[test]
+    fn trap_mode_leaves_io_errors_to_the_program() {
+        use super::{RegisterFileExt, VmInterpretedExecutor};
+        use crate::executor::ext::VmExecutorExt;
+        use crate::executor::interpreted::host::HostCapabilities;
+
+        let dir = std::env::temp_dir().join(format!("solid-snake-trap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("missing.txt");
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_capabilities(
+            HostCapabilities::new()
+                .with_args(vec![path.to_str().unwrap().to_string()])
+                .with_read_dir(&dir)
+                .unwrap(),
+        );
+        vm.set_trap_on_error(true);
+
+        let program = [
+            ArgGetInstruction::encode((R!(1), R!(0))),
+            FileOpenInstruction::encode((R!(2), R!(1), R!(0))),
+            ErrorFlagEqualImmediateInstruction::encode((R!(3), VmErrorCode::IoError as i64)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let flagged: u8 = vm.registers().get_register_value(R!(3)).unwrap();
+        assert_eq!(flagged, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Stops a running executor from another thread. The executor checks it at backward jumps and
+/// calls, so any loop or recursion notices it, and spawned executors share the handle of their parent.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Clears a previous interrupt so the executor can be resumed.
+    pub fn reset(&self) {
+        self.requested.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.requested.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{RegisterFileExt, VmInterpretedExecutor};
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    #[test]
+    fn interrupts_loop_from_other_thread() {
+        let program = [
+            IncrementU8Instruction::encode((R!(0), 1u8)), // 0..4
+            JumpInstruction::encode((0u64,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        let handle = vm.interrupt_handle();
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.interrupt();
+        });
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+        canceller.join().unwrap();
+
+        assert!(matches!(err, VmExecutionError::Interrupted { pc: 0 }));
+        assert_eq!(vm.get_program_counter().unwrap(), 0);
+    }
+
+    #[test]
+    fn interrupted_at_call_and_resumed() {
+        let program = [
+            CallFunctionInstruction::encode((20u64,)), // 0..10
+            HaltInstruction::encode((0,)),             // 10..20
+            // function at offset 20
+            IncrementU8Instruction::encode((R!(0), 1u8)),
+            ReturnInstruction::encode(()),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        let handle = vm.interrupt_handle();
+        handle.interrupt();
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+        assert!(matches!(err, VmExecutionError::Interrupted { pc: 2 }));
+
+        handle.reset();
+        assert_eq!(vm.resume_processeded_bytecode(&bc).unwrap(), 0);
+        let counter: u8 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(counter, 1);
+    }
+
+    #[test]
+    fn forward_code_ignores_interrupt() {
+        let program = [
+            IncrementU8Instruction::encode((R!(0), 1u8)),
+            HaltInstruction::encode((3,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.interrupt_handle().interrupt();
+
+        assert_eq!(vm.execute_processeded_bytecode(&bc).unwrap(), 3);
+    }
+}
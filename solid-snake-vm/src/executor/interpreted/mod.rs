@@ -1,3 +1,14 @@
+pub mod channel;
+pub mod debugger;
+pub mod fuel;
+pub mod host;
 pub mod implimentation;
+pub mod interrupt;
+pub mod native;
 pub mod opcode_decoder;
 pub mod opcode_impl;
+pub mod output;
+pub mod profile;
+pub mod snapshot;
+pub mod trace;
+pub mod verifier;
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::implimentation::VmInterpretedExecutor;
+use crate::executor::ext::VmExecutionError;
+
+/// Host code reached through `CallNative`. It reads its arguments from R1..R3 of the current frame
+/// and leaves its result in R0, like a bytecode function would.
+pub type NativeFn =
+    Arc<dyn Fn(&mut VmInterpretedExecutor) -> Result<(), VmExecutionError> + Send + Sync>;
+
+/// Arguments are passed in R1..R3, so no native function takes more.
+pub const MAX_NATIVE_ARITY: u8 = 3;
+
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: u8,
+    pub function: NativeFn,
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Native functions by id, see `VmInterpretedExecutor::register_native`. Spawned executors share
+/// the registry of their parent.
+#[derive(Debug, Clone, Default)]
+pub struct NativeRegistry {
+    functions: HashMap<u32, NativeFunction>,
+    ids: HashMap<String, u32>,
+}
+
+impl NativeRegistry {
+    pub fn register(
+        &mut self,
+        id: u32,
+        name: &str,
+        arity: u8,
+        function: NativeFn,
+    ) -> Result<(), VmExecutionError> {
+        if arity > MAX_NATIVE_ARITY {
+            return Err(VmExecutionError::InvalidNativeArity {
+                name: name.to_string(),
+                arity,
+            });
+        }
+        if self.functions.contains_key(&id) || self.ids.contains_key(name) {
+            return Err(VmExecutionError::NativeAlreadyRegistered {
+                id,
+                name: name.to_string(),
+            });
+        }
+
+        self.ids.insert(name.to_string(), id);
+        self.functions.insert(
+            id,
+            NativeFunction {
+                name: name.to_string(),
+                arity,
+                function,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, id: u32) -> Option<&NativeFunction> {
+        self.functions.get(&id)
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &NativeFunction)> {
+        self.functions.iter().map(|(&id, function)| (id, function))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::NativeRegistry;
+    use crate::executor::ext::VmExecutionError;
+
+    #[test]
+    fn looks_up_by_name_and_id() {
+        let mut registry = NativeRegistry::default();
+        registry
+            .register(7, "clock", 0, Arc::new(|_| Ok(())))
+            .unwrap();
+
+        assert_eq!(registry.id("clock"), Some(7));
+        assert_eq!(registry.get(7).unwrap().name, "clock");
+        assert!(registry.get(8).is_none());
+    }
+
+    #[test]
+    fn rejects_duplicates_and_arity() {
+        let mut registry = NativeRegistry::default();
+        registry.register(1, "a", 1, Arc::new(|_| Ok(()))).unwrap();
+
+        assert!(matches!(
+            registry.register(1, "b", 1, Arc::new(|_| Ok(()))),
+            Err(VmExecutionError::NativeAlreadyRegistered { id: 1, .. })
+        ));
+        assert!(matches!(
+            registry.register(2, "a", 1, Arc::new(|_| Ok(()))),
+            Err(VmExecutionError::NativeAlreadyRegistered { .. })
+        ));
+        assert!(matches!(
+            registry.register(3, "c", 4, Arc::new(|_| Ok(()))),
+            Err(VmExecutionError::InvalidNativeArity { arity: 4, .. })
+        ));
+    }
+}
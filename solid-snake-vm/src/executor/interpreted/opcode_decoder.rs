@@ -18,6 +18,35 @@ pub enum VmParseError {
     InvalidArgument(String),
     UnknownInstruction(String),
     WrongArgumentCount { expected: usize, got: usize },
+    DuplicateLabel(String),
+    UndefinedLabel(String),
+    UnknownDirective(String),
+    InvalidDirective(String),
+    DuplicateConstant(String),
+    UndefinedConstant(String),
+    IncludeFailed(String),
+}
+
+impl std::error::Error for VmParseError {}
+
+impl std::fmt::Display for VmParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmParseError::InvalidRegister(reg) => write!(f, "Invalid register '{}'", reg),
+            VmParseError::InvalidArgument(arg) => write!(f, "Invalid argument '{}'", arg),
+            VmParseError::UnknownInstruction(name) => write!(f, "Unknown instruction '{}'", name),
+            VmParseError::WrongArgumentCount { expected, got } => {
+                write!(f, "Expected {} arguments, got {}", expected, got)
+            }
+            VmParseError::DuplicateLabel(label) => write!(f, "Duplicate label '{}'", label),
+            VmParseError::UndefinedLabel(label) => write!(f, "Undefined label '{}'", label),
+            VmParseError::UnknownDirective(name) => write!(f, "Unknown directive '{}'", name),
+            VmParseError::InvalidDirective(reason) => write!(f, "Invalid directive: {}", reason),
+            VmParseError::DuplicateConstant(name) => write!(f, "Duplicate constant '{}'", name),
+            VmParseError::UndefinedConstant(name) => write!(f, "Undefined constant '{}'", name),
+            VmParseError::IncludeFailed(reason) => write!(f, "Could not include {}", reason),
+        }
+    }
 }
 
 impl FromStr for RegisterType {
@@ -29,10 +58,7 @@ impl FromStr for RegisterType {
                 .parse::<u8>()
                 .map_err(|_| VmParseError::InvalidRegister(s.to_string()))?;
             if (idx as usize) >= MAX_REGISTERS {
-                return Err(VmParseError::InvalidRegister(format!(
-                    "Register index {} out of range",
-                    idx
-                )));
+                return Err(VmParseError::InvalidRegister(s.to_string()));
             }
             Ok(RegisterType::from(idx))
         } else {
@@ -129,19 +155,25 @@ macro_rules! impl_vm_parse_for_ints {
 impl_vm_parse_for_ints!(u8, u16, u32, u64, i8, i16, i32, i64);
 
 macro_rules! impl_vm_parse_for_floats {
-    ($($ty:ty),*) => {
+    ($($ty:ty => $bits:ty),*) => {
         $(
             impl VmParse for $ty {
                 fn parse_vm(s: &str) -> Result<Self, VmParseError> {
-                    s.parse::<$ty>()
-                        .map_err(|_| VmParseError::InvalidArgument(s.to_string()))
+                    // Hex is the raw bit pattern, so NaN payloads survive a round trip
+                    if let Some(stripped) = s.strip_prefix("0x") {
+                        <$bits>::from_str_radix(stripped, 16).map(<$ty>::from_bits)
+                            .map_err(|_| VmParseError::InvalidArgument(s.to_string()))
+                    } else {
+                        s.parse::<$ty>()
+                            .map_err(|_| VmParseError::InvalidArgument(s.to_string()))
+                    }
                 }
             }
         )*
     };
 }
 
-impl_vm_parse_for_floats!(f32, f64);
+impl_vm_parse_for_floats!(f32 => u32, f64 => u64);
 
 impl VmParse for RegisterType {
     fn parse_vm(s: &str) -> Result<Self, VmParseError> {
@@ -499,5 +531,21 @@ pub enum VmErrorCode {
     DivisionByZero = 3,
     InvalidRegisterAccess = 4,
     FloatInvalidResult = 5,
+    IoError = 6,
     // ...
 }
+
+impl VmErrorCode {
+    /// Whether the flag comes from arithmetic, only those stop execution in trap mode. Others, like
+    /// `IoError`, report a condition the program is expected to handle.
+    pub fn is_arithmetic(code: i64) -> bool {
+        [
+            VmErrorCode::Overflow,
+            VmErrorCode::Underflow,
+            VmErrorCode::DivisionByZero,
+            VmErrorCode::FloatInvalidResult,
+        ]
+        .iter()
+        .any(|&arithmetic| arithmetic as i64 == code)
+    }
+}
@@ -0,0 +1,424 @@
+use std::sync::atomic::{
+    AtomicI8, AtomicI16, AtomicI32, AtomicI64, AtomicU8, AtomicU16, AtomicU32, AtomicU64, Ordering,
+};
+
+use log::debug;
+use paste::paste;
+
+use crate::define_instruction;
+use crate::executor::ext::VmExecutionError;
+use crate::executor::interpreted::implimentation::{
+    RegisterFileExt, VmHeapExt, VmInterpretedExecutor,
+};
+use crate::executor::interpreted::opcode_decoder::RegisterType;
+
+define_instruction!(
+    AllocateShared,
+    "Allocates a shared segment of size from `reg_size` and stores its section index in `reg_target`. Shared segments can only be accessed with atomic instructions.",
+    [
+        (reg_target: RegisterType, "Register to store the section index"),
+        (reg_size: RegisterType, "Register containing the allocation size in bytes")
+    ],
+    [Allocation],
+    allocate_shared_handler
+);
+
+#[inline(always)]
+pub fn allocate_shared_handler(
+    executor: &mut VmInterpretedExecutor,
+    args: AllocateSharedArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_target, reg_size) = args;
+
+    let size: u64 = executor.registers().get_register_value(reg_size)?;
+    let section_idx = executor.heap_mut().alloc_shared(size as usize)?;
+    // The program counter already points past this instruction.
+    let pc = executor.get_program_counter()? - 1;
+    executor.heap_mut().record_alloc_pc(section_idx, pc)?;
+
+    debug!(
+        "AllocateShared: Segment with R{} ({}) bytes, store pointer to R{} ({})",
+        reg_size, size, reg_target, section_idx
+    );
+
+    executor
+        .registers_mut()
+        .set_register_value(reg_target, section_idx as u64)?;
+
+    Ok(())
+}
+
+// All atomic instructions are sequentially consistent. Offsets must be aligned to the size of the type.
+macro_rules! impl_atomic_instructions {
+    ($suffix:ident, $ty:ty, $atomic:ty) => {
+        paste! {
+            $crate::define_instruction!(
+                [<AtomicLoad $suffix>],
+                concat!("Atomically loads a ", stringify!($ty), " from the shared segment in `reg_ptr` at the byte offset in `reg_offset`."),
+                [
+                    (dest: RegisterType, "Destination register"),
+                    (reg_ptr: RegisterType, "Register containing the shared segment index"),
+                    (reg_offset: RegisterType, "Register containing the byte offset within the segment")
+                ],
+                [Memory],
+                [<AtomicLoad $suffix handler>]
+            );
+
+            $crate::define_instruction!(
+                [<AtomicStore $suffix>],
+                concat!("Atomically stores a ", stringify!($ty), " from `reg_value` into the shared segment in `reg_ptr` at the byte offset in `reg_offset`."),
+                [
+                    (reg_ptr: RegisterType, "Register containing the shared segment index"),
+                    (reg_value: RegisterType, "Register containing the value to store"),
+                    (reg_offset: RegisterType, "Register containing the byte offset within the segment")
+                ],
+                [Memory],
+                [<AtomicStore $suffix handler>]
+            );
+
+            $crate::define_instruction!(
+                [<AtomicSwap $suffix>],
+                concat!("Atomically replaces a ", stringify!($ty), " in a shared segment with `reg_value`, putting the previous value in `dest`."),
+                [
+                    (dest: RegisterType, "Register for the previous value"),
+                    (reg_ptr: RegisterType, "Register containing the shared segment index"),
+                    (reg_value: RegisterType, "Register containing the value to store"),
+                    (reg_offset: RegisterType, "Register containing the byte offset within the segment")
+                ],
+                [Memory],
+                [<AtomicSwap $suffix handler>]
+            );
+
+            $crate::define_instruction!(
+                [<CompareAndSwap $suffix>],
+                concat!("Atomically replaces a ", stringify!($ty), " in a shared segment with `reg_new` if it equals `reg_expected`. The previous value goes in `dest`; the swap happened if it equals `reg_expected`."),
+                [
+                    (dest: RegisterType, "Register for the previous value"),
+                    (reg_ptr: RegisterType, "Register containing the shared segment index"),
+                    (reg_expected: RegisterType, "Register containing the expected current value"),
+                    (reg_new: RegisterType, "Register containing the value to store"),
+                    (reg_offset: RegisterType, "Register containing the byte offset within the segment")
+                ],
+                [Memory],
+                [<CompareAndSwap $suffix handler>]
+            );
+
+            $crate::define_instruction!(
+                [<AtomicFetchAdd $suffix>],
+                concat!("Atomically adds `reg_value` to a ", stringify!($ty), " in a shared segment, wrapping on overflow, and puts the previous value in `dest`."),
+                [
+                    (dest: RegisterType, "Register for the previous value"),
+                    (reg_ptr: RegisterType, "Register containing the shared segment index"),
+                    (reg_value: RegisterType, "Register containing the value to add"),
+                    (reg_offset: RegisterType, "Register containing the byte offset within the segment")
+                ],
+                [Memory],
+                [<AtomicFetchAdd $suffix handler>]
+            );
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<AtomicLoad $suffix handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<AtomicLoad $suffix Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (dest, reg_ptr, reg_offset) = args;
+
+                let section_idx: u64 = executor.registers().get_register_value(reg_ptr)?;
+                let offset: u64 = executor.registers().get_register_value(reg_offset)?;
+
+                let value = executor
+                    .heap()
+                    .shared(section_idx as usize)?
+                    .atomic::<$atomic>(offset as usize)?
+                    .load(Ordering::SeqCst);
+
+                debug!("AtomicLoad: R{} <= {} ({}) from address R{} ({}) with offset R{} ({})", dest, stringify!($ty), value, reg_ptr, section_idx, reg_offset, offset);
+
+                executor.registers_mut().set_register_value(dest, value)?;
+
+                Ok(())
+            }
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<AtomicStore $suffix handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<AtomicStore $suffix Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (reg_ptr, reg_value, reg_offset) = args;
+
+                let section_idx: u64 = executor.registers().get_register_value(reg_ptr)?;
+                let offset: u64 = executor.registers().get_register_value(reg_offset)?;
+                let value: $ty = executor.registers().get_register_value(reg_value)?;
+
+                debug!("AtomicStore: {} R{} ({}) to address R{} ({}) with offset R{} ({})", stringify!($ty), reg_value, value, reg_ptr, section_idx, reg_offset, offset);
+
+                executor
+                    .heap()
+                    .shared(section_idx as usize)?
+                    .atomic::<$atomic>(offset as usize)?
+                    .store(value, Ordering::SeqCst);
+
+                Ok(())
+            }
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<AtomicSwap $suffix handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<AtomicSwap $suffix Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (dest, reg_ptr, reg_value, reg_offset) = args;
+
+                let section_idx: u64 = executor.registers().get_register_value(reg_ptr)?;
+                let offset: u64 = executor.registers().get_register_value(reg_offset)?;
+                let value: $ty = executor.registers().get_register_value(reg_value)?;
+
+                let previous = executor
+                    .heap()
+                    .shared(section_idx as usize)?
+                    .atomic::<$atomic>(offset as usize)?
+                    .swap(value, Ordering::SeqCst);
+
+                debug!("AtomicSwap: {} R{} ({}) into address R{} ({}) with offset R{} ({}), previous {} to R{}", stringify!($ty), reg_value, value, reg_ptr, section_idx, reg_offset, offset, previous, dest);
+
+                executor.registers_mut().set_register_value(dest, previous)?;
+
+                Ok(())
+            }
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<CompareAndSwap $suffix handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<CompareAndSwap $suffix Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (dest, reg_ptr, reg_expected, reg_new, reg_offset) = args;
+
+                let section_idx: u64 = executor.registers().get_register_value(reg_ptr)?;
+                let offset: u64 = executor.registers().get_register_value(reg_offset)?;
+                let expected: $ty = executor.registers().get_register_value(reg_expected)?;
+                let new: $ty = executor.registers().get_register_value(reg_new)?;
+
+                let previous = match executor
+                    .heap()
+                    .shared(section_idx as usize)?
+                    .atomic::<$atomic>(offset as usize)?
+                    .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)
+                {
+                    Ok(previous) | Err(previous) => previous,
+                };
+
+                debug!("CompareAndSwap: {} at address R{} ({}) with offset R{} ({}), expected R{} ({}), new R{} ({}), previous {} to R{}", stringify!($ty), reg_ptr, section_idx, reg_offset, offset, reg_expected, expected, reg_new, new, previous, dest);
+
+                executor.registers_mut().set_register_value(dest, previous)?;
+
+                Ok(())
+            }
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<AtomicFetchAdd $suffix handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<AtomicFetchAdd $suffix Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (dest, reg_ptr, reg_value, reg_offset) = args;
+
+                let section_idx: u64 = executor.registers().get_register_value(reg_ptr)?;
+                let offset: u64 = executor.registers().get_register_value(reg_offset)?;
+                let value: $ty = executor.registers().get_register_value(reg_value)?;
+
+                let previous = executor
+                    .heap()
+                    .shared(section_idx as usize)?
+                    .atomic::<$atomic>(offset as usize)?
+                    .fetch_add(value, Ordering::SeqCst);
+
+                debug!("AtomicFetchAdd: {} R{} ({}) to address R{} ({}) with offset R{} ({}), previous {} to R{}", stringify!($ty), reg_value, value, reg_ptr, section_idx, reg_offset, offset, previous, dest);
+
+                executor.registers_mut().set_register_value(dest, previous)?;
+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_atomic_instructions!(U8, u8, AtomicU8);
+impl_atomic_instructions!(U16, u16, AtomicU16);
+impl_atomic_instructions!(U32, u32, AtomicU32);
+impl_atomic_instructions!(U64, u64, AtomicU64);
+impl_atomic_instructions!(I8, i8, AtomicI8);
+impl_atomic_instructions!(I16, i16, AtomicI16);
+impl_atomic_instructions!(I32, i32, AtomicI32);
+impl_atomic_instructions!(I64, i64, AtomicI64);
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::ext::VmExecutionError;
+
+    #[test]
+    fn store_then_load() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(16u64, R!(1))
+            .setup_register(8u64, R!(2))
+            .setup_register(-42i32, R!(3))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                AtomicStoreI32Instruction::encode((R!(0), R!(3), R!(2))),
+                AtomicLoadI32Instruction::encode((R!(4), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(4), -42i32)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn swap_returns_previous() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .setup_register(0u64, R!(2))
+            .setup_register(7u64, R!(3))
+            .setup_register(9u64, R!(4))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                AtomicStoreU64Instruction::encode((R!(0), R!(3), R!(2))),
+                AtomicSwapU64Instruction::encode((R!(5), R!(0), R!(4), R!(2))),
+                AtomicLoadU64Instruction::encode((R!(6), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(5), 7u64)
+            .expect_register(R!(6), 9u64)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn compare_and_swap_success_and_failure() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(4u64, R!(1))
+            .setup_register(0u64, R!(2))
+            .setup_register(0u16, R!(3))
+            .setup_register(5u16, R!(4))
+            .setup_register(6u16, R!(5))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                // 0 -> 5 succeeds
+                CompareAndSwapU16Instruction::encode((R!(6), R!(0), R!(3), R!(4), R!(2))),
+                // expects 0 again, but it is 5 now
+                CompareAndSwapU16Instruction::encode((R!(7), R!(0), R!(3), R!(5), R!(2))),
+                AtomicLoadU16Instruction::encode((R!(8), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(6), 0u16)
+            .expect_register(R!(7), 5u16)
+            .expect_register(R!(8), 5u16)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn fetch_add_wraps() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(1u64, R!(1))
+            .setup_register(0u64, R!(2))
+            .setup_register(250u8, R!(3))
+            .setup_register(10u8, R!(4))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                AtomicStoreU8Instruction::encode((R!(0), R!(3), R!(2))),
+                AtomicFetchAddU8Instruction::encode((R!(5), R!(0), R!(4), R!(2))),
+                AtomicLoadU8Instruction::encode((R!(6), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(5), 250u8)
+            .expect_register(R!(6), 4u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn misaligned_offset_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(16u64, R!(1))
+            .setup_register(2u64, R!(2))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                AtomicLoadU32Instruction::encode((R!(3), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::MisalignedAtomicAccess(2))
+        ));
+    }
+
+    #[test]
+    fn out_of_bounds_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(4u64, R!(1))
+            .setup_register(4u64, R!(2))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                AtomicLoadU32Instruction::encode((R!(3), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::SegmentationFault)));
+    }
+
+    #[test]
+    fn non_atomic_access_to_shared_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .setup_register(0u64, R!(2))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))),
+                StoreIndirectWithOffsetU8Instruction::encode((R!(0), R!(2), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::NonAtomicSharedAccess(0))
+        ));
+    }
+
+    #[test]
+    fn atomic_access_to_private_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .setup_register(0u64, R!(2))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                AtomicLoadU8Instruction::encode((R!(3), R!(0), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::NotSharedSegment(0))));
+    }
+}
@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use log::debug;
+
+use crate::{
+    define_instruction,
+    executor::{
+        ext::VmExecutionError,
+        interpreted::{
+            channel::VmChannel,
+            implimentation::{RegisterFileExt, VmHeapExt, VmInterpretedExecutor},
+            opcode_decoder::RegisterType,
+        },
+    },
+};
+
+define_instruction!(
+    ChannelCreate,
+    "Creates a channel for moving heap sections between executors and stores its handle in `dest`. Executors spawned afterwards can use the same handle.",
+    [
+        (dest: RegisterType, "Register to store the channel handle")
+    ],
+    [Allocation],
+    channel_create
+);
+
+define_instruction!(
+    ChannelSend,
+    "Moves the heap section in `section` into the channel. The section is freed in this executor's heap.",
+    [
+        (channel: RegisterType, "Register containing the channel handle"),
+        (section: RegisterType, "Register containing the index of the section to send")
+    ],
+    [Memory, SideEffects],
+    channel_send
+);
+
+define_instruction!(
+    ChannelRecv,
+    "Waits for a section from the channel, adds it to this executor's heap and stores its index in `dest`. Fails if the channel is empty and no other executor holds it. An interrupt stops the wait and resuming waits again.",
+    [
+        (dest: RegisterType, "Register to store the received section index"),
+        (channel: RegisterType, "Register containing the channel handle")
+    ],
+    [Memory, SideEffects],
+    channel_recv
+);
+
+define_instruction!(
+    ChannelTryRecv,
+    "Takes a section from the channel if one is waiting, adds it to this executor's heap and stores its index in `dest`. Sets `received` to 1 if it got one, 0 otherwise.",
+    [
+        (dest: RegisterType, "Register to store the received section index"),
+        (received: RegisterType, "Register set to 1 if a section was received, 0 otherwise"),
+        (channel: RegisterType, "Register containing the channel handle")
+    ],
+    [Memory, SideEffects],
+    channel_try_recv
+);
+
+define_instruction!(
+    ChannelClose,
+    "Gives up this executor's hold on the channel. Receivers stop waiting once no other executor holds it. The handle is invalid afterwards.",
+    [
+        (channel: RegisterType, "Register containing the channel handle")
+    ],
+    [SideEffects],
+    channel_close
+);
+
+#[inline(always)]
+pub fn channel_create(
+    executor: &mut VmInterpretedExecutor,
+    args: ChannelCreateArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest,) = args;
+
+    let handle = executor.add_channel(Arc::new(VmChannel::default()));
+
+    debug!("ChannelCreate: Channel {handle} to R{dest}");
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, handle as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn channel_send(
+    executor: &mut VmInterpretedExecutor,
+    args: ChannelSendArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_channel, reg_section) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_channel)?;
+    let section_idx: u64 = executor.registers().get_register_value(reg_section)?;
+
+    debug!(
+        "ChannelSend: Section R{reg_section} ({section_idx}) to channel R{reg_channel} ({handle})"
+    );
+
+    let channel = Arc::clone(executor.channel(handle as usize)?);
+    let section = executor.heap_mut().take(section_idx as usize)?;
+    channel.send(section);
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn channel_recv(
+    executor: &mut VmInterpretedExecutor,
+    args: ChannelRecvArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, reg_channel) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_channel)?;
+
+    debug!("ChannelRecv: From channel R{reg_channel} ({handle}) to R{dest}");
+
+    let pc = executor.get_program_counter()? - 1;
+    let interrupt = executor.interrupt_handle();
+    let section = match executor.channel(handle as usize)?.recv(&interrupt, pc) {
+        Ok(section) => section,
+        Err(err) => {
+            // Stay on the ChannelRecv so resuming after an interrupt waits again.
+            if let VmExecutionError::Interrupted { .. } = err {
+                executor.set_program_counter(pc)?;
+            }
+            return Err(err);
+        }
+    };
+    let section_idx = executor.heap_mut().adopt(section)?;
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, section_idx as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn channel_try_recv(
+    executor: &mut VmInterpretedExecutor,
+    args: ChannelTryRecvArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, reg_received, reg_channel) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_channel)?;
+
+    debug!("ChannelTryRecv: From channel R{reg_channel} ({handle}) to R{dest}");
+
+    let received = match executor.channel(handle as usize)?.try_recv() {
+        Some(section) => {
+            let section_idx = executor.heap_mut().adopt(section)?;
+            executor
+                .registers_mut()
+                .set_register_value(dest, section_idx as u64)?;
+            true
+        }
+        None => false,
+    };
+
+    executor
+        .registers_mut()
+        .set_register_value(reg_received, received as u8)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn channel_close(
+    executor: &mut VmInterpretedExecutor,
+    args: ChannelCloseArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_channel,) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_channel)?;
+
+    debug!("ChannelClose: Channel R{reg_channel} ({handle})");
+
+    executor.close_channel(handle as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::ext::VmExecutionError;
+
+    #[test]
+    fn send_and_try_recv() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(4u64, R!(1))
+            .setup_register(7u8, R!(4))
+            .setup_register(0u64, R!(5))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                StoreIndirectWithOffsetU8Instruction::encode((R!(0), R!(4), R!(5))),
+                ChannelCreateInstruction::encode((R!(2),)),
+                ChannelSendInstruction::encode((R!(2), R!(0))),
+                ChannelTryRecvInstruction::encode((R!(3), R!(6), R!(2))),
+                LoadIndirectWithOffsetU8Instruction::encode((R!(7), R!(3), R!(5))),
+                ChannelTryRecvInstruction::encode((R!(8), R!(9), R!(2))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(6), 1u8)
+            .expect_register(R!(7), 7u8)
+            .expect_register(R!(9), 0u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn sender_loses_section() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(4u64, R!(1))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                ChannelCreateInstruction::encode((R!(2),)),
+                ChannelSendInstruction::encode((R!(2), R!(0))),
+                LoadIndirectWithOffsetU8Instruction::encode((R!(3), R!(0), R!(5))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::UseAfterFree(0))));
+    }
+
+    #[test]
+    fn recv_from_thread() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(0u64, R!(5))
+            .with_program(vec![
+                ChannelCreateInstruction::encode((R!(0),)),      // 0..3
+                SpawnInstruction::encode((38u64, R!(0), R!(2))), // 3..15
+                ChannelRecvInstruction::encode((R!(3), R!(0))),  // 15..19
+                LoadIndirectWithOffsetU8Instruction::encode((R!(4), R!(3), R!(5))), // 19..24
+                JoinInstruction::encode((R!(2), R!(6))),         // 24..28
+                HaltInstruction::encode((0,)),                   // 28..38
+                // thread function at offset 38, channel handle in R1
+                LoadImmediateU64Instruction::encode((R!(6), 1u64)),
+                AllocateInstruction::encode((R!(7), R!(6))),
+                LoadImmediateU8Instruction::encode((R!(8), 99u8)),
+                StoreIndirectWithOffsetU8Instruction::encode((R!(7), R!(8), R!(9))),
+                ChannelSendInstruction::encode((R!(1), R!(7))),
+                ReturnInstruction::encode(()),
+            ])
+            .expect_register(R!(4), 99u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn recv_without_senders_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                ChannelCreateInstruction::encode((R!(0),)),
+                ChannelRecvInstruction::encode((R!(1), R!(0))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::ChannelDisconnected)));
+    }
+
+    #[test]
+    fn recv_fails_once_peer_exits() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                ChannelCreateInstruction::encode((R!(0),)),      // 0..3
+                SpawnInstruction::encode((29u64, R!(0), R!(2))), // 3..15
+                ChannelRecvInstruction::encode((R!(1), R!(0))),  // 15..19
+                HaltInstruction::encode((0,)),                   // 19..29
+                // thread function at offset 29, returns without sending
+                ReturnInstruction::encode(()),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::ChannelDisconnected)));
+    }
+
+    #[test]
+    fn recv_fails_once_blocked_peer_closes() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        // The parent only joins, so closing its handle tells the thread nothing can arrive.
+        let result = crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                ChannelCreateInstruction::encode((R!(5),)),      // 0..3
+                SpawnInstruction::encode((32u64, R!(5), R!(2))), // 3..15
+                ChannelCloseInstruction::encode((R!(5),)),       // 15..18
+                JoinInstruction::encode((R!(2), R!(3))),         // 18..22
+                HaltInstruction::encode((0,)),                   // 22..32
+                // thread function at offset 32, channel handle in R1
+                ChannelRecvInstruction::encode((R!(0), R!(1))),
+                ReturnInstruction::encode(()),
+            ])
+            .run();
+
+        match result {
+            Err(VmExecutionError::ThreadFailed { error, .. }) => {
+                assert!(matches!(*error, VmExecutionError::ChannelDisconnected));
+            }
+            other => panic!("Expected the thread to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn interrupt_stops_blocked_recv() {
+        use std::time::Duration;
+
+        use crate::executor::ext::VmExecutorExt;
+        use crate::executor::interpreted::implimentation::VmInterpretedExecutor;
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        // The parent still holds the channel while it waits in Join, so only an interrupt ends the wait.
+        let program = [
+            ChannelCreateInstruction::encode((R!(5),)),      // 0..3
+            SpawnInstruction::encode((29u64, R!(5), R!(2))), // 3..15
+            JoinInstruction::encode((R!(2), R!(3))),         // 15..19
+            HaltInstruction::encode((0,)),                   // 19..29
+            // thread function at offset 29, channel handle in R1
+            ChannelRecvInstruction::encode((R!(0), R!(1))),
+            ReturnInstruction::encode(()),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        let handle = vm.interrupt_handle();
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.interrupt();
+        });
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+        canceller.join().unwrap();
+
+        match err {
+            VmExecutionError::ThreadFailed { error, .. } => {
+                assert!(matches!(*error, VmExecutionError::Interrupted { pc: 4 }));
+            }
+            other => panic!("Expected the thread to be interrupted, got {:?}", other),
+        }
+        assert_eq!(vm.get_program_counter().unwrap(), 2);
+    }
+}
@@ -0,0 +1,378 @@
+use log::debug;
+use paste::paste;
+
+use crate::executor::ext::VmExecutionError;
+use crate::executor::interpreted::implimentation::{RegisterFileExt, VmInterpretedExecutor};
+use crate::executor::interpreted::opcode_decoder::{RegisterType, VmErrorCode};
+use crate::set_error_if;
+
+// Results always follow Rust's `as`: integers wrap, floats saturate and NaN becomes 0.
+// The error flag tells whether the value survived the conversion.
+macro_rules! set_conversion_error {
+    (int_to_int, $executor:expr, $val:expr, $from:ty, $to:ty) => {
+        let lossy = <$to>::try_from($val).is_err();
+        let positive = $val > <$from>::default();
+        set_error_if!($executor, lossy && positive, VmErrorCode::Overflow);
+        set_error_if!($executor, lossy && !positive, VmErrorCode::Underflow);
+    };
+    (int_to_float, $executor:expr, $val:expr, $from:ty, $to:ty) => {};
+    (float_to_int, $executor:expr, $val:expr, $from:ty, $to:ty) => {
+        let invalid = !$val.is_finite();
+        // MAX + 1 and MIN are powers of two, so they are exact as f64 even when MAX isn't
+        let whole = f64::from($val.trunc());
+        set_error_if!($executor, invalid, VmErrorCode::FloatInvalidResult);
+        set_error_if!(
+            $executor,
+            !invalid && whole >= <$to>::MAX as f64 + 1.0,
+            VmErrorCode::Overflow
+        );
+        set_error_if!(
+            $executor,
+            !invalid && whole < <$to>::MIN as f64,
+            VmErrorCode::Underflow
+        );
+    };
+    (float_to_float, $executor:expr, $val:expr, $from:ty, $to:ty) => {
+        let lost = $val.is_finite() && ($val as $to).is_infinite();
+        set_error_if!($executor, lost && $val > 0.0, VmErrorCode::Overflow);
+        set_error_if!($executor, lost && $val < 0.0, VmErrorCode::Underflow);
+    };
+}
+
+macro_rules! impl_convert_instruction {
+    ($opcode:ident, $from:ty => $to:ty, $kind:ident) => {
+        paste! {
+            $crate::define_instruction!(
+                $opcode,
+                concat!(
+                    "Converts a ", stringify!($from), " register to ", stringify!($to),
+                    " and stores it in the destination register. Sets an error flag when the value does not fit."
+                ),
+                [
+                    (dest: RegisterType, "Destination register"),
+                    (source: RegisterType, "Source register")
+                ],
+                [Arithmetic, Pure],
+                [<$opcode handler>]
+            );
+
+            #[inline(always)]
+            #[allow(non_snake_case)]
+            fn [<$opcode handler>](
+                executor: &mut VmInterpretedExecutor,
+                args: [<$opcode Args>],
+            ) -> Result<(), VmExecutionError> {
+                let (dest, source) = args;
+
+                let val: $from = executor.registers().get_register_value(source)?;
+                let result = val as $to;
+
+                debug!(
+                    "Convert: R{} <= {} R{} ({}) as {} ({})",
+                    dest,
+                    stringify!($from),
+                    source,
+                    val,
+                    stringify!($to),
+                    result
+                );
+
+                set_conversion_error!($kind, executor, val, $from, $to);
+                executor
+                    .registers_mut()
+                    .set_register_value(dest, result)?;
+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_convert_instruction!(ConvertU8ToU16, u8 => u16, int_to_int);
+impl_convert_instruction!(ConvertU8ToU32, u8 => u32, int_to_int);
+impl_convert_instruction!(ConvertU8ToU64, u8 => u64, int_to_int);
+impl_convert_instruction!(ConvertU8ToI8, u8 => i8, int_to_int);
+impl_convert_instruction!(ConvertU8ToI16, u8 => i16, int_to_int);
+impl_convert_instruction!(ConvertU8ToI32, u8 => i32, int_to_int);
+impl_convert_instruction!(ConvertU8ToI64, u8 => i64, int_to_int);
+impl_convert_instruction!(ConvertU8ToF32, u8 => f32, int_to_float);
+impl_convert_instruction!(ConvertU8ToF64, u8 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertU16ToU8, u16 => u8, int_to_int);
+impl_convert_instruction!(ConvertU16ToU32, u16 => u32, int_to_int);
+impl_convert_instruction!(ConvertU16ToU64, u16 => u64, int_to_int);
+impl_convert_instruction!(ConvertU16ToI8, u16 => i8, int_to_int);
+impl_convert_instruction!(ConvertU16ToI16, u16 => i16, int_to_int);
+impl_convert_instruction!(ConvertU16ToI32, u16 => i32, int_to_int);
+impl_convert_instruction!(ConvertU16ToI64, u16 => i64, int_to_int);
+impl_convert_instruction!(ConvertU16ToF32, u16 => f32, int_to_float);
+impl_convert_instruction!(ConvertU16ToF64, u16 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertU32ToU8, u32 => u8, int_to_int);
+impl_convert_instruction!(ConvertU32ToU16, u32 => u16, int_to_int);
+impl_convert_instruction!(ConvertU32ToU64, u32 => u64, int_to_int);
+impl_convert_instruction!(ConvertU32ToI8, u32 => i8, int_to_int);
+impl_convert_instruction!(ConvertU32ToI16, u32 => i16, int_to_int);
+impl_convert_instruction!(ConvertU32ToI32, u32 => i32, int_to_int);
+impl_convert_instruction!(ConvertU32ToI64, u32 => i64, int_to_int);
+impl_convert_instruction!(ConvertU32ToF32, u32 => f32, int_to_float);
+impl_convert_instruction!(ConvertU32ToF64, u32 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertU64ToU8, u64 => u8, int_to_int);
+impl_convert_instruction!(ConvertU64ToU16, u64 => u16, int_to_int);
+impl_convert_instruction!(ConvertU64ToU32, u64 => u32, int_to_int);
+impl_convert_instruction!(ConvertU64ToI8, u64 => i8, int_to_int);
+impl_convert_instruction!(ConvertU64ToI16, u64 => i16, int_to_int);
+impl_convert_instruction!(ConvertU64ToI32, u64 => i32, int_to_int);
+impl_convert_instruction!(ConvertU64ToI64, u64 => i64, int_to_int);
+impl_convert_instruction!(ConvertU64ToF32, u64 => f32, int_to_float);
+impl_convert_instruction!(ConvertU64ToF64, u64 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertI8ToU8, i8 => u8, int_to_int);
+impl_convert_instruction!(ConvertI8ToU16, i8 => u16, int_to_int);
+impl_convert_instruction!(ConvertI8ToU32, i8 => u32, int_to_int);
+impl_convert_instruction!(ConvertI8ToU64, i8 => u64, int_to_int);
+impl_convert_instruction!(ConvertI8ToI16, i8 => i16, int_to_int);
+impl_convert_instruction!(ConvertI8ToI32, i8 => i32, int_to_int);
+impl_convert_instruction!(ConvertI8ToI64, i8 => i64, int_to_int);
+impl_convert_instruction!(ConvertI8ToF32, i8 => f32, int_to_float);
+impl_convert_instruction!(ConvertI8ToF64, i8 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertI16ToU8, i16 => u8, int_to_int);
+impl_convert_instruction!(ConvertI16ToU16, i16 => u16, int_to_int);
+impl_convert_instruction!(ConvertI16ToU32, i16 => u32, int_to_int);
+impl_convert_instruction!(ConvertI16ToU64, i16 => u64, int_to_int);
+impl_convert_instruction!(ConvertI16ToI8, i16 => i8, int_to_int);
+impl_convert_instruction!(ConvertI16ToI32, i16 => i32, int_to_int);
+impl_convert_instruction!(ConvertI16ToI64, i16 => i64, int_to_int);
+impl_convert_instruction!(ConvertI16ToF32, i16 => f32, int_to_float);
+impl_convert_instruction!(ConvertI16ToF64, i16 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertI32ToU8, i32 => u8, int_to_int);
+impl_convert_instruction!(ConvertI32ToU16, i32 => u16, int_to_int);
+impl_convert_instruction!(ConvertI32ToU32, i32 => u32, int_to_int);
+impl_convert_instruction!(ConvertI32ToU64, i32 => u64, int_to_int);
+impl_convert_instruction!(ConvertI32ToI8, i32 => i8, int_to_int);
+impl_convert_instruction!(ConvertI32ToI16, i32 => i16, int_to_int);
+impl_convert_instruction!(ConvertI32ToI64, i32 => i64, int_to_int);
+impl_convert_instruction!(ConvertI32ToF32, i32 => f32, int_to_float);
+impl_convert_instruction!(ConvertI32ToF64, i32 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertI64ToU8, i64 => u8, int_to_int);
+impl_convert_instruction!(ConvertI64ToU16, i64 => u16, int_to_int);
+impl_convert_instruction!(ConvertI64ToU32, i64 => u32, int_to_int);
+impl_convert_instruction!(ConvertI64ToU64, i64 => u64, int_to_int);
+impl_convert_instruction!(ConvertI64ToI8, i64 => i8, int_to_int);
+impl_convert_instruction!(ConvertI64ToI16, i64 => i16, int_to_int);
+impl_convert_instruction!(ConvertI64ToI32, i64 => i32, int_to_int);
+impl_convert_instruction!(ConvertI64ToF32, i64 => f32, int_to_float);
+impl_convert_instruction!(ConvertI64ToF64, i64 => f64, int_to_float);
+
+impl_convert_instruction!(ConvertF32ToU8, f32 => u8, float_to_int);
+impl_convert_instruction!(ConvertF32ToU16, f32 => u16, float_to_int);
+impl_convert_instruction!(ConvertF32ToU32, f32 => u32, float_to_int);
+impl_convert_instruction!(ConvertF32ToU64, f32 => u64, float_to_int);
+impl_convert_instruction!(ConvertF32ToI8, f32 => i8, float_to_int);
+impl_convert_instruction!(ConvertF32ToI16, f32 => i16, float_to_int);
+impl_convert_instruction!(ConvertF32ToI32, f32 => i32, float_to_int);
+impl_convert_instruction!(ConvertF32ToI64, f32 => i64, float_to_int);
+impl_convert_instruction!(ConvertF32ToF64, f32 => f64, float_to_float);
+
+impl_convert_instruction!(ConvertF64ToU8, f64 => u8, float_to_int);
+impl_convert_instruction!(ConvertF64ToU16, f64 => u16, float_to_int);
+impl_convert_instruction!(ConvertF64ToU32, f64 => u32, float_to_int);
+impl_convert_instruction!(ConvertF64ToU64, f64 => u64, float_to_int);
+impl_convert_instruction!(ConvertF64ToI8, f64 => i8, float_to_int);
+impl_convert_instruction!(ConvertF64ToI16, f64 => i16, float_to_int);
+impl_convert_instruction!(ConvertF64ToI32, f64 => i32, float_to_int);
+impl_convert_instruction!(ConvertF64ToI64, f64 => i64, float_to_int);
+impl_convert_instruction!(ConvertF64ToF32, f64 => f32, float_to_float);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::interpreted::opcode_impl::all::*;
+    use crate::{R, define_vm_tests};
+
+    define_vm_tests!(
+        convert_widening,
+        [
+            (ConvertU8ToU16, u16),
+            (ConvertU8ToU32, u32),
+            (ConvertU8ToU64, u64),
+            (ConvertU8ToI16, i16),
+            (ConvertU8ToI32, i32),
+            (ConvertU8ToI64, i64),
+            (ConvertU8ToF32, f32),
+            (ConvertU8ToF64, f64)
+        ],
+        VmTest::new().setup(200u8, R!(0)).expect(200 as T, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_negative_to_signed,
+        [
+            (ConvertI64ToI8, i8),
+            (ConvertI64ToI16, i16),
+            (ConvertI64ToI32, i32),
+            (ConvertI64ToF32, f32),
+            (ConvertI64ToF64, f64)
+        ],
+        VmTest::new().setup(-42i64, R!(0)).expect(-42 as T, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_narrowing_overflow,
+        [
+            (ConvertU16ToU8, u8),
+            (ConvertU16ToI8, i8),
+            (ConvertI32ToU8, u8),
+            (ConvertI64ToI8, i8)
+        ],
+        VmTest::new()
+            .setup(300i32, R!(0))
+            .expect(44 as T, R!(1))
+            .expect_error(VmErrorCode::Overflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_negative_to_unsigned,
+        [
+            (ConvertI8ToU8, u8),
+            (ConvertI16ToU16, u16),
+            (ConvertI32ToU32, u32),
+            (ConvertI64ToU64, u64)
+        ],
+        VmTest::new()
+            .setup(-1i8, R!(0))
+            .expect(T::MAX, R!(1))
+            .expect_error(VmErrorCode::Underflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_float_truncates,
+        [
+            (ConvertF64ToI8, i8),
+            (ConvertF64ToI16, i16),
+            (ConvertF64ToI32, i32),
+            (ConvertF64ToI64, i64)
+        ],
+        VmTest::new().setup(-3.9f64, R!(0)).expect(-3 as T, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_float_overflow,
+        [
+            (ConvertF32ToU8, u8),
+            (ConvertF32ToI8, i8),
+            (ConvertF32ToU16, u16),
+            (ConvertF32ToI16, i16)
+        ],
+        VmTest::new()
+            .setup(1e6f32, R!(0))
+            .expect(T::MAX, R!(1))
+            .expect_error(VmErrorCode::Overflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_float_underflow,
+        [
+            (ConvertF64ToU32, u32),
+            (ConvertF64ToU64, u64),
+            (ConvertF64ToI32, i32),
+            (ConvertF64ToI64, i64)
+        ],
+        VmTest::new()
+            .setup(-1e30f64, R!(0))
+            .expect(T::MIN, R!(1))
+            .expect_error(VmErrorCode::Underflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_nan,
+        [(ConvertF64ToI64, i64), (ConvertF64ToU8, u8)],
+        VmTest::new()
+            .setup(f64::NAN, R!(0))
+            .expect(0 as T, R!(1))
+            .expect_error(VmErrorCode::FloatInvalidResult),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_infinity,
+        [(ConvertF32ToI32, i32), (ConvertF32ToU64, u64)],
+        VmTest::new()
+            .setup(f32::INFINITY, R!(0))
+            .expect(T::MAX, R!(1))
+            .expect_error(VmErrorCode::FloatInvalidResult),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_float_just_past_max,
+        [(ConvertF32ToU64, u64), (ConvertF32ToI64, i64)],
+        VmTest::new()
+            .setup(T::MAX as f32, R!(0))
+            .expect(T::MAX, R!(1))
+            .expect_error(VmErrorCode::Overflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_float_at_min,
+        [(ConvertF64ToI64, i64), (ConvertF64ToI32, i32)],
+        VmTest::new()
+            .setup(T::MIN as f64, R!(0))
+            .expect(T::MIN, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_f64_to_f32_overflow,
+        [(ConvertF64ToF32, f32)],
+        VmTest::new()
+            .setup(-1e300f64, R!(0))
+            .expect(T::NEG_INFINITY, R!(1))
+            .expect_error(VmErrorCode::Underflow),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_f64_to_f32_in_range,
+        [(ConvertF64ToF32, f32)],
+        VmTest::new().setup(1.5f64, R!(0)).expect(1.5 as T, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    define_vm_tests!(
+        convert_f32_to_f64,
+        [(ConvertF32ToF64, f64)],
+        VmTest::new()
+            .setup(f32::MAX, R!(0))
+            .expect(f32::MAX as T, R!(1)),
+        (R!(1), R!(0))
+    );
+
+    #[test]
+    fn convert_keeps_nan_between_floats() {
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(f64::NAN, R!(0))
+            .with_program(vec![
+                ConvertF64ToF32Instruction::encode((R!(1), R!(0))),
+                ConvertF32ToF64Instruction::encode((R!(2), R!(1))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(2), f64::NAN.to_bits())
+            .expect_error(VmErrorCode::None)
+            .expect_pc(2)
+            .run()
+            .unwrap();
+    }
+}
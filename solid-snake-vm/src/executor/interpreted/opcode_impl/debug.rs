@@ -14,7 +14,7 @@ macro_rules! impl_dbg_instruction {
         paste! {
             $crate::define_instruction!(
                 $opcode,
-                concat!("Prints the value of a ", stringify!($ty), " register to stdout for debugging."),
+                concat!("Prints the value of a ", stringify!($ty), " register to the executor's output for debugging."),
                 [
                     (source: RegisterType, "Register to print")
                 ],
@@ -35,9 +35,7 @@ macro_rules! impl_dbg_instruction {
 
                 debug!("Debug: R{source}");
 
-                println!("R{} : {}", source, val);
-
-                Ok(())
+                executor.write_output(&format!("R{} : {}\n", source, val))
             }
         }
     };
@@ -56,7 +54,7 @@ impl_dbg_instruction!(DebugPrintF64, f64);
 
 crate::define_instruction!(
     DebugPrintRaw,
-    "Prints the raw 64-bit value of a register in hexadecimal for debugging.",
+    "Prints the raw 64-bit value of a register in hexadecimal to the executor's output for debugging.",
     [
         (reg: RegisterType, "Register to inspect as raw bits")
     ],
@@ -70,6 +68,5 @@ fn debug_print_raw(
 ) -> Result<(), VmExecutionError> {
     let (reg,) = args;
     let val = executor.registers().raw[usize::from(reg)];
-    println!("R{} raw bits: {:#018X}", reg, val);
-    Ok(())
+    executor.write_output(&format!("R{} raw bits: {:#018X}\n", reg, val))
 }
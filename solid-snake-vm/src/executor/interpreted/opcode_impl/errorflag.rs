@@ -0,0 +1,287 @@
+use log::debug;
+
+use crate::{
+    define_instruction,
+    executor::{
+        ext::VmExecutionError,
+        interpreted::{
+            implimentation::{RegisterFileExt, VmInterpretedExecutor},
+            opcode_decoder::RegisterType,
+        },
+    },
+};
+
+// The error flag is cleared before every instruction, so these read the flag left by the previous one.
+// They pass it on unchanged, which lets several checks in a row all see the same flag.
+
+define_instruction!(
+    ErrorFlagEqualIndirect,
+    "Sets 1 if the error flag left by the previous instruction equals the code in a register, 0 otherwise. Keeps the flag set.",
+    [
+        (dest: RegisterType, "Destination register for result (1 or 0)"),
+        (code: RegisterType, "Register holding the i64 error code to compare with")
+    ],
+    [Logical],
+    error_flag_equal_indirect
+);
+define_instruction!(
+    ErrorFlagEqualImmediate,
+    "Sets 1 if the error flag left by the previous instruction equals the given code, 0 otherwise. Keeps the flag set.",
+    [
+        (dest: RegisterType, "Destination register for result (1 or 0)"),
+        (code: i64, "Error code to compare with")
+    ],
+    [Logical],
+    error_flag_equal_immediate
+);
+define_instruction!(
+    JumpIfErrorIndirect,
+    "Jumps to the target address if the error flag left by the previous instruction equals the code in a register. Keeps the flag set.",
+    [
+        (target: u64, "Bytecode address(byte offset) to jump to"),
+        (code: RegisterType, "Register holding the i64 error code to compare with")
+    ],
+    [ControlFlow, SideEffects],
+    jump_if_error_indirect
+);
+define_instruction!(
+    JumpIfErrorImmediate,
+    "Jumps to the target address if the error flag left by the previous instruction equals the given code. Keeps the flag set.",
+    [
+        (target: u64, "Bytecode address(byte offset) to jump to"),
+        (code: i64, "Error code to compare with")
+    ],
+    [ControlFlow, SideEffects],
+    jump_if_error_immediate
+);
+
+#[inline(always)]
+fn carry_error_flag(executor: &mut VmInterpretedExecutor) -> i64 {
+    executor.error_code = executor.prev_error_code;
+    executor.prev_error_code
+}
+
+#[inline(always)]
+pub fn error_flag_equal_indirect(
+    executor: &mut VmInterpretedExecutor,
+    args: ErrorFlagEqualIndirectArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, code_reg) = args;
+
+    let code: i64 = executor.registers().get_register_value(code_reg)?;
+    let flag = carry_error_flag(executor);
+
+    debug!("ErrorFlagEqual: R{dest} <= flag ({flag}) == R{code_reg} ({code})");
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, (flag == code) as u8)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn error_flag_equal_immediate(
+    executor: &mut VmInterpretedExecutor,
+    args: ErrorFlagEqualImmediateArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, code) = args;
+
+    let flag = carry_error_flag(executor);
+
+    debug!("ErrorFlagEqual: R{dest} <= flag ({flag}) == {code}");
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, (flag == code) as u8)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn jump_if_error_indirect(
+    executor: &mut VmInterpretedExecutor,
+    args: JumpIfErrorIndirectArgs,
+) -> Result<(), VmExecutionError> {
+    let (target, code_reg) = args;
+
+    let code: i64 = executor.registers().get_register_value(code_reg)?;
+    let flag = carry_error_flag(executor);
+
+    debug!("Jump: To {target} if flag ({flag}) == R{code_reg} ({code})");
+
+    if flag == code {
+        executor.set_program_counter(target as usize)?;
+    }
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn jump_if_error_immediate(
+    executor: &mut VmInterpretedExecutor,
+    args: JumpIfErrorImmediateArgs,
+) -> Result<(), VmExecutionError> {
+    let (target, code) = args;
+
+    let flag = carry_error_flag(executor);
+
+    debug!("Jump: To {target} if flag ({flag}) == {code}");
+
+    if flag == code {
+        executor.set_program_counter(target as usize)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::interpreted::opcode_decoder::VmErrorCode;
+
+    #[test]
+    fn flag_seen_by_next_instruction() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(0u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                ErrorFlagEqualImmediateInstruction::encode((
+                    R!(3),
+                    VmErrorCode::DivisionByZero as i64,
+                )),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(3), 1u8)
+            .expect_error(VmErrorCode::DivisionByZero)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn flag_kept_across_checks() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(0u8, R!(1))
+            .setup_register(VmErrorCode::DivisionByZero as i64, R!(5))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                ErrorFlagEqualImmediateInstruction::encode((R!(3), VmErrorCode::Overflow as i64)),
+                ErrorFlagEqualIndirectInstruction::encode((R!(4), R!(5))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(3), 0u8)
+            .expect_register(R!(4), 1u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn flag_gone_after_other_instruction() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(0u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                IncrementU8Instruction::encode((R!(4), 1u8)),
+                ErrorFlagEqualImmediateInstruction::encode((
+                    R!(3),
+                    VmErrorCode::DivisionByZero as i64,
+                )),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(3), 0u8)
+            .expect_error(VmErrorCode::None)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn no_flag_equals_none() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(2u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                ErrorFlagEqualImmediateInstruction::encode((R!(3), VmErrorCode::None as i64)),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(2), 5u8)
+            .expect_register(R!(3), 1u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn jump_if_error_taken() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(0u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))), // 0..5
+                JumpIfErrorImmediateInstruction::encode((
+                    27u64,
+                    VmErrorCode::DivisionByZero as i64,
+                )), // 5..23
+                IncrementU8Instruction::encode((R!(3), 1u8)),       // <-- skipped
+                HaltInstruction::encode((0,)),                      // 27
+            ])
+            .expect_pc(3)
+            .expect_register(R!(3), 0u8)
+            .expect_error(VmErrorCode::DivisionByZero)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn jump_if_error_not_taken() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(10u8, R!(0))
+            .setup_register(2u8, R!(1))
+            .with_program(vec![
+                DivideU8Instruction::encode((R!(2), R!(0), R!(1))),
+                JumpIfErrorImmediateInstruction::encode((
+                    27u64,
+                    VmErrorCode::DivisionByZero as i64,
+                )),
+                IncrementU8Instruction::encode((R!(3), 1u8)), // executed
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_pc(3)
+            .expect_register(R!(3), 1u8)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn jump_if_error_indirect() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(u8::MAX, R!(0))
+            .setup_register(VmErrorCode::Overflow as i64, R!(5))
+            .with_program(vec![
+                IncrementU8Instruction::encode((R!(0), 1u8)), // 0..4
+                JumpIfErrorIndirectInstruction::encode((19u64, R!(5))), // 4..15
+                IncrementU8Instruction::encode((R!(3), 1u8)), // <-- skipped
+                HaltInstruction::encode((0,)),                // 19
+            ])
+            .expect_register(R!(3), 0u8)
+            .expect_error(VmErrorCode::Overflow)
+            .run()
+            .unwrap();
+    }
+}
@@ -28,7 +28,7 @@ define_instruction!(
 
 define_instruction!(
     Halt,
-    "Halts execution of the virtual machine immediately.",
+    "Halts execution of the virtual machine immediately and flushes its output.",
     [(exit_code: i64, "Exit code for the VM (default is 0)")],
     [ControlFlow, SideEffects],
     halt
@@ -181,6 +181,7 @@ mod tests {
         let err = test
             .with_program(vec![
                 CallFunctionInstruction::encode((0u64,)), // Any address, doesn't matter
+                HaltInstruction::encode((0,)),
             ])
             .run()
             .unwrap_err();
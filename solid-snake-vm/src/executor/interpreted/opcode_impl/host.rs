@@ -0,0 +1,475 @@
+use std::io::{Read, Write};
+
+use log::debug;
+
+use crate::{
+    define_instruction,
+    executor::{
+        ext::{VmExecutionError, VmExecutorExt},
+        interpreted::{
+            host::FileMode,
+            implimentation::{
+                RegisterFileExt, VmHeapExt, VmInterpretedExecutor, VmMemorySectionExt,
+            },
+            opcode_decoder::{RegisterType, VmErrorCode},
+        },
+    },
+};
+
+define_instruction!(
+    ReadLine,
+    "Reads a line from the host's input into a new heap section, newline included. Stores the section index in `dest` and its length in `length`, which is 0 at the end of the input. Needs the input capability, sets the IoError flag if reading fails.",
+    [
+        (dest: RegisterType, "Register to store the new section index"),
+        (length: RegisterType, "Register to store the number of bytes read")
+    ],
+    [Allocation, SideEffects],
+    read_line
+);
+
+define_instruction!(
+    ArgCount,
+    "Stores the number of arguments the host gave the program in `dest`. Needs the arguments capability.",
+    [
+        (dest: RegisterType, "Register to store the argument count")
+    ],
+    [SideEffects],
+    arg_count
+);
+
+define_instruction!(
+    ArgGet,
+    "Copies an argument into a new heap section as UTF-8 and stores the section index in `dest`. Needs the arguments capability, sets the IoError flag if `index` is out of range.",
+    [
+        (dest: RegisterType, "Register to store the new section index"),
+        (index: RegisterType, "Register containing the argument index")
+    ],
+    [Allocation, SideEffects],
+    arg_get
+);
+
+define_instruction!(
+    FileOpen,
+    "Opens the file whose UTF-8 path fills a heap section and stores a file handle in `dest`. Mode 0 reads, 1 creates or truncates and writes, 2 creates or appends. Fails unless a directory capability grants the access, sets the IoError flag if opening fails.",
+    [
+        (dest: RegisterType, "Register to store the file handle"),
+        (path: RegisterType, "Register containing the section index of the path"),
+        (mode: RegisterType, "Register containing the open mode")
+    ],
+    [SideEffects],
+    file_open
+);
+
+define_instruction!(
+    FileRead,
+    "Reads up to `length` bytes from a file into the start of a heap section and stores the number of bytes read in `count`, 0 at the end of the file. Sets the IoError flag if reading fails.",
+    [
+        (count: RegisterType, "Register to store the number of bytes read"),
+        (file: RegisterType, "Register containing the file handle"),
+        (section: RegisterType, "Register containing the section index to read into"),
+        (length: RegisterType, "Register containing the maximum number of bytes to read")
+    ],
+    [Memory, SideEffects],
+    file_read
+);
+
+define_instruction!(
+    FileWrite,
+    "Writes the first `length` bytes of a heap section to a file and stores the number of bytes written in `count`. Sets the IoError flag if writing fails.",
+    [
+        (count: RegisterType, "Register to store the number of bytes written"),
+        (file: RegisterType, "Register containing the file handle"),
+        (section: RegisterType, "Register containing the section index to write from"),
+        (length: RegisterType, "Register containing the number of bytes to write")
+    ],
+    [Memory, SideEffects],
+    file_write
+);
+
+define_instruction!(
+    FileClose,
+    "Closes a file handle. Each handle can be closed once.",
+    [
+        (file: RegisterType, "Register containing the file handle")
+    ],
+    [SideEffects],
+    file_close
+);
+
+/// Copies `bytes` into a new section, recorded like an `Allocate` at the current instruction.
+fn alloc_bytes(
+    executor: &mut VmInterpretedExecutor,
+    bytes: &[u8],
+) -> Result<usize, VmExecutionError> {
+    let section_idx = executor.heap_mut().alloc(bytes.len())?;
+    // The program counter already points past this instruction.
+    let pc = executor.get_program_counter()? - 1;
+    executor.heap_mut().record_alloc_pc(section_idx, pc)?;
+    // Like `Allocate` with size 0, an empty section has nothing to write and cannot be dereferenced.
+    if !bytes.is_empty() {
+        executor
+            .heap_mut()
+            .section_mut(section_idx)?
+            .dereference_bytes_mut()?
+            .copy_from_slice(bytes);
+    }
+    Ok(section_idx)
+}
+
+#[inline(always)]
+pub fn read_line(
+    executor: &mut VmInterpretedExecutor,
+    args: ReadLineArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, reg_length) = args;
+
+    let Ok(line) = executor.capabilities().read_line()? else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+    let section_idx = alloc_bytes(executor, &line)?;
+
+    debug!(
+        "ReadLine: {} bytes to section {section_idx} in R{dest}",
+        line.len()
+    );
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, section_idx as u64)?;
+    executor
+        .registers_mut()
+        .set_register_value(reg_length, line.len() as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn arg_count(
+    executor: &mut VmInterpretedExecutor,
+    args: ArgCountArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest,) = args;
+
+    let count = executor.capabilities().args()?.len();
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, count as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn arg_get(
+    executor: &mut VmInterpretedExecutor,
+    args: ArgGetArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, reg_index) = args;
+
+    let index: u64 = executor.registers().get_register_value(reg_index)?;
+    let Some(arg) = executor.capabilities().args()?.get(index as usize).cloned() else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+    let section_idx = alloc_bytes(executor, arg.as_bytes())?;
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, section_idx as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn file_open(
+    executor: &mut VmInterpretedExecutor,
+    args: FileOpenArgs,
+) -> Result<(), VmExecutionError> {
+    let (dest, reg_path, reg_mode) = args;
+
+    let path_idx: u64 = executor.registers().get_register_value(reg_path)?;
+    let mode: u64 = executor.registers().get_register_value(reg_mode)?;
+    let path_bytes = executor
+        .heap()
+        .section(path_idx as usize)?
+        .dereference_bytes()?;
+    let (Ok(path), Some(mode)) = (std::str::from_utf8(path_bytes), FileMode::from_u64(mode)) else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+
+    debug!("FileOpen: {path} as {mode:?} to R{dest}");
+
+    let Ok(file) = executor.capabilities().open(path, mode)? else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+    let handle = executor.add_file(file);
+
+    executor
+        .registers_mut()
+        .set_register_value(dest, handle as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn file_read(
+    executor: &mut VmInterpretedExecutor,
+    args: FileReadArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_count, reg_file, reg_section, reg_length) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_file)?;
+    let section_idx: u64 = executor.registers().get_register_value(reg_section)?;
+    let length: u64 = executor.registers().get_register_value(reg_length)?;
+
+    // Check the destination first so a bad section does not consume file data.
+    executor
+        .heap()
+        .section(section_idx as usize)?
+        .bytes_n(length as usize)?;
+
+    let mut buffer = vec![0; length as usize];
+    let Ok(count) = executor.file_mut(handle as usize)?.read(&mut buffer) else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+
+    debug!("FileRead: {count} bytes from file {handle} to section {section_idx}");
+
+    let watched = executor.watch_write(section_idx as usize, 0, count)?;
+    executor
+        .heap_mut()
+        .section_mut(section_idx as usize)?
+        .bytes_n_mut(count)?
+        .copy_from_slice(&buffer[..count]);
+    executor.watch_write_done(section_idx as usize, watched)?;
+
+    executor
+        .registers_mut()
+        .set_register_value(reg_count, count as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn file_write(
+    executor: &mut VmInterpretedExecutor,
+    args: FileWriteArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_count, reg_file, reg_section, reg_length) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_file)?;
+    let section_idx: u64 = executor.registers().get_register_value(reg_section)?;
+    let length: u64 = executor.registers().get_register_value(reg_length)?;
+
+    let bytes = executor
+        .heap()
+        .section(section_idx as usize)?
+        .bytes_n(length as usize)?
+        .to_vec();
+    executor.watch_read(section_idx as usize, 0, bytes.len())?;
+
+    debug!("FileWrite: {length} bytes from section {section_idx} to file {handle}");
+
+    let Ok(count) = executor.file_mut(handle as usize)?.write(&bytes) else {
+        executor.set_error(VmErrorCode::IoError as i64);
+        return Ok(());
+    };
+
+    executor
+        .registers_mut()
+        .set_register_value(reg_count, count as u64)?;
+
+    Ok(())
+}
+
+#[inline(always)]
+pub fn file_close(
+    executor: &mut VmInterpretedExecutor,
+    args: FileCloseArgs,
+) -> Result<(), VmExecutionError> {
+    let (reg_file,) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_file)?;
+
+    debug!("FileClose: {handle}");
+
+    executor.close_file(handle as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::host::HostCapabilities;
+    use crate::executor::interpreted::implimentation::{
+        RegisterFileExt, VmHeapExt, VmInterpretedExecutor, VmMemorySectionExt,
+    };
+    use crate::executor::interpreted::opcode_decoder::VmErrorCode;
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solid-snake-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run(vm: &mut VmInterpretedExecutor, program: &[Vec<u8>]) -> Result<i64, VmExecutionError> {
+        let bc = vm.preprocess_bytecode(&program.concat())?;
+        vm.execute_processeded_bytecode(&bc)
+    }
+
+    fn section_bytes(vm: &VmInterpretedExecutor, reg: u8) -> Vec<u8> {
+        let section: u64 = vm.registers().get_register_value(reg).unwrap();
+        vm.heap()
+            .section(section as usize)
+            .unwrap()
+            .dereference_bytes()
+            .unwrap()
+            .to_vec()
+    }
+
+    #[test]
+    fn reads_lines_and_args() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_capabilities(
+            HostCapabilities::new()
+                .with_input(Cursor::new("hello\n"))
+                .with_args(vec!["first".to_string(), "second".to_string()]),
+        );
+
+        run(
+            &mut vm,
+            &[
+                ReadLineInstruction::encode((R!(1), R!(2))),
+                ReadLineInstruction::encode((R!(3), R!(4))),
+                ArgCountInstruction::encode((R!(5),)),
+                LoadImmediateU64Instruction::encode((R!(6), 1u64)),
+                ArgGetInstruction::encode((R!(7), R!(6))),
+                HaltInstruction::encode((0,)),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(section_bytes(&vm, 1), b"hello\n");
+        let at_end: u64 = vm.registers().get_register_value(R!(4)).unwrap();
+        assert_eq!(at_end, 0);
+        let count: u64 = vm.registers().get_register_value(R!(5)).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(section_bytes(&vm, 7), b"second");
+    }
+
+    #[test]
+    fn denied_without_grant() {
+        let mut vm = VmInterpretedExecutor::new(None);
+
+        let err = run(
+            &mut vm,
+            &[
+                ArgCountInstruction::encode((R!(1),)),
+                HaltInstruction::encode((0,)),
+            ],
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, VmExecutionError::CapabilityDenied(_)));
+    }
+
+    #[test]
+    fn writes_and_reads_file_in_granted_dir() {
+        let dir = temp_dir("file-io");
+        let path = dir.join("out.txt");
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_capabilities(
+            HostCapabilities::new()
+                .with_args(vec![path.to_str().unwrap().to_string()])
+                .with_write_dir(&dir)
+                .unwrap(),
+        );
+
+        run(
+            &mut vm,
+            &[
+                // R1 = path section, R2 = file opened for writing
+                ArgGetInstruction::encode((R!(1), R!(0))),
+                LoadImmediateU64Instruction::encode((R!(3), 1u64)),
+                FileOpenInstruction::encode((R!(2), R!(1), R!(3))),
+                // Write the path itself to the file
+                LoadImmediateU64Instruction::encode((R!(4), 4u64)),
+                FileWriteInstruction::encode((R!(5), R!(2), R!(1), R!(4))),
+                FileCloseInstruction::encode((R!(2),)),
+                // Read it back into a new section
+                FileOpenInstruction::encode((R!(2), R!(1), R!(0))),
+                AllocateInstruction::encode((R!(6), R!(4))),
+                FileReadInstruction::encode((R!(7), R!(2), R!(6), R!(4))),
+                FileReadInstruction::encode((R!(8), R!(2), R!(6), R!(4))),
+                FileCloseInstruction::encode((R!(2),)),
+                HaltInstruction::encode((0,)),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            &path.to_str().unwrap().as_bytes()[..4]
+        );
+        assert_eq!(
+            section_bytes(&vm, 6),
+            &path.to_str().unwrap().as_bytes()[..4]
+        );
+        let read: u64 = vm.registers().get_register_value(R!(7)).unwrap();
+        assert_eq!(read, 4);
+        let at_end: u64 = vm.registers().get_register_value(R!(8)).unwrap();
+        assert_eq!(at_end, 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_file_sets_error_flag() {
+        let dir = temp_dir("missing");
+        let path = dir.join("missing.txt");
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_capabilities(
+            HostCapabilities::new()
+                .with_args(vec![path.to_str().unwrap().to_string()])
+                .with_read_dir(&dir)
+                .unwrap(),
+        );
+
+        run(
+            &mut vm,
+            &[
+                ArgGetInstruction::encode((R!(1), R!(0))),
+                FileOpenInstruction::encode((R!(2), R!(1), R!(0))),
+                ErrorFlagEqualImmediateInstruction::encode((R!(3), VmErrorCode::IoError as i64)),
+                HaltInstruction::encode((0,)),
+            ],
+        )
+        .unwrap();
+
+        let flagged: u64 = vm.registers().get_register_value(R!(3)).unwrap();
+        assert_eq!(flagged, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn closing_unknown_handle_fails() {
+        let err = crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                FileCloseInstruction::encode((R!(0),)),
+                HaltInstruction::encode((0,)),
+            ])
+            .run()
+            .unwrap_err();
+
+        assert!(matches!(err, VmExecutionError::InvalidFileHandle(0)));
+    }
+}
@@ -174,7 +174,7 @@ macro_rules! impl_load_indirect_with_offset {
                 let value = <$ty as FromBytes>::from_be_bytes(bytes);
                 executor.registers_mut().set_register_value(reg_dest, value)?;
 
-                Ok(())
+                executor.watch_read(section_idx as usize, offset as usize, std::mem::size_of::<$ty>())
             }
         }
     };
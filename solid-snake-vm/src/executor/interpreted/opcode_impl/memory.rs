@@ -9,9 +9,9 @@ use crate::executor::interpreted::opcode_decoder::RegisterType;
 
 define_instruction!(
     Allocate,
-    "Allocates a heap section of size from `reg_size` and stores the section index in `reg_target`.",
+    "Allocates a heap section of size from `reg_size` and stores a handle to the section in `reg_target`. Handles of freed sections stay invalid after the slot is reused.",
     [
-        (reg_target: RegisterType, "Register to store the section index"),
+        (reg_target: RegisterType, "Register to store the section handle"),
         (reg_size: RegisterType, "Register containing the allocation size in bytes")
     ],
     [Allocation],
@@ -20,9 +20,9 @@ define_instruction!(
 
 define_instruction!(
     Deallocate,
-    "Frees the heap section at the index given in `reg_target`.",
+    "Frees the heap section whose handle is in `reg_target`. Freeing it twice is an error.",
     [
-        (reg_target: RegisterType, "Register containing the section index to free")
+        (reg_target: RegisterType, "Register containing the handle of the section to free")
     ],
     [Allocation],
     deallocate_handler
@@ -63,6 +63,9 @@ pub fn allocate_handler(
 
     let size: u64 = executor.registers().get_register_value(reg_size)?;
     let section_idx = executor.heap_mut().alloc(size as usize)?;
+    // The program counter already points past this instruction.
+    let pc = executor.get_program_counter()? - 1;
+    executor.heap_mut().record_alloc_pc(section_idx, pc)?;
 
     debug!(
         "Allocate: Section with R{} ({}) bytes, store pointer to R{} ({})",
@@ -119,18 +122,18 @@ pub fn memcpy_handler(
     );
 
     let src = executor.heap().section(src_idx as usize)?;
-    println!("src len {}", src.len());
     let src_bytes = src
         .bytes_n_with_offset(size as usize, src_offset as usize)?
         .to_vec();
+    // The source is read first, so a watched read is reported even if the write is watched too.
+    let read = executor.watch_read(src_idx as usize, src_offset as usize, size as usize);
+    let watched = executor.watch_write(dest_idx as usize, dest_offset as usize, size as usize)?;
     let dest = executor.heap_mut().section_mut(dest_idx as usize)?;
-    println!("dest len {}", dest.len());
-
     let dest_bytes = dest.bytes_n_with_offset_mut(size as usize, dest_offset as usize)?;
 
     dest_bytes.copy_from_slice(&src_bytes);
 
-    Ok(())
+    read.and(executor.watch_write_done(dest_idx as usize, watched))
 }
 
 #[inline(always)]
@@ -152,12 +155,360 @@ pub fn memset_handler(
         size
     );
 
+    let watched = executor.watch_write(section_idx as usize, 0, size as usize)?;
     let section = executor.heap_mut().section_mut(section_idx as usize)?;
     let dest = section.bytes_n_mut(size as usize)?;
 
     dest.fill(value as u8);
 
-    Ok(())
+    executor.watch_write_done(section_idx as usize, watched)
 }
 
 // todo tests
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::ext::VmExecutionError;
+    use crate::executor::interpreted::implimentation::HeapLimits;
+
+    fn limited(limits: HeapLimits) -> crate::asm_internal::VmProgramTest {
+        let mut test = crate::asm_internal::VmProgramTest::new();
+        test.vm_mut().heap_mut().set_limits(limits);
+        test
+    }
+
+    #[test]
+    fn huge_allocation_is_refused() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = limited(HeapLimits {
+            max_section_bytes: 1024,
+            ..HeapLimits::default()
+        })
+        .setup_register(u64::MAX, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),
+            HaltInstruction::encode((0,)),
+        ])
+        .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::OutOfMemory {
+                requested: usize::MAX,
+                limit: 1024
+            })
+        ));
+    }
+
+    #[test]
+    fn huge_allocation_fails_without_limits() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        for allocate in [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateSharedInstruction::encode((R!(0), R!(1))),
+        ] {
+            let result = crate::asm_internal::VmProgramTest::new()
+                .setup_register(u64::MAX, R!(1))
+                .with_program(vec![allocate, HaltInstruction::encode((0,))])
+                .run();
+
+            assert!(matches!(
+                result,
+                Err(VmExecutionError::OutOfMemory {
+                    requested: usize::MAX,
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn live_bytes_are_released_on_free() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        limited(HeapLimits {
+            max_live_bytes: 16,
+            ..HeapLimits::default()
+        })
+        .setup_register(16u64, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),
+            DeallocateInstruction::encode((R!(0),)),
+            AllocateInstruction::encode((R!(2), R!(1))),
+            HaltInstruction::encode((0,)),
+        ])
+        .run()
+        .unwrap();
+
+        let result = limited(HeapLimits {
+            max_live_bytes: 16,
+            ..HeapLimits::default()
+        })
+        .setup_register(10u64, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateInstruction::encode((R!(2), R!(1))),
+            HaltInstruction::encode((0,)),
+        ])
+        .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::OutOfMemory {
+                requested: 20,
+                limit: 16
+            })
+        ));
+    }
+
+    #[test]
+    fn section_count_is_limited() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = limited(HeapLimits {
+            max_live_sections: 2,
+            ..HeapLimits::default()
+        })
+        .setup_register(1u64, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateSharedInstruction::encode((R!(2), R!(1))),
+            AllocateInstruction::encode((R!(3), R!(1))),
+            HaltInstruction::encode((0,)),
+        ])
+        .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::OutOfMemory {
+                requested: 3,
+                limit: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn empty_sections_count_as_live() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = limited(HeapLimits {
+            max_live_sections: 2,
+            ..HeapLimits::default()
+        })
+        .setup_register(0u64, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateInstruction::encode((R!(2), R!(1))),
+            AllocateInstruction::encode((R!(3), R!(1))),
+            HaltInstruction::encode((0,)),
+        ])
+        .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::OutOfMemory {
+                requested: 3,
+                limit: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn threads_share_the_limits() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = limited(HeapLimits {
+            max_live_bytes: 16,
+            ..HeapLimits::default()
+        })
+        .setup_register(10u64, R!(1))
+        .with_program(vec![
+            AllocateInstruction::encode((R!(0), R!(1))),     // 0..4
+            SpawnInstruction::encode((30u64, R!(1), R!(2))), // 4..16
+            JoinInstruction::encode((R!(2), R!(3))),         // 16..20
+            HaltInstruction::encode((0,)),                   // 20..30
+            // thread function at offset 30, size in R1
+            AllocateInstruction::encode((R!(4), R!(1))),
+            ReturnInstruction::encode(()),
+        ])
+        .run();
+
+        match result {
+            Err(VmExecutionError::ThreadFailed { error, .. }) => assert!(matches!(
+                *error,
+                VmExecutionError::OutOfMemory {
+                    requested: 20,
+                    limit: 16
+                }
+            )),
+            other => panic!("Expected the thread to run out of memory, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stale_handle_is_use_after_free() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                DeallocateInstruction::encode((R!(0),)),
+                AllocateInstruction::encode((R!(2), R!(1))),
+                MemSetInstruction::encode((R!(0), R!(3), R!(1))),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::UseAfterFree(0))));
+    }
+
+    #[test]
+    fn reused_slot_gets_new_handle() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                DeallocateInstruction::encode((R!(0),)),
+                AllocateInstruction::encode((R!(2), R!(1))),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(0), 0u64)
+            .expect_register(R!(2), 1u64 << 32)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn double_free_is_reported() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                DeallocateInstruction::encode((R!(0),)),
+                DeallocateInstruction::encode((R!(0),)),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(result, Err(VmExecutionError::DoubleFree(0))));
+    }
+
+    #[test]
+    fn freeing_a_free_slot_with_its_current_generation_is_double_free() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        // 1 << 32 is the handle the next allocation in slot 0 would get.
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .setup_register(1u64 << 32, R!(2))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                DeallocateInstruction::encode((R!(0),)),
+                DeallocateInstruction::encode((R!(2),)),
+                HaltInstruction::encode((0,)),
+            ])
+            .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::DoubleFree(0x1_0000_0000))
+        ));
+    }
+
+    #[test]
+    fn empty_sections_can_be_freed() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),
+                DeallocateInstruction::encode((R!(0),)),
+                HaltInstruction::encode((0,)),
+            ])
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn stats_and_leaks() {
+        use crate::executor::ext::VmExecutorExt;
+        use crate::executor::interpreted::implimentation::{
+            HeapLeak, HeapStats, RegisterFileExt, VmInterpretedExecutor,
+        };
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let program = [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            AllocateInstruction::encode((R!(3), R!(1))),
+            DeallocateInstruction::encode((R!(0),)),
+            AllocateInstruction::encode((R!(4), R!(2))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.registers_mut().set_register_value(R!(1), 8u64).unwrap();
+        vm.registers_mut().set_register_value(R!(2), 4u64).unwrap();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        assert_eq!(
+            vm.heap().stats(),
+            HeapStats {
+                live_sections: 2,
+                live_bytes: 12,
+                peak_bytes: 16,
+                total_allocations: 3,
+                total_frees: 1,
+                free_list_len: 0,
+            }
+        );
+        assert_eq!(
+            vm.heap().leak_report().0,
+            vec![
+                HeapLeak {
+                    handle: 1 << 32,
+                    size: 4,
+                    alloc_pc: Some(3),
+                },
+                HeapLeak {
+                    handle: 1,
+                    size: 8,
+                    alloc_pc: Some(1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unfreed_empty_section_is_reported() {
+        use crate::executor::ext::VmExecutorExt;
+        use crate::executor::interpreted::implimentation::{HeapLeak, VmInterpretedExecutor};
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let program = [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        assert_eq!(
+            vm.heap().leak_report().0,
+            vec![HeapLeak {
+                handle: 0,
+                size: 0,
+                alloc_pc: Some(0),
+            }]
+        );
+    }
+}
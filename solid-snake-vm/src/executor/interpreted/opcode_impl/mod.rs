@@ -1,15 +1,20 @@
 pub mod add;
+pub mod atomic;
 pub mod bitwiseand;
 pub mod bitwisenot;
 pub mod bitwiseor;
 pub mod bitwisexor;
+pub mod channel;
+pub mod convert;
 pub mod debug;
 pub mod decr;
 pub mod divide;
 pub mod equal;
+pub mod errorflag;
 pub mod function;
 pub mod greaterthan;
 pub mod greaterthanorequal;
+pub mod host;
 pub mod incr;
 pub mod jump;
 pub mod lessthan;
@@ -20,28 +25,35 @@ pub mod memory;
 pub mod modulo;
 pub mod mov;
 pub mod multiply;
+pub mod native;
 pub mod notequal;
 pub mod print;
 pub mod shiftleft;
 pub mod shiftright;
 pub mod store;
 pub mod subtract;
+pub mod thread;
 
 pub mod all {
     pub use super::*;
 
     pub use add::*;
+    pub use atomic::*;
     pub use bitwiseand::*;
     pub use bitwisenot::*;
     pub use bitwiseor::*;
     pub use bitwisexor::*;
+    pub use channel::*;
+    pub use convert::*;
     pub use debug::*;
     pub use decr::*;
     pub use divide::*;
     pub use equal::*;
+    pub use errorflag::*;
     pub use function::*;
     pub use greaterthan::*;
     pub use greaterthanorequal::*;
+    pub use host::*;
     pub use incr::*;
     pub use jump::*;
     pub use lessthan::*;
@@ -52,10 +64,12 @@ pub mod all {
     pub use modulo::*;
     pub use mov::*;
     pub use multiply::*;
+    pub use native::*;
     pub use notequal::*;
     pub use print::*;
     pub use shiftleft::*;
     pub use shiftright::*;
     pub use store::*;
     pub use subtract::*;
+    pub use thread::*;
 }
@@ -0,0 +1,130 @@
+use log::debug;
+
+use crate::{
+    define_instruction,
+    executor::{ext::VmExecutionError, interpreted::implimentation::VmInterpretedExecutor},
+};
+
+define_instruction!(
+    CallNative,
+    "Calls a host function registered on the executor under `fn_id`. Arguments are passed in R1..R3 and the result is returned in R0, like `CallFunction`. Programs calling unregistered ids are rejected when loaded.",
+    [
+        (fn_id: u32, "Id the host function was registered with")
+    ],
+    [SideEffects],
+    callnative
+);
+
+#[inline(always)]
+pub fn callnative(
+    executor: &mut VmInterpretedExecutor,
+    args: CallNativeArgs,
+) -> Result<(), VmExecutionError> {
+    let (fn_id,) = args;
+
+    let function = executor
+        .natives()
+        .get(fn_id)
+        .ok_or_else(|| VmExecutionError::UnresolvedNatives(vec![fn_id]))?
+        .function
+        .clone();
+
+    debug!("CallNative: {fn_id}");
+
+    function(executor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{
+        RegisterFileExt, VmHeapExt, VmInterpretedExecutor, VmMemorySectionExt,
+    };
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    #[test]
+    fn native_reads_args_and_returns() {
+        let mut test = crate::asm_internal::VmProgramTest::new();
+        test.vm_mut()
+            .register_native(1, "add3", 3, |executor| {
+                let registers = executor.registers();
+                let sum = registers.raw[1] + registers.raw[2] + registers.raw[3];
+                executor.registers_mut().set_register_value(R!(0), sum)
+            })
+            .unwrap();
+
+        test.setup_register(1u64, R!(1))
+            .setup_register(2u64, R!(2))
+            .setup_register(3u64, R!(3))
+            .with_program(vec![
+                CallNativeInstruction::encode((1u32,)),
+                HaltInstruction::encode((0,)),
+            ])
+            .expect_register(R!(0), 6u64)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn native_accesses_heap() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.register_native(2, "checksum", 1, |executor| {
+            let section: u64 = executor.registers().get_register_value(R!(1))?;
+            let sum = executor
+                .heap()
+                .section(section as usize)?
+                .dereference_bytes()?
+                .iter()
+                .map(|&byte| byte as u64)
+                .sum::<u64>();
+            executor.registers_mut().set_register_value(R!(0), sum)
+        })
+        .unwrap();
+
+        let program = [
+            LoadImmediateU64Instruction::encode((R!(2), 4u64)),
+            AllocateInstruction::encode((R!(1), R!(2))),
+            LoadImmediateU8Instruction::encode((R!(3), 5u8)),
+            MemSetInstruction::encode((R!(1), R!(3), R!(2))),
+            CallNativeInstruction::encode((2u32,)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let sum: u64 = vm.registers().get_register_value(R!(0)).unwrap();
+        assert_eq!(sum, 20);
+    }
+
+    #[test]
+    fn unresolved_native_rejected_at_load() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counter = calls.clone();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.register_native(1, "tick", 0, move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        })
+        .unwrap();
+
+        let program = [
+            CallNativeInstruction::encode((1u32,)),
+            CallNativeInstruction::encode((9u32,)),
+            CallNativeInstruction::encode((4u32,)),
+            CallNativeInstruction::encode((9u32,)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let Err(VmExecutionError::UnresolvedNatives(ids)) = vm.preprocess_bytecode(&program) else {
+            panic!("expected unresolved natives");
+        };
+
+        assert_eq!(ids, [4, 9]);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+    }
+}
@@ -10,7 +10,7 @@ use crate::executor::{
 
 crate::define_instruction!(
     Print,
-    "Prints a UTF-8 string from memory to standard output. Reads `length` bytes from `section_id` starting at `offset`, decodes as UTF-8, and prints the resulting string.",
+    "Prints a UTF-8 string from memory to the executor's output, stdout by default. Reads `length` bytes from `section_id` starting at `offset`, decodes as UTF-8, and prints the resulting string.",
     [
         (reg_section_id: RegisterType, "Register containing the heap section index"),
         (reg_offset: RegisterType, "Register containing the byte offset into the section"),
@@ -36,9 +36,7 @@ fn print_raw(
 
     let text: Cow<str> = String::from_utf8_lossy(bytes);
 
-    print!("{}", text);
-
-    Ok(())
+    executor.write_output(&text)
 }
 
 // formatting instrs
@@ -42,12 +42,13 @@ macro_rules! impl_store_indirect_with_offset {
 
                 debug!("Store: {} R{} ({}) to address R{} ({}) with offset R{} {}", stringify!($ty), reg_value, value, reg_ptr, section_idx, reg_offset, offset);
 
+                let watched = executor.watch_write(section_idx as usize, offset as usize, bytes.len())?;
                 let section = executor.heap_mut().section_mut(section_idx as usize)?;
                 let target = section.bytes_n_with_offset_mut(bytes.len(), offset as usize)?;
 
                 target.copy_from_slice(bytes);
 
-                Ok(())
+                executor.watch_write_done(section_idx as usize, watched)
             }
         }
     };
@@ -97,12 +98,13 @@ macro_rules! impl_store_from_imm_with_offset {
 
                 let bytes = &value.to_be_bytes();
 
+                let watched = executor.watch_write(section_idx as usize, offset as usize, bytes.len())?;
                 let section = executor.heap_mut().section_mut(section_idx as usize)?;
                 let target = section.bytes_n_with_offset_mut(bytes.len(), offset as usize)?;
 
                 target.copy_from_slice(bytes);
 
-                Ok(())
+                executor.watch_write_done(section_idx as usize, watched)
             }
         }
     };
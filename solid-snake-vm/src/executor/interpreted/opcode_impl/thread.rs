@@ -0,0 +1,202 @@
+use std::thread::JoinHandle;
+
+use log::debug;
+
+use crate::{
+    define_instruction,
+    executor::{
+        ext::VmExecutionError,
+        interpreted::{
+            implimentation::{RegisterFileExt, ThreadExit, VmInterpretedExecutor, VmThread},
+            opcode_decoder::RegisterType,
+        },
+    },
+};
+
+define_instruction!(
+    Spawn,
+    "Starts a new executor on its own thread, calling the function at the target address with `arg` in R1. The new executor has a private heap and only sees shared segments. Stores a handle to the thread in `handle`.",
+    [
+        (target: u64, "Bytecode address (offset) of the function to run"),
+        (arg: RegisterType, "Register whose value is passed in R1"),
+        (handle: RegisterType, "Register to store the thread handle")
+    ],
+    [ControlFlow, SideEffects],
+    spawn
+);
+
+define_instruction!(
+    Join,
+    "Waits for a spawned thread to finish and stores its R0 in `result`. If the thread fails, fails with `ThreadFailed` and stays on the `Join`, so resuming joins again and the thread carries on where it stopped. Each finished thread can be joined once.",
+    [
+        (handle: RegisterType, "Register containing the thread handle"),
+        (result: RegisterType, "Register to store the thread's R0")
+    ],
+    [SideEffects],
+    join
+);
+
+#[inline(always)]
+pub fn spawn(
+    executor: &mut VmInterpretedExecutor,
+    args: SpawnArgs,
+) -> Result<(), VmExecutionError> {
+    let (target, reg_arg, reg_handle) = args;
+
+    let arg: u64 = executor.registers().get_register_value(reg_arg)?;
+    let child = executor.spawn_child(target as usize, arg);
+    let handle = executor.add_thread(VmThread::Running(run_thread(Box::new(child))));
+
+    debug!("Spawn: Thread {handle} at {target} with R{reg_arg} ({arg}), handle to R{reg_handle}");
+
+    executor
+        .registers_mut()
+        .set_register_value(reg_handle, handle as u64)?;
+
+    Ok(())
+}
+
+// Runs a child executor from its program counter on a new thread.
+fn run_thread(mut child: Box<VmInterpretedExecutor>) -> JoinHandle<ThreadExit> {
+    let program = child.program();
+
+    std::thread::spawn(move || {
+        let processed_bytecode = program
+            .iter()
+            .map(|decoded_instr| (*decoded_instr, decoded_instr.exec_instr_fn()))
+            .collect::<Vec<_>>();
+        let result = child
+            .resume_processeded_bytecode(&processed_bytecode)
+            .and_then(|_| child.registers().get_register_value(0usize));
+        (child, result)
+    })
+}
+
+#[inline(always)]
+pub fn join(executor: &mut VmInterpretedExecutor, args: JoinArgs) -> Result<(), VmExecutionError> {
+    let (reg_handle, reg_result) = args;
+
+    let handle: u64 = executor.registers().get_register_value(reg_handle)?;
+
+    debug!("Join: Thread R{reg_handle} ({handle}), result to R{reg_result}");
+
+    let thread = match executor.take_thread(handle as usize)? {
+        VmThread::Running(thread) => thread,
+        VmThread::Stopped(child) => run_thread(child),
+    };
+    let (child, result) = thread
+        .join()
+        .map_err(|_| VmExecutionError::ThreadPanicked)?;
+
+    match result {
+        Ok(result) => executor
+            .registers_mut()
+            .set_register_value(reg_result, result),
+        Err(error) => {
+            // Keep the thread and stay on the Join, e.g. so a host that adds fuel after the
+            // thread ran out can resume and still get the thread's result.
+            executor.return_thread(handle as usize, VmThread::Stopped(child));
+            let pc = executor.get_program_counter()?;
+            executor.set_program_counter(pc - 1)?;
+            Err(VmExecutionError::ThreadFailed {
+                handle: handle as usize,
+                error: Box::new(error),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::R;
+    use crate::executor::ext::VmExecutionError;
+
+    #[test]
+    fn spawn_and_join() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(41u64, R!(1))
+            .with_program(vec![
+                SpawnInstruction::encode((26u64, R!(1), R!(2))), // 0..12
+                JoinInstruction::encode((R!(2), R!(3))),         // 12..16
+                HaltInstruction::encode((0,)),                   // 16..26
+                // thread function at offset 26
+                IncrementU8Instruction::encode((R!(1), 1u8)),
+                MoveI64Instruction::encode((R!(0), R!(1))),
+                ReturnInstruction::encode(()),
+            ])
+            .expect_register(R!(3), 42u64)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn thread_sees_shared_segment() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .setup_register(0u64, R!(5))
+            .with_program(vec![
+                AllocateSharedInstruction::encode((R!(0), R!(1))), // 0..4
+                SpawnInstruction::encode((35u64, R!(0), R!(2))),   // 4..16
+                JoinInstruction::encode((R!(2), R!(3))),           // 16..20
+                AtomicLoadU64Instruction::encode((R!(4), R!(0), R!(5))), // 20..25
+                HaltInstruction::encode((0,)),                     // 25..35
+                // thread function at offset 35, segment handle in R1
+                LoadImmediateU64Instruction::encode((R!(6), 7u64)),
+                AtomicFetchAddU64Instruction::encode((R!(7), R!(1), R!(6), R!(5))),
+                ReturnInstruction::encode(()),
+            ])
+            .expect_register(R!(4), 7u64)
+            .run()
+            .unwrap();
+    }
+
+    #[test]
+    fn thread_error_propagates_and_private_heap_hidden() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .setup_register(8u64, R!(1))
+            .with_program(vec![
+                AllocateInstruction::encode((R!(0), R!(1))),     // 0..4
+                SpawnInstruction::encode((30u64, R!(0), R!(2))), // 4..16
+                JoinInstruction::encode((R!(2), R!(3))),         // 16..20
+                HaltInstruction::encode((0,)),                   // 20..30
+                // thread function at offset 30, parent's private section index in R1
+                MemSetInstruction::encode((R!(1), R!(5), R!(5))),
+                ReturnInstruction::encode(()),
+            ])
+            .run();
+
+        match result {
+            Err(VmExecutionError::ThreadFailed { handle, error }) => {
+                assert_eq!(handle, 0);
+                assert!(matches!(*error, VmExecutionError::NullPointerException));
+            }
+            other => panic!("Expected the thread to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn join_twice_fails() {
+        use crate::executor::interpreted::opcode_impl::all::*;
+
+        let result = crate::asm_internal::VmProgramTest::new()
+            .with_program(vec![
+                SpawnInstruction::encode((30u64, R!(1), R!(2))), // 0..12
+                JoinInstruction::encode((R!(2), R!(3))),         // 12..16
+                JoinInstruction::encode((R!(2), R!(3))),         // 16..20
+                HaltInstruction::encode((0,)),                   // 20..30
+                ReturnInstruction::encode(()),
+            ])
+            .run();
+
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::InvalidThreadHandle(0))
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Where `Print` and the `DebugPrint*` instructions write, see `VmInterpretedExecutor::set_output`.
+/// Spawned executors write to the sink of their parent.
+pub trait OutputSink: Send {
+    fn write_str(&mut self, text: &str) -> io::Result<()>;
+
+    /// Called when the program halts.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The default sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write_str(&mut self, text: &str) -> io::Result<()> {
+        io::stdout().lock().write_all(text.as_bytes())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+/// Keeps the output in memory. Clones share the buffer, so keep one to read what the program printed.
+#[derive(Debug, Clone, Default)]
+pub struct BufferSink {
+    buffer: Arc<Mutex<String>>,
+}
+
+impl BufferSink {
+    pub fn contents(&self) -> String {
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn take(&self) -> String {
+        std::mem::take(&mut *self.buffer.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl OutputSink for BufferSink {
+    fn write_str(&mut self, text: &str) -> io::Result<()> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push_str(text);
+        Ok(())
+    }
+}
+
+/// Hands every write to a closure, e.g. to route output to `log`.
+pub struct CallbackSink<F> {
+    callback: F,
+}
+
+impl<F: FnMut(&str) + Send> CallbackSink<F> {
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+impl<F: FnMut(&str) + Send> OutputSink for CallbackSink<F> {
+    fn write_str(&mut self, text: &str) -> io::Result<()> {
+        (self.callback)(text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use super::{BufferSink, CallbackSink, OutputSink};
+    use crate::R;
+    use crate::executor::ext::VmExecutorExt;
+    use crate::executor::interpreted::implimentation::VmInterpretedExecutor;
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn hello_program() -> Vec<u8> {
+        [
+            LoadImmediateU64Instruction::encode((R!(2), 2u64)),
+            AllocateInstruction::encode((R!(1), R!(2))),
+            LoadImmediateU8Instruction::encode((R!(3), b'h')),
+            StoreIndirectWithOffsetU8Instruction::encode((R!(1), R!(3), R!(4))),
+            LoadImmediateU8Instruction::encode((R!(3), b'i')),
+            IncrementU8Instruction::encode((R!(4), 1u8)),
+            StoreIndirectWithOffsetU8Instruction::encode((R!(1), R!(3), R!(4))),
+            LoadImmediateU8Instruction::encode((R!(4), 0u8)),
+            PrintInstruction::encode((R!(1), R!(4), R!(2))),
+            DebugPrintU8Instruction::encode((R!(3),)),
+            DebugPrintRawInstruction::encode((R!(2),)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn buffer_captures_prints() {
+        let sink = BufferSink::default();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_output(sink.clone());
+        let bc = vm.preprocess_bytecode(&hello_program()).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        assert_eq!(sink.take(), "hiR3 : 105\nR2 raw bits: 0x0000000000000002\n");
+        assert_eq!(sink.contents(), "");
+    }
+
+    #[test]
+    fn callback_receives_writes() {
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let received = writes.clone();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_output(CallbackSink::new(move |text| {
+            received.lock().unwrap().push(text.to_string())
+        }));
+        let bc = vm.preprocess_bytecode(&hello_program()).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        assert_eq!(writes.lock().unwrap()[0], "hi");
+        assert_eq!(writes.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn flushed_on_halt() {
+        struct Flushes(Arc<Mutex<Vec<&'static str>>>);
+
+        impl OutputSink for Flushes {
+            fn write_str(&mut self, _: &str) -> io::Result<()> {
+                self.0.lock().unwrap().push("write");
+                Ok(())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.lock().unwrap().push("flush");
+                Ok(())
+            }
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.set_output(Flushes(events.clone()));
+        let program = [
+            DebugPrintU8Instruction::encode((R!(0),)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), ["write", "flush"]);
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::opcodes::OpCode;
+
+/// Counters for one function, identified by the instruction index its `CallFunction` jumps to.
+/// The code a run starts in counts as a function too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub entry: usize,
+    pub calls: u64,
+    /// Instructions executed in this function itself, not in its callees.
+    pub instructions: u64,
+    /// Time spent in this function and its callees. Recursive calls are only counted once.
+    pub inclusive: Duration,
+    pub exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct StackNode {
+    function: usize,
+    parent: usize,
+    children: HashMap<usize, usize>,
+    instructions: u64,
+}
+
+#[derive(Debug)]
+struct ActiveCall {
+    profile: usize,
+    node: usize,
+    start: Instant,
+    callees: Duration,
+}
+
+/// Counts executed instructions per instruction index, per opcode and per function, see
+/// `VmInterpretedExecutor::set_profiler`. Every instruction is counted, nothing is sampled.
+#[derive(Debug)]
+pub struct Profiler {
+    instruction_counts: Vec<u64>,
+    opcode_counts: Box<[u64]>,
+    functions: Vec<FunctionProfile>,
+    function_indices: HashMap<usize, usize>,
+    // Open calls per function, a call closing with another one still open is recursive.
+    active_calls: Vec<u32>,
+    // Node 0 is the host, the calls made from a node are its children.
+    nodes: Vec<StackNode>,
+    stack: Vec<ActiveCall>,
+    // Set while the open calls wait for a run that stopped early to be resumed.
+    suspended: Option<Instant>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let len = OpCode::variant_iter()
+            .map(|opcode| opcode as usize + 1)
+            .max()
+            .unwrap_or_default();
+
+        Self {
+            instruction_counts: Vec::new(),
+            opcode_counts: vec![0; len].into_boxed_slice(),
+            functions: Vec::new(),
+            function_indices: HashMap::new(),
+            active_calls: Vec::new(),
+            nodes: vec![StackNode {
+                function: usize::MAX,
+                parent: 0,
+                children: HashMap::new(),
+                instructions: 0,
+            }],
+            stack: Vec::new(),
+            suspended: None,
+        }
+    }
+
+    /// Executions of each instruction, indexed like the preprocessed program.
+    pub fn instruction_counts(&self) -> &[u64] {
+        self.instruction_counts.as_slice()
+    }
+
+    /// Executions of each opcode that ran at least once, most executed first.
+    pub fn opcode_counts(&self) -> Vec<(OpCode, u64)> {
+        let mut counts: Vec<(OpCode, u64)> = OpCode::variant_iter()
+            .map(|opcode| (opcode, self.opcode_counts[opcode as usize]))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        counts
+    }
+
+    /// Functions in the order they were first called, the function a run started in comes first.
+    pub fn functions(&self) -> &[FunctionProfile] {
+        self.functions.as_slice()
+    }
+
+    /// Writes one `caller;callee count` line per call stack, weighted by executed instructions,
+    /// the folded format flamegraph tools read.
+    pub fn write_folded(&self, mut out: impl Write) -> io::Result<()> {
+        for (idx, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.instructions == 0 {
+                continue;
+            }
+
+            let mut frames = Vec::new();
+            let mut current = idx;
+            while current != 0 {
+                frames.push(function_name(self.nodes[current].function));
+                current = self.nodes[current].parent;
+            }
+            frames.reverse();
+            writeln!(out, "{} {}", frames.join(";"), node.instructions)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the function a run starts in, unless a previous run stopped inside it and this one
+    /// carries on there.
+    pub(crate) fn enter_run(&mut self, pc: usize) {
+        self.resume_clocks();
+        if self.stack.is_empty() {
+            self.enter(pc);
+        }
+    }
+
+    /// Keeps the open calls of a run that stopped before halting, e.g. with `OutOfFuel`, so a
+    /// resumed run carries on in them. The time until then is not counted.
+    pub(crate) fn suspend_run(&mut self) {
+        self.suspended = Some(Instant::now());
+    }
+
+    /// Closes every open call once the program halted, so the times include runs that ended
+    /// inside a function.
+    pub(crate) fn exit_run(&mut self) {
+        self.resume_clocks();
+        while !self.stack.is_empty() {
+            self.close();
+        }
+    }
+
+    fn resume_clocks(&mut self) {
+        if let Some(suspended) = self.suspended.take() {
+            let paused = suspended.elapsed();
+            for active in self.stack.iter_mut() {
+                active.start += paused;
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn count(&mut self, pc: usize, opcode: OpCode) {
+        if pc >= self.instruction_counts.len() {
+            self.instruction_counts.resize(pc + 1, 0);
+        }
+        self.instruction_counts[pc] += 1;
+        self.opcode_counts[opcode as usize] += 1;
+
+        if let Some(active) = self.stack.last() {
+            self.functions[active.profile].instructions += 1;
+            self.nodes[active.node].instructions += 1;
+        }
+    }
+
+    pub(crate) fn enter(&mut self, function: usize) {
+        let profile = *self.function_indices.entry(function).or_insert_with(|| {
+            self.functions.push(FunctionProfile {
+                entry: function,
+                calls: 0,
+                instructions: 0,
+                inclusive: Duration::ZERO,
+                exclusive: Duration::ZERO,
+            });
+            self.active_calls.push(0);
+            self.functions.len() - 1
+        });
+        self.functions[profile].calls += 1;
+        self.active_calls[profile] += 1;
+
+        let parent = self.stack.last().map_or(0, |active| active.node);
+        let node = match self.nodes[parent].children.get(&function) {
+            Some(&node) => node,
+            None => {
+                self.nodes.push(StackNode {
+                    function,
+                    parent,
+                    children: HashMap::new(),
+                    instructions: 0,
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent].children.insert(function, node);
+                node
+            }
+        };
+
+        self.stack.push(ActiveCall {
+            profile,
+            node,
+            start: Instant::now(),
+            callees: Duration::ZERO,
+        });
+    }
+
+    /// Closes the innermost call. A return out of the function the run started in is ignored,
+    /// `exit_run` closes that one.
+    pub(crate) fn exit(&mut self) {
+        if self.stack.len() > 1 {
+            self.close();
+        }
+    }
+
+    fn close(&mut self) {
+        let Some(active) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = active.start.elapsed();
+
+        self.active_calls[active.profile] -= 1;
+        let function = &mut self.functions[active.profile];
+        function.exclusive += elapsed.saturating_sub(active.callees);
+        if self.active_calls[active.profile] == 0 {
+            function.inclusive += elapsed;
+        }
+
+        if let Some(caller) = self.stack.last_mut() {
+            caller.callees += elapsed;
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut functions: Vec<&FunctionProfile> = self.functions.iter().collect();
+        functions.sort_by_key(|function| std::cmp::Reverse(function.inclusive));
+
+        writeln!(
+            f,
+            "{:<12} {:>10} {:>14} {:>14} {:>14}",
+            "function", "calls", "instructions", "inclusive", "exclusive"
+        )?;
+        for function in functions {
+            writeln!(
+                f,
+                "{:<12} {:>10} {:>14} {:>14?} {:>14?}",
+                function_name(function.entry),
+                function.calls,
+                function.instructions,
+                function.inclusive,
+                function.exclusive
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<24} {:>14}", "opcode", "executions")?;
+        for (opcode, count) in self.opcode_counts() {
+            writeln!(f, "{:<24} {:>14}", format!("{:?}", opcode), count)?;
+        }
+        Ok(())
+    }
+}
+
+fn function_name(function: usize) -> String {
+    format!("fn@{}", function)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profiler;
+    use crate::R;
+    use crate::executor::ext::VmExecutorExt;
+    use crate::executor::interpreted::implimentation::VmInterpretedExecutor;
+    use crate::executor::interpreted::opcode_impl::all::*;
+    use crate::opcodes::OpCode;
+
+    fn call_program() -> Vec<u8> {
+        [
+            CallFunctionInstruction::encode((30u64,)), // 0..10
+            CallFunctionInstruction::encode((30u64,)), // 10..20
+            HaltInstruction::encode((0,)),             // 20..30
+            // function at offset 30
+            IncrementU8Instruction::encode((R!(1), 1u8)),
+            CallFunctionInstruction::encode((46u64,)), // 34..44
+            ReturnInstruction::encode(()),             // 44..46
+            // function at offset 46
+            ReturnInstruction::encode(()),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn counts_instructions_and_functions() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&call_program()).unwrap();
+        vm.set_profiler(Some(Profiler::new()));
+        assert_eq!(vm.execute_processeded_bytecode(&bc).unwrap(), 0);
+
+        let profiler = vm.take_profiler().unwrap();
+        assert_eq!(profiler.instruction_counts(), [1, 1, 1, 2, 2, 2, 2]);
+
+        let opcodes = profiler.opcode_counts();
+        assert!(opcodes.contains(&(OpCode::CallFunction, 4)));
+        assert!(opcodes.contains(&(OpCode::Return, 4)));
+        assert!(opcodes.contains(&(OpCode::Halt, 1)));
+
+        let functions: Vec<(usize, u64, u64)> = profiler
+            .functions()
+            .iter()
+            .map(|function| (function.entry, function.calls, function.instructions))
+            .collect();
+        assert_eq!(functions, [(0, 1, 3), (3, 2, 6), (6, 2, 2)]);
+
+        let main = &profiler.functions()[0];
+        let callee = &profiler.functions()[1];
+        assert!(main.inclusive >= callee.inclusive);
+        assert!(callee.inclusive >= callee.exclusive);
+    }
+
+    #[test]
+    fn writes_folded_stacks() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&call_program()).unwrap();
+        vm.set_profiler(Some(Profiler::new()));
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let mut out = Vec::new();
+        vm.profiler().unwrap().write_folded(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "fn@0 3\nfn@0;fn@3 6\nfn@0;fn@3;fn@6 2\n"
+        );
+    }
+
+    #[test]
+    fn recursion_counted_once_inclusive() {
+        let program = [
+            LoadImmediateU64Instruction::encode((R!(1), 3u64)), // 0..11
+            CallFunctionInstruction::encode((31u64,)),          // 11..21
+            HaltInstruction::encode((0,)),                      // 21..31
+            // function at offset 31, calls itself while R1 > 0
+            JumpIfFalseInstruction::encode((63u64, R!(1))), // 31..42
+            DecrementU64Instruction::encode((R!(1), 1u64)), // 42..53
+            CallFunctionInstruction::encode((31u64,)),      // 53..63
+            ReturnInstruction::encode(()),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_profiler(Some(Profiler::new()));
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let profiler = vm.profiler().unwrap();
+        let function = &profiler.functions()[1];
+        assert_eq!(function.calls, 4);
+        assert!(function.inclusive <= profiler.functions()[0].inclusive);
+
+        let mut out = Vec::new();
+        profiler.write_folded(&mut out).unwrap();
+        let folded = String::from_utf8(out).unwrap();
+        assert!(folded.contains("fn@0;fn@3;fn@3;fn@3;fn@3 2\n"));
+    }
+
+    #[test]
+    fn resumed_run_carries_on_in_open_calls() {
+        let program = [
+            CallFunctionInstruction::encode((20u64,)), // 0..10
+            HaltInstruction::encode((0,)),             // 10..20
+            // function at offset 20
+            IncrementU8Instruction::encode((R!(1), 1u8)),
+            IncrementU8Instruction::encode((R!(1), 1u8)),
+            ReturnInstruction::encode(()),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_profiler(Some(Profiler::new()));
+        vm.set_fuel(Some(2));
+
+        vm.execute_processeded_bytecode(&bc).unwrap_err();
+        vm.add_fuel(10);
+        assert_eq!(vm.resume_processeded_bytecode(&bc).unwrap(), 0);
+
+        let profiler = vm.profiler().unwrap();
+        let functions: Vec<(usize, u64, u64)> = profiler
+            .functions()
+            .iter()
+            .map(|function| (function.entry, function.calls, function.instructions))
+            .collect();
+        assert_eq!(functions, [(0, 1, 2), (2, 1, 3)]);
+
+        let mut out = Vec::new();
+        profiler.write_folded(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "fn@0 2\nfn@0;fn@2 3\n");
+    }
+
+    #[test]
+    fn counts_failing_instructions() {
+        let program = [
+            IncrementU8Instruction::encode((R!(1), 1u8)),
+            DivideU8Instruction::encode((R!(2), R!(1), R!(3))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_profiler(Some(Profiler::new()));
+        vm.set_trap_on_error(true);
+
+        vm.execute_processeded_bytecode(&bc).unwrap_err();
+
+        assert_eq!(vm.profiler().unwrap().instruction_counts(), [1, 1]);
+    }
+}
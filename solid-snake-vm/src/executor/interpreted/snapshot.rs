@@ -0,0 +1,195 @@
+use std::{error::Error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use super::implimentation::HeapLimits;
+
+/// Bumped whenever the layout of `VmSnapshot` changes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The state of a paused executor, see `VmInterpretedExecutor::snapshot`. Running threads and
+/// channels are not part of it, and neither are settings like fuel or trap-on-error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VmSnapshot {
+    pub version: u32,
+    /// Fingerprint of the bytecode the executor was running, a snapshot only restores onto the same program.
+    pub program_fingerprint: u64,
+    pub frames: Vec<FrameSnapshot>,
+    pub program_counter: usize,
+    pub error_code: i64,
+    pub prev_error_code: i64,
+    pub heap: HeapSnapshot,
+    pub constants: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameSnapshot {
+    pub return_address: u64,
+    pub registers: Vec<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeapSnapshot {
+    pub sections: Vec<SectionSnapshot>,
+    pub generations: Vec<u32>,
+    pub alloc_pcs: Vec<Option<usize>>,
+    pub freed_sections: Vec<usize>,
+    pub limits: HeapLimits,
+    pub peak_bytes: usize,
+    pub total_allocations: usize,
+    pub total_frees: usize,
+}
+
+/// Shared segments are restored as new segments holding the same bytes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SectionSnapshot {
+    Private(Vec<u8>),
+    Shared(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    UnsupportedVersion(u32),
+    ProgramMismatch { expected: u64, found: u64 },
+    Malformed(&'static str),
+}
+
+impl Error for SnapshotError {}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported snapshot version {}, this VM supports {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::ProgramMismatch { expected, found } => write!(
+                f,
+                "Snapshot was taken from program {:#018x}, the loaded program is {:#018x}",
+                found, expected
+            ),
+            SnapshotError::Malformed(reason) => write!(f, "Malformed snapshot: {}", reason),
+        }
+    }
+}
+
+/// FNV-1a, so fingerprints stay the same across builds and machines.
+pub fn program_fingerprint(bytecode: &[u8]) -> u64 {
+    bytecode.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotError;
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::{
+        RegisterFileExt, VmHeapExt, VmInterpretedExecutor,
+    };
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn counting_program() -> Vec<u8> {
+        [
+            LoadImmediateU64Instruction::encode((R!(1), 8u64)), // 0..11
+            AllocateInstruction::encode((R!(0), R!(1))),        // 11..15
+            IncrementU8Instruction::encode((R!(2), 1u8)),       // 15..19
+            StoreIndirectWithOffsetU8Instruction::encode((R!(0), R!(2), R!(3))), // 19..24
+            LessThanU8Instruction::encode((R!(4), R!(2), R!(5))), // 24..28
+            JumpIfInstruction::encode((15u64, R!(4))),          // 28..39
+            LoadIndirectWithOffsetU8Instruction::encode((R!(6), R!(0), R!(3))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn resumes_from_restored_snapshot() {
+        let program = counting_program();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.registers_mut().set_register_value(R!(5), 10u8).unwrap();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_fuel(Some(12));
+
+        let err = vm.execute_processeded_bytecode(&bc).unwrap_err();
+        assert!(matches!(err, VmExecutionError::OutOfFuel { .. }));
+
+        let json = serde_json::to_string(&vm.snapshot()).unwrap();
+
+        let mut restored = VmInterpretedExecutor::new(None);
+        let bc = restored.preprocess_bytecode(&program).unwrap();
+        restored
+            .restore(serde_json::from_str(&json).unwrap())
+            .unwrap();
+        assert_eq!(
+            restored.get_program_counter().unwrap(),
+            vm.get_program_counter().unwrap()
+        );
+
+        assert_eq!(restored.resume_processeded_bytecode(&bc).unwrap(), 0);
+        let last: u8 = restored.registers().get_register_value(R!(6)).unwrap();
+        assert_eq!(last, 10);
+        assert_eq!(restored.heap().stats().live_bytes, 8);
+    }
+
+    #[test]
+    fn rejects_other_program() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.preprocess_bytecode(&counting_program()).unwrap();
+        let snapshot = vm.snapshot();
+
+        let mut other = VmInterpretedExecutor::new(None);
+        other
+            .preprocess_bytecode(&HaltInstruction::encode((0,)))
+            .unwrap();
+
+        assert!(matches!(
+            other.restore(snapshot),
+            Err(VmExecutionError::InvalidSnapshot(
+                SnapshotError::ProgramMismatch { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_version() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.preprocess_bytecode(&counting_program()).unwrap();
+        let mut snapshot = vm.snapshot();
+        snapshot.version += 1;
+
+        assert!(matches!(
+            vm.restore(snapshot),
+            Err(VmExecutionError::InvalidSnapshot(
+                SnapshotError::UnsupportedVersion(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_free_list() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.preprocess_bytecode(&counting_program()).unwrap();
+        vm.heap_mut().alloc(8).unwrap();
+        let freed = vm.heap_mut().alloc(8).unwrap();
+        vm.heap_mut().free(freed).unwrap();
+        let snapshot = vm.snapshot();
+
+        let mut twice = snapshot.clone();
+        twice.heap.freed_sections.push(1);
+        let mut live = snapshot.clone();
+        live.heap.freed_sections.push(0);
+
+        for snapshot in [twice, live] {
+            assert!(matches!(
+                vm.restore(snapshot),
+                Err(VmExecutionError::InvalidSnapshot(SnapshotError::Malformed(
+                    _
+                )))
+            ));
+        }
+        vm.restore(snapshot).unwrap();
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use serde::{Serialize, Serializer};
+use serde_json::json;
+
+use super::implimentation::RegisterFile;
+use crate::docs::ArgType;
+use crate::executor::ext::VmExecutionError;
+use crate::opcodes::{DecodedInstruction, OpCode};
+
+/// One executed instruction. Register operands are listed in argument order, `after` is read from
+/// the frame that is current once the instruction finished, so for a call it is the callee's frame.
+/// `error` is set when the instruction stopped execution, e.g. with a fault or a watchpoint hit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TraceRecord {
+    pub pc: usize,
+    #[serde(serialize_with = "opcode_name")]
+    pub opcode: OpCode,
+    pub depth: usize,
+    pub registers: Vec<RegisterTrace>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RegisterTrace {
+    pub register: u8,
+    pub before: u64,
+    pub after: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallEventKind {
+    Call,
+    Return,
+}
+
+/// A call into or a return from the function starting at instruction index `function`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CallEvent {
+    pub kind: CallEventKind,
+    pub function: usize,
+    /// The `CallFunction` or `Return` instruction.
+    pub pc: usize,
+    pub depth: usize,
+    pub timestamp_us: f64,
+}
+
+/// Records what an executor runs, see `VmInterpretedExecutor::set_tracer`. Calls and returns are
+/// always kept, every executed instruction is also written as a JSON line when an output is given.
+pub struct Tracer {
+    json_lines: Option<Box<dyn Write + Send>>,
+    calls: Vec<CallEvent>,
+    open_calls: Vec<usize>,
+    register_offsets: HashMap<u16, Box<[usize]>>,
+    start: Instant,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self {
+            json_lines: None,
+            calls: Vec::new(),
+            open_calls: Vec::new(),
+            register_offsets: HashMap::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Writes one `TraceRecord` per line to `out`. Every instruction is a write, so wrap files in a `BufWriter`.
+    pub fn with_json_lines(mut self, out: impl Write + Send + 'static) -> Self {
+        self.json_lines = Some(Box::new(out));
+        self
+    }
+
+    pub fn calls(&self) -> &[CallEvent] {
+        self.calls.as_slice()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.json_lines.as_mut() {
+            Some(out) => out.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the calls and returns in the Chrome `trace_event` format. Calls that never returned,
+    /// e.g. because the program halted inside them, are closed at the last recorded timestamp.
+    pub fn write_chrome_trace(&self, out: impl Write) -> io::Result<()> {
+        let mut events = Vec::with_capacity(self.calls.len() + self.open_calls.len());
+        for event in &self.calls {
+            let phase = match event.kind {
+                CallEventKind::Call => "B",
+                CallEventKind::Return => "E",
+            };
+            events.push(json!({
+                "name": function_name(event.function),
+                "cat": "call",
+                "ph": phase,
+                "ts": event.timestamp_us,
+                "pid": 0,
+                "tid": 0,
+                "args": { "pc": event.pc, "depth": event.depth },
+            }));
+        }
+
+        let end = self.calls.last().map_or(0.0, |event| event.timestamp_us);
+        for &function in self.open_calls.iter().rev() {
+            events.push(json!({
+                "name": function_name(function),
+                "cat": "call",
+                "ph": "E",
+                "ts": end,
+                "pid": 0,
+                "tid": 0,
+            }));
+        }
+
+        serde_json::to_writer(out, &json!({ "traceEvents": events }))?;
+        Ok(())
+    }
+
+    /// Captures the register operands of `decoded` before it runs.
+    pub(crate) fn begin(
+        &mut self,
+        pc: usize,
+        decoded: &DecodedInstruction,
+        depth: usize,
+        registers: &RegisterFile,
+    ) -> TraceRecord {
+        let opcode = decoded.opcode();
+        let offsets = self
+            .register_offsets
+            .entry(opcode as u16)
+            .or_insert_with(|| {
+                opcode
+                    .get_doc()
+                    .arg_layout()
+                    .filter(|(_, arg)| arg.typ == ArgType::Register)
+                    .map(|(arg_offset, _)| arg_offset)
+                    .collect()
+            });
+
+        let mut operands = Vec::new();
+        if !offsets.is_empty() {
+            let encoded = decoded.encode();
+            let args = &encoded[size_of::<u16>()..];
+            for &offset in offsets.iter() {
+                let register = args[offset];
+                let value = registers
+                    .raw
+                    .get(register as usize)
+                    .copied()
+                    .unwrap_or_default();
+                operands.push(RegisterTrace {
+                    register,
+                    before: value,
+                    after: value,
+                });
+            }
+        }
+
+        TraceRecord {
+            pc,
+            opcode,
+            depth,
+            registers: operands,
+            error: None,
+        }
+    }
+
+    /// Completes a record from `begin` once the instruction ran and writes it out. A call or
+    /// return that failed is not recorded as one.
+    pub(crate) fn finish(
+        &mut self,
+        mut record: TraceRecord,
+        decoded: &DecodedInstruction,
+        registers: &RegisterFile,
+        error: Option<&VmExecutionError>,
+    ) -> io::Result<()> {
+        for operand in record.registers.iter_mut() {
+            operand.after = registers
+                .raw
+                .get(operand.register as usize)
+                .copied()
+                .unwrap_or_default();
+        }
+        record.error = error.map(ToString::to_string);
+
+        match decoded {
+            _ if error.is_some() => {}
+            DecodedInstruction::CallFunction((target,)) => {
+                let function = *target as usize;
+                self.open_calls.push(function);
+                self.push_call_event(CallEventKind::Call, function, &record);
+            }
+            DecodedInstruction::Return(_) => {
+                // A tracer set inside a function sees returns without a recorded call.
+                if let Some(function) = self.open_calls.pop() {
+                    self.push_call_event(CallEventKind::Return, function, &record);
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(out) = self.json_lines.as_mut() {
+            serde_json::to_writer(&mut *out, &record)?;
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn push_call_event(&mut self, kind: CallEventKind, function: usize, record: &TraceRecord) {
+        self.calls.push(CallEvent {
+            kind,
+            function,
+            pc: record.pc,
+            depth: record.depth,
+            timestamp_us: self.start.elapsed().as_secs_f64() * 1_000_000.0,
+        });
+    }
+}
+
+fn function_name(function: usize) -> String {
+    format!("fn@{}", function)
+}
+
+fn opcode_name<S: Serializer>(opcode: &OpCode, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{:?}", opcode))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::Value;
+
+    use super::{CallEventKind, Tracer};
+    use crate::R;
+    use crate::executor::ext::VmExecutorExt;
+    use crate::executor::interpreted::implimentation::VmInterpretedExecutor;
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn call_program() -> Vec<u8> {
+        [
+            LoadImmediateU64Instruction::encode((R!(1), 4u64)), // 0..11
+            CallFunctionInstruction::encode((31u64,)),          // 11..21
+            HaltInstruction::encode((0,)),                      // 21..31
+            // function at offset 31
+            IncrementU8Instruction::encode((R!(1), 2u8)),
+            ReturnInstruction::encode(()),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn writes_json_lines() {
+        let buffer = SharedBuffer::default();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&call_program()).unwrap();
+        vm.set_tracer(Some(Tracer::new().with_json_lines(buffer.clone())));
+
+        assert_eq!(vm.execute_processeded_bytecode(&bc).unwrap(), 0);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let opcodes: Vec<&str> = records
+            .iter()
+            .map(|record| record["opcode"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                "LoadImmediateU64",
+                "CallFunction",
+                "IncrementU8",
+                "Return",
+                "Halt"
+            ]
+        );
+
+        let increment = &records[2];
+        assert_eq!(increment["pc"], 3);
+        assert_eq!(increment["depth"], 1);
+        assert_eq!(increment["registers"][0]["register"], 1);
+        assert_eq!(increment["registers"][0]["before"], 4);
+        assert_eq!(increment["registers"][0]["after"], 6);
+    }
+
+    #[test]
+    fn exports_chrome_trace() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&call_program()).unwrap();
+        vm.set_tracer(Some(Tracer::new()));
+        vm.execute_processeded_bytecode(&bc).unwrap();
+
+        let tracer = vm.take_tracer().unwrap();
+        let kinds: Vec<CallEventKind> = tracer.calls().iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [CallEventKind::Call, CallEventKind::Return]);
+
+        let mut out = Vec::new();
+        tracer.write_chrome_trace(&mut out).unwrap();
+        let trace: Value = serde_json::from_slice(&out).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["ph"], "B");
+        assert_eq!(events[0]["name"], "fn@3");
+        assert_eq!(events[0]["args"]["pc"], 1);
+        assert_eq!(events[1]["ph"], "E");
+        assert_eq!(events[1]["name"], "fn@3");
+    }
+
+    #[test]
+    fn closes_unreturned_calls() {
+        let program = [
+            CallFunctionInstruction::encode((10u64,)), // 0..10
+            HaltInstruction::encode((5,)),
+        ]
+        .concat();
+        let mut vm = VmInterpretedExecutor::new(None);
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.set_tracer(Some(Tracer::new()));
+        assert_eq!(vm.execute_processeded_bytecode(&bc).unwrap(), 5);
+
+        let mut out = Vec::new();
+        vm.tracer().unwrap().write_chrome_trace(&mut out).unwrap();
+        let trace: Value = serde_json::from_slice(&out).unwrap();
+        let phases: Vec<&str> = trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["ph"].as_str().unwrap())
+            .collect();
+        assert_eq!(phases, ["B", "E"]);
+    }
+
+    #[test]
+    fn records_instructions_that_stop_execution() {
+        use crate::executor::interpreted::debugger::{WatchKind, Watchpoint};
+        use crate::executor::interpreted::implimentation::RegisterFileExt;
+
+        let program = [
+            AllocateInstruction::encode((R!(0), R!(1))),
+            MemSetInstruction::encode((R!(0), R!(5), R!(1))),
+            DeallocateInstruction::encode((R!(3),)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+        let buffer = SharedBuffer::default();
+        let mut vm = VmInterpretedExecutor::new(None);
+        vm.registers_mut().set_register_value(R!(1), 4u64).unwrap();
+        vm.registers_mut().set_register_value(R!(3), 7u64).unwrap();
+        let bc = vm.preprocess_bytecode(&program).unwrap();
+        vm.watchpoints_mut().add(Watchpoint {
+            section: 0,
+            range: 0..4,
+            kind: WatchKind::Write,
+        });
+        vm.set_tracer(Some(Tracer::new().with_json_lines(buffer.clone())));
+
+        vm.execute_processeded_bytecode(&bc).unwrap_err();
+        vm.resume_processeded_bytecode(&bc).unwrap_err();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let records: Vec<Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].get("error").is_none());
+        assert_eq!(records[1]["opcode"], "MemSet");
+        assert!(
+            records[1]["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Watchpoint")
+        );
+        assert_eq!(records[2]["opcode"], "Deallocate");
+        assert_eq!(records[2]["error"], "Null Pointer Exception");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use crate::docs::ArgType;
+use crate::executor::interpreted::implimentation::MAX_REGISTERS;
+use crate::opcodes::OpCode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationErrorKind {
+    EmptyProgram,
+    UnknownOpcode(u16),
+    TruncatedInstruction { expected: usize, available: usize },
+    InvalidBranchTarget(u64),
+    InvalidRegister(u8),
+    FallsOffEnd,
+}
+
+/// A problem found in a bytecode stream, `offset` is the byte offset of the offending instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    pub offset: usize,
+    pub kind: VerificationErrorKind,
+}
+
+impl Error for VerificationError {}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {}: ", self.offset)?;
+        match &self.kind {
+            VerificationErrorKind::EmptyProgram => write!(f, "program is empty"),
+            VerificationErrorKind::UnknownOpcode(opcode) => write!(f, "unknown opcode {}", opcode),
+            VerificationErrorKind::TruncatedInstruction {
+                expected,
+                available,
+            } => write!(
+                f,
+                "instruction needs {} bytes but only {} remain",
+                expected, available
+            ),
+            VerificationErrorKind::InvalidBranchTarget(target) => write!(
+                f,
+                "branch target {} is not the start of an instruction",
+                target
+            ),
+            VerificationErrorKind::InvalidRegister(reg) => write!(
+                f,
+                "register R{} is out of range (max R{})",
+                reg,
+                MAX_REGISTERS - 1
+            ),
+            VerificationErrorKind::FallsOffEnd => {
+                write!(f, "execution can run past the last instruction")
+            }
+        }
+    }
+}
+
+/// Checks that a bytecode stream can be decoded and executed without leaving the program.
+/// All problems are collected, decoding stops at the first instruction whose length can't be known.
+pub fn verify_bytecode(bytecode: &[u8]) -> Result<(), Vec<VerificationError>> {
+    let mut errors = Vec::new();
+
+    if bytecode.is_empty() {
+        errors.push(VerificationError {
+            offset: 0,
+            kind: VerificationErrorKind::EmptyProgram,
+        });
+        return Err(errors);
+    }
+
+    let mut register_offsets: HashMap<u16, Vec<usize>> = HashMap::new();
+    let mut instruction_starts = HashSet::new();
+    let mut branches = Vec::new();
+    let mut last_instruction = None;
+    let mut offset = 0;
+
+    while offset < bytecode.len() {
+        let remaining = &bytecode[offset..];
+        if remaining.len() < size_of::<u16>() {
+            errors.push(VerificationError {
+                offset,
+                kind: VerificationErrorKind::TruncatedInstruction {
+                    expected: size_of::<u16>(),
+                    available: remaining.len(),
+                },
+            });
+            break;
+        }
+
+        let raw_opcode = u16::from_be_bytes([remaining[0], remaining[1]]);
+        let Ok(opcode) = OpCode::try_from(raw_opcode) else {
+            errors.push(VerificationError {
+                offset,
+                kind: VerificationErrorKind::UnknownOpcode(raw_opcode),
+            });
+            break;
+        };
+
+        let instr_size = opcode.instr_size();
+        if remaining.len() < instr_size {
+            errors.push(VerificationError {
+                offset,
+                kind: VerificationErrorKind::TruncatedInstruction {
+                    expected: instr_size,
+                    available: remaining.len(),
+                },
+            });
+            break;
+        }
+
+        let args = &remaining[size_of::<u16>()..instr_size];
+
+        let registers = register_offsets.entry(raw_opcode).or_insert_with(|| {
+            opcode
+                .get_doc()
+                .arg_layout()
+                .filter(|(_, arg)| arg.typ == ArgType::Register)
+                .map(|(arg_offset, _)| arg_offset)
+                .collect()
+        });
+        for &arg_offset in registers.iter() {
+            let reg = args[arg_offset];
+            if reg as usize >= MAX_REGISTERS {
+                errors.push(VerificationError {
+                    offset,
+                    kind: VerificationErrorKind::InvalidRegister(reg),
+                });
+            }
+        }
+
+        if opcode.has_branch_target() {
+            let target = u64::from_be_bytes(args[..size_of::<u64>()].try_into().unwrap());
+            branches.push((offset, target));
+        }
+
+        instruction_starts.insert(offset);
+        last_instruction = Some((offset, opcode));
+        offset += instr_size;
+    }
+
+    for (offset, target) in branches {
+        let is_instruction_start =
+            usize::try_from(target).is_ok_and(|target| instruction_starts.contains(&target));
+        if !is_instruction_start {
+            errors.push(VerificationError {
+                offset,
+                kind: VerificationErrorKind::InvalidBranchTarget(target),
+            });
+        }
+    }
+
+    // Only meaningful if the whole stream decoded
+    if offset == bytecode.len()
+        && let Some((last_offset, last_opcode)) = last_instruction
+        && !last_opcode.is_terminator()
+    {
+        errors.push(VerificationError {
+            offset: last_offset,
+            kind: VerificationErrorKind::FallsOffEnd,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        errors.sort_by_key(|error| error.offset);
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::R;
+    use crate::executor::ext::{VmExecutionError, VmExecutorExt};
+    use crate::executor::interpreted::implimentation::VmInterpretedExecutor;
+    use crate::executor::interpreted::opcode_impl::all::*;
+
+    fn kinds(bytecode: &[u8]) -> Vec<VerificationErrorKind> {
+        verify_bytecode(bytecode)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.kind)
+            .collect()
+    }
+
+    #[test]
+    fn verify_accepts_valid_program() {
+        let bc = [
+            LoadImmediateI64Instruction::encode((R!(1), 5i64)),
+            JumpIfInstruction::encode((22u64, R!(1))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        assert_eq!(verify_bytecode(&bc), Ok(()));
+    }
+
+    #[test]
+    fn verify_rejects_empty_program() {
+        assert_eq!(kinds(&[]), vec![VerificationErrorKind::EmptyProgram]);
+    }
+
+    #[test]
+    fn verify_rejects_unknown_opcode() {
+        let bc = [0xFF, 0xFF, 0, 0];
+        assert_eq!(
+            kinds(&bc),
+            vec![VerificationErrorKind::UnknownOpcode(0xFFFF)]
+        );
+    }
+
+    #[test]
+    fn verify_rejects_truncated_instruction() {
+        let mut bc = LoadImmediateI64Instruction::encode((R!(1), 5i64));
+        bc.truncate(6);
+        assert_eq!(
+            kinds(&bc),
+            vec![VerificationErrorKind::TruncatedInstruction {
+                expected: 11,
+                available: 6
+            }]
+        );
+
+        let mut bc = HaltInstruction::encode((0,));
+        bc.push(0);
+        assert_eq!(
+            kinds(&bc),
+            vec![VerificationErrorKind::TruncatedInstruction {
+                expected: 2,
+                available: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn verify_rejects_mid_instruction_branch() {
+        let bc = [
+            LoadImmediateI64Instruction::encode((R!(1), 5i64)),
+            JumpInstruction::encode((3u64,)),
+        ]
+        .concat();
+
+        assert_eq!(
+            kinds(&bc),
+            vec![VerificationErrorKind::InvalidBranchTarget(3)]
+        );
+    }
+
+    #[test]
+    fn verify_rejects_out_of_range_call() {
+        let bc = [
+            CallFunctionInstruction::encode((u64::MAX,)),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        assert_eq!(
+            kinds(&bc),
+            vec![VerificationErrorKind::InvalidBranchTarget(u64::MAX)]
+        );
+    }
+
+    #[test]
+    fn verify_rejects_out_of_range_register() {
+        let bc = [
+            AddI64Instruction::encode((R!(200), R!(1), R!(128))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        assert_eq!(
+            kinds(&bc),
+            vec![
+                VerificationErrorKind::InvalidRegister(200),
+                VerificationErrorKind::InvalidRegister(128)
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_rejects_falling_off_end() {
+        let bc = LoadImmediateI64Instruction::encode((R!(1), 5i64));
+        assert_eq!(kinds(&bc), vec![VerificationErrorKind::FallsOffEnd]);
+    }
+
+    #[test]
+    fn verify_reports_error_offsets() {
+        let bc = [
+            HaltInstruction::encode((0,)),
+            AddI64Instruction::encode((R!(1), R!(130), R!(1))),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        let errors = verify_bytecode(&bc).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].offset, 10);
+    }
+
+    #[test]
+    fn preprocess_rejects_malformed_bytecode() {
+        let mut vm = VmInterpretedExecutor::new(None);
+        let mut bc = LoadImmediateI64Instruction::encode((R!(1), 5i64));
+        bc.truncate(4);
+
+        let result = vm.preprocess_bytecode(&bc);
+        assert!(matches!(
+            result,
+            Err(VmExecutionError::VerificationFailed(errors)) if errors.len() == 1
+        ));
+    }
+
+    #[test]
+    fn preprocess_never_panics_on_prefixes() {
+        let bc = [
+            LoadImmediateI64Instruction::encode((R!(1), 5i64)),
+            JumpIfInstruction::encode((0u64, R!(1))),
+            CallFunctionInstruction::encode((0u64,)),
+            ReturnInstruction::encode(()),
+            HaltInstruction::encode((0,)),
+        ]
+        .concat();
+
+        let mut vm = VmInterpretedExecutor::new(None);
+        for len in 0..bc.len() {
+            let _ = vm.preprocess_bytecode(&bc[..len]);
+        }
+        assert!(vm.preprocess_bytecode(&bc).is_ok());
+    }
+}
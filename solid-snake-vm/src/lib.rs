@@ -1,5 +1,7 @@
 pub mod asm_internal;
+pub mod bytecode_module;
 pub mod bytecode_parser;
+pub mod disassembler;
 pub mod executor;
 pub mod docs;
 pub mod opcodes;
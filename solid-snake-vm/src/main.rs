@@ -6,6 +6,8 @@ use executor::{
     interpreted::{
         implimentation::{RegisterFileExt, VmInterpretedExecutor},
         opcode_decoder::RegisterType,
+        profile::Profiler,
+        trace::Tracer,
     },
 };
 
@@ -13,10 +15,13 @@ use opcodes::OpCode;
 use rustc_version_runtime::version;
 use sysinfo::{System, get_current_pid};
 
+use crate::bytecode_module::BytecodeModule;
 use crate::docs::Docs;
 
 mod asm_internal;
+mod bytecode_module;
 mod bytecode_parser;
+mod disassembler;
 mod docs;
 mod executor;
 mod opcodes;
@@ -24,6 +29,63 @@ mod opcodes;
 fn main() -> Result<(), Box<dyn Error>> {
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().collect();
+    if let [_, flag, module_path] = args.as_slice()
+        && flag == "--disassemble"
+    {
+        let module = BytecodeModule::read_from_file(module_path)?;
+        print!("{}", disassembler::disassemble(&module.code)?);
+        return Ok(());
+    }
+
+    if let [_, flag, source_path, module_path] = args.as_slice()
+        && flag == "--assemble"
+    {
+        let assembled = match bytecode_parser::assemble_file(source_path) {
+            Ok(assembled) => assembled,
+            Err(errors) => {
+                let source = std::fs::read_to_string(source_path).unwrap_or_default();
+                bytecode_parser::report_asm_errors(&errors, source_path, &source);
+                std::process::exit(1);
+            }
+        };
+        assembled.into_module().write_to_file(module_path)?;
+        return Ok(());
+    }
+
+    if let [_, flag, module_path] = args.as_slice()
+        && flag == "--trap"
+    {
+        let exit_code = run_module(module_path, true, false)?;
+        std::process::exit(exit_code as i32);
+    }
+
+    if let [_, flag, module_path] = args.as_slice()
+        && flag == "--leaks"
+    {
+        let exit_code = run_module(module_path, false, true)?;
+        std::process::exit(exit_code as i32);
+    }
+
+    if let [_, flag, module_path, folded_path] = args.as_slice()
+        && flag == "--profile"
+    {
+        let exit_code = profile_module(module_path, folded_path)?;
+        std::process::exit(exit_code as i32);
+    }
+
+    if let [_, flag, module_path, trace_path, timeline_path] = args.as_slice()
+        && flag == "--trace"
+    {
+        let exit_code = trace_module(module_path, trace_path, timeline_path)?;
+        std::process::exit(exit_code as i32);
+    }
+
+    if let Some(module_path) = args.get(1) {
+        let exit_code = run_module(module_path, false, false)?;
+        std::process::exit(exit_code as i32);
+    }
+
     let docs = Docs {
         instructions: OpCode::get_docs(),
     };
@@ -91,6 +153,66 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+fn run_module(path: &str, trap_on_error: bool, report_leaks: bool) -> Result<i64, Box<dyn Error>> {
+    let module = BytecodeModule::read_from_file(path)?;
+
+    let mut vm = VmInterpretedExecutor::new(None);
+    vm.set_trap_on_error(trap_on_error);
+    let bc = vm.load_module(&module)?;
+
+    let exit_code = vm.execute_processeded_bytecode(&bc)?;
+    if report_leaks {
+        let stats = vm.heap().stats();
+        eprintln!(
+            "Heap: peak {} bytes, {} allocations, {} frees",
+            stats.peak_bytes, stats.total_allocations, stats.total_frees
+        );
+        let leaks = vm.heap().leak_report();
+        if !leaks.is_empty() {
+            eprint!("{}", leaks);
+        }
+    }
+
+    Ok(exit_code)
+}
+
+/// Runs a module writing every executed instruction as JSON lines to `trace_path`, and its calls
+/// as a Chrome trace to `timeline_path`.
+fn trace_module(path: &str, trace_path: &str, timeline_path: &str) -> Result<i64, Box<dyn Error>> {
+    let module = BytecodeModule::read_from_file(path)?;
+
+    let mut vm = VmInterpretedExecutor::new(None);
+    let bc = vm.load_module(&module)?;
+    let trace = std::io::BufWriter::new(File::create(trace_path)?);
+    vm.set_tracer(Some(Tracer::new().with_json_lines(trace)));
+
+    let result = vm.execute_processeded_bytecode(&bc);
+    if let Some(mut tracer) = vm.take_tracer() {
+        tracer.flush()?;
+        tracer.write_chrome_trace(std::io::BufWriter::new(File::create(timeline_path)?))?;
+    }
+
+    Ok(result?)
+}
+
+/// Runs a module under the counting profiler, printing a summary to stderr and writing folded
+/// stacks for flamegraph tools to `folded_path`.
+fn profile_module(path: &str, folded_path: &str) -> Result<i64, Box<dyn Error>> {
+    let module = BytecodeModule::read_from_file(path)?;
+
+    let mut vm = VmInterpretedExecutor::new(None);
+    let bc = vm.load_module(&module)?;
+    vm.set_profiler(Some(Profiler::new()));
+
+    let result = vm.execute_processeded_bytecode(&bc);
+    if let Some(profiler) = vm.take_profiler() {
+        eprint!("{}", profiler);
+        profiler.write_folded(std::io::BufWriter::new(File::create(folded_path)?))?;
+    }
+
+    Ok(result?)
+}
+
 fn bench_vm_fib(fib_n: i64, n_iter: usize) {
     use std::time::Instant;
 
@@ -114,7 +236,7 @@ Halt 0                        ; R10 = F(80)
 ",
     );
 
-    let (bc, _lined_code) = parse_byte_code_from_txt(&code);
+    let bc = parse_byte_code_from_txt(&code).unwrap().bytecode;
 
     let mut vm = VmInterpretedExecutor::new(None);
 
@@ -176,9 +298,9 @@ fib_base_case:
 "
     );
 
-    let (bc, _lined_code) = parse_byte_code_from_txt(&code);
+    let bc = parse_byte_code_from_txt(&code).unwrap().bytecode;
 
-    // _lined_code.iter().for_each(|(line, idx)| {
+    // lined_code.iter().for_each(|(line, idx)| {
     //     println!("{idx:0>3} : {line}",)
     // });
 
@@ -285,7 +407,8 @@ Deallocate R3
 Halt 0
     ";
 
-    let (bc, _lined_code) = parse_byte_code_from_txt(code);
+    let assembled = parse_byte_code_from_txt(code).unwrap();
+    let bc = assembled.bytecode;
 
     let mut vm = VmInterpretedExecutor::new(None);
 
@@ -323,7 +446,8 @@ Halt 0
 
     let bytecode = asm_!(LoadImmediateI64 R1, -123);
 
-    _lined_code
+    assembled
+        .lined_code
         .iter()
         .for_each(|(line, idx)| println!("{idx:0>3} : {line}",));
 
@@ -270,24 +270,54 @@ pub enum OpCode {
     GreaterThanOrEqualF32 = 508,
     GreaterThanOrEqualF64 = 509,
 
+    // Shared segments are only accessed through atomic instructions
+    AllocateShared = 520,
+
     // Atomic instructions have variants for each type
-    // AtomicSwapU8 = 530,
-    // AtomicSwapU16 = 531,
-    // AtomicSwapU32 = 532,
-    // AtomicSwapU64 = 533,
-    // AtomicSwapI8 = 534,
-    // AtomicSwapI16 = 535,
-    // AtomicSwapI32 = 536,
-    // AtomicSwapI64 = 537,
-
-    // CompareAndSwapU8 = 560,
-    // CompareAndSwapU16 = 561,
-    // CompareAndSwapU32 = 562,
-    // CompareAndSwapU64 = 563,
-    // CompareAndSwapI8 = 564,
-    // CompareAndSwapI16 = 565,
-    // CompareAndSwapI32 = 566,
-    // CompareAndSwapI64 = 567,
+    AtomicSwapU8 = 530,
+    AtomicSwapU16 = 531,
+    AtomicSwapU32 = 532,
+    AtomicSwapU64 = 533,
+    AtomicSwapI8 = 534,
+    AtomicSwapI16 = 535,
+    AtomicSwapI32 = 536,
+    AtomicSwapI64 = 537,
+
+    AtomicLoadU8 = 540,
+    AtomicLoadU16 = 541,
+    AtomicLoadU32 = 542,
+    AtomicLoadU64 = 543,
+    AtomicLoadI8 = 544,
+    AtomicLoadI16 = 545,
+    AtomicLoadI32 = 546,
+    AtomicLoadI64 = 547,
+
+    AtomicStoreU8 = 550,
+    AtomicStoreU16 = 551,
+    AtomicStoreU32 = 552,
+    AtomicStoreU64 = 553,
+    AtomicStoreI8 = 554,
+    AtomicStoreI16 = 555,
+    AtomicStoreI32 = 556,
+    AtomicStoreI64 = 557,
+
+    CompareAndSwapU8 = 560,
+    CompareAndSwapU16 = 561,
+    CompareAndSwapU32 = 562,
+    CompareAndSwapU64 = 563,
+    CompareAndSwapI8 = 564,
+    CompareAndSwapI16 = 565,
+    CompareAndSwapI32 = 566,
+    CompareAndSwapI64 = 567,
+
+    AtomicFetchAddU8 = 570,
+    AtomicFetchAddU16 = 571,
+    AtomicFetchAddU32 = 572,
+    AtomicFetchAddU64 = 573,
+    AtomicFetchAddI8 = 574,
+    AtomicFetchAddI16 = 575,
+    AtomicFetchAddI32 = 576,
+    AtomicFetchAddI64 = 577,
 
     // Call Moves the program counter to target u64 address
     CallFunction = 600,
@@ -311,105 +341,105 @@ pub enum OpCode {
     Halt = 606,
 
     // Must have pretty much all plausible combinations of types, for source and target
-    // ConvertU8ToU16 = 700,
-    // ConvertU8ToU32 = 701,
-    // ConvertU8ToU64 = 702,
-    // ConvertU8ToI8 = 703,
-    // ConvertU8ToI16 = 704,
-    // ConvertU8ToI32 = 705,
-    // ConvertU8ToI64 = 706,
-    // ConvertU8ToF32 = 707,
-    // ConvertU8ToF64 = 708,
-
-    // ConvertU16ToU8 = 710,
-    // ConvertU16ToU32 = 711,
-    // ConvertU16ToU64 = 712,
-    // ConvertU16ToI8 = 713,
-    // ConvertU16ToI16 = 714,
-    // ConvertU16ToI32 = 715,
-    // ConvertU16ToI64 = 716,
-    // ConvertU16ToF32 = 717,
-    // ConvertU16ToF64 = 718,
-
-    // ConvertU32ToU8 = 720,
-    // ConvertU32ToU16 = 721,
-    // ConvertU32ToU64 = 722,
-    // ConvertU32ToI8 = 723,
-    // ConvertU32ToI16 = 724,
-    // ConvertU32ToI32 = 725,
-    // ConvertU32ToI64 = 726,
-    // ConvertU32ToF32 = 727,
-    // ConvertU32ToF64 = 728,
-
-    // ConvertU64ToU8 = 730,
-    // ConvertU64ToU16 = 731,
-    // ConvertU64ToU32 = 732,
-    // ConvertU64ToI8 = 733,
-    // ConvertU64ToI16 = 734,
-    // ConvertU64ToI32 = 735,
-    // ConvertU64ToI64 = 736,
-    // ConvertU64ToF32 = 737,
-    // ConvertU64ToF64 = 738,
-
-    // ConvertI8ToU8 = 740,
-    // ConvertI8ToU16 = 741,
-    // ConvertI8ToU32 = 742,
-    // ConvertI8ToU64 = 743,
-    // ConvertI8ToI16 = 744,
-    // ConvertI8ToI32 = 745,
-    // ConvertI8ToI64 = 746,
-    // ConvertI8ToF32 = 747,
-    // ConvertI8ToF64 = 748,
-
-    // ConvertI16ToU8 = 750,
-    // ConvertI16ToU16 = 751,
-    // ConvertI16ToU32 = 752,
-    // ConvertI16ToU64 = 753,
-    // ConvertI16ToI8 = 754,
-    // ConvertI16ToI32 = 755,
-    // ConvertI16ToI64 = 756,
-    // ConvertI16ToF32 = 757,
-    // ConvertI16ToF64 = 758,
-
-    // ConvertI32ToU8 = 760,
-    // ConvertI32ToU16 = 761,
-    // ConvertI32ToU32 = 762,
-    // ConvertI32ToU64 = 763,
-    // ConvertI32ToI8 = 764,
-    // ConvertI32ToI16 = 765,
-    // ConvertI32ToI64 = 766,
-    // ConvertI32ToF32 = 767,
-    // ConvertI32ToF64 = 768,
-
-    // ConvertI64ToU8 = 770,
-    // ConvertI64ToU16 = 771,
-    // ConvertI64ToU32 = 772,
-    // ConvertI64ToU64 = 773,
-    // ConvertI64ToI8 = 774,
-    // ConvertI64ToI16 = 775,
-    // ConvertI64ToI32 = 776,
-    // ConvertI64ToF32 = 777,
-    // ConvertI64ToF64 = 778,
-
-    // ConvertF32ToU8 = 780,
-    // ConvertF32ToU16 = 781,
-    // ConvertF32ToU32 = 782,
-    // ConvertF32ToU64 = 783,
-    // ConvertF32ToI8 = 784,
-    // ConvertF32ToI16 = 785,
-    // ConvertF32ToI32 = 786,
-    // ConvertF32ToI64 = 787,
-    // ConvertF32ToF64 = 788,
-
-    // ConvertF64ToU8 = 790,
-    // ConvertF64ToU16 = 791,
-    // ConvertF64ToU32 = 792,
-    // ConvertF64ToU64 = 793,
-    // ConvertF64ToI8 = 794,
-    // ConvertF64ToI16 = 795,
-    // ConvertF64ToI32 = 796,
-    // ConvertF64ToI64 = 797,
-    // ConvertF64ToF32 = 798,
+    ConvertU8ToU16 = 700,
+    ConvertU8ToU32 = 701,
+    ConvertU8ToU64 = 702,
+    ConvertU8ToI8 = 703,
+    ConvertU8ToI16 = 704,
+    ConvertU8ToI32 = 705,
+    ConvertU8ToI64 = 706,
+    ConvertU8ToF32 = 707,
+    ConvertU8ToF64 = 708,
+
+    ConvertU16ToU8 = 710,
+    ConvertU16ToU32 = 711,
+    ConvertU16ToU64 = 712,
+    ConvertU16ToI8 = 713,
+    ConvertU16ToI16 = 714,
+    ConvertU16ToI32 = 715,
+    ConvertU16ToI64 = 716,
+    ConvertU16ToF32 = 717,
+    ConvertU16ToF64 = 718,
+
+    ConvertU32ToU8 = 720,
+    ConvertU32ToU16 = 721,
+    ConvertU32ToU64 = 722,
+    ConvertU32ToI8 = 723,
+    ConvertU32ToI16 = 724,
+    ConvertU32ToI32 = 725,
+    ConvertU32ToI64 = 726,
+    ConvertU32ToF32 = 727,
+    ConvertU32ToF64 = 728,
+
+    ConvertU64ToU8 = 730,
+    ConvertU64ToU16 = 731,
+    ConvertU64ToU32 = 732,
+    ConvertU64ToI8 = 733,
+    ConvertU64ToI16 = 734,
+    ConvertU64ToI32 = 735,
+    ConvertU64ToI64 = 736,
+    ConvertU64ToF32 = 737,
+    ConvertU64ToF64 = 738,
+
+    ConvertI8ToU8 = 740,
+    ConvertI8ToU16 = 741,
+    ConvertI8ToU32 = 742,
+    ConvertI8ToU64 = 743,
+    ConvertI8ToI16 = 744,
+    ConvertI8ToI32 = 745,
+    ConvertI8ToI64 = 746,
+    ConvertI8ToF32 = 747,
+    ConvertI8ToF64 = 748,
+
+    ConvertI16ToU8 = 750,
+    ConvertI16ToU16 = 751,
+    ConvertI16ToU32 = 752,
+    ConvertI16ToU64 = 753,
+    ConvertI16ToI8 = 754,
+    ConvertI16ToI32 = 755,
+    ConvertI16ToI64 = 756,
+    ConvertI16ToF32 = 757,
+    ConvertI16ToF64 = 758,
+
+    ConvertI32ToU8 = 760,
+    ConvertI32ToU16 = 761,
+    ConvertI32ToU32 = 762,
+    ConvertI32ToU64 = 763,
+    ConvertI32ToI8 = 764,
+    ConvertI32ToI16 = 765,
+    ConvertI32ToI64 = 766,
+    ConvertI32ToF32 = 767,
+    ConvertI32ToF64 = 768,
+
+    ConvertI64ToU8 = 770,
+    ConvertI64ToU16 = 771,
+    ConvertI64ToU32 = 772,
+    ConvertI64ToU64 = 773,
+    ConvertI64ToI8 = 774,
+    ConvertI64ToI16 = 775,
+    ConvertI64ToI32 = 776,
+    ConvertI64ToF32 = 777,
+    ConvertI64ToF64 = 778,
+
+    ConvertF32ToU8 = 780,
+    ConvertF32ToU16 = 781,
+    ConvertF32ToU32 = 782,
+    ConvertF32ToU64 = 783,
+    ConvertF32ToI8 = 784,
+    ConvertF32ToI16 = 785,
+    ConvertF32ToI32 = 786,
+    ConvertF32ToI64 = 787,
+    ConvertF32ToF64 = 788,
+
+    ConvertF64ToU8 = 790,
+    ConvertF64ToU16 = 791,
+    ConvertF64ToU32 = 792,
+    ConvertF64ToU64 = 793,
+    ConvertF64ToI8 = 794,
+    ConvertF64ToI16 = 795,
+    ConvertF64ToI32 = 796,
+    ConvertF64ToI64 = 797,
+    ConvertF64ToF32 = 798,
     MoveU8 = 821,
     MoveU16 = 822,
     MoveU32 = 823,
@@ -506,11 +536,36 @@ pub enum OpCode {
     StoreConstantArray = 1030,
 
     // A way to check if errors happened with specific codes, versions for using register and constant
-    // ErrorFlagEqualIndirect = 1100,
-    // ErrorFlagEqualImmediate = 1101,
+    ErrorFlagEqualIndirect = 1100,
+    ErrorFlagEqualImmediate = 1101,
     // Jumps to given code address if error register is set. Does not clear the register, versions for using register and constant
-    // JumpIfErrorIndirect = 1120,
-    // JumpIfErrorImmediate = 1121,
+    JumpIfErrorIndirect = 1120,
+    JumpIfErrorImmediate = 1121,
+
+    // Runs a function on a new executor and thread, which only shares the shared segments
+    Spawn = 1200,
+    // Waits for a spawned thread and gets its R0
+    Join = 1201,
+
+    // Channels move whole heap sections between executors
+    ChannelCreate = 1210,
+    ChannelSend = 1211,
+    ChannelRecv = 1212,
+    ChannelTryRecv = 1213,
+    ChannelClose = 1214,
+
+    // Calls a host function registered on the executor, arguments in R1..R3 and the result in R0
+    CallNative = 1300,
+
+    // Host I/O, each needs a capability granted by the embedder
+    ReadLine = 1400,
+    ArgCount = 1401,
+    ArgGet = 1402,
+    FileOpen = 1410,
+    FileRead = 1411,
+    FileWrite = 1412,
+    FileClose = 1413,
+
     DebugPrintU8 = 2000,
     DebugPrintU16 = 2001,
     DebugPrintU32 = 2002,
@@ -524,11 +579,65 @@ pub enum OpCode {
     DebugPrintRaw = 2010,
     // TODO : import for bytecode modules
     // TODO : import for ffi extensions
-    // TODO : threading (fork, join?)
 }
 
 impl OpCode {
     pub fn get_docs() -> Vec<crate::docs::InstructionDocsEntry> {
         Self::variant_iter().map(Self::get_doc).collect::<Vec<_>>()
     }
+
+    /// Instructions whose first argument is a bytecode address. Preprocessing rewrites it to an instruction index.
+    pub fn has_branch_target(self) -> bool {
+        matches!(
+            self,
+            OpCode::Jump
+                | OpCode::JumpIf
+                | OpCode::JumpIfFalse
+                | OpCode::JumpIfErrorIndirect
+                | OpCode::JumpIfErrorImmediate
+                | OpCode::CallFunction
+                | OpCode::Spawn
+        )
+    }
+
+    /// Instructions after which execution never continues with the next instruction.
+    pub fn is_terminator(self) -> bool {
+        matches!(self, OpCode::Jump | OpCode::Return | OpCode::Halt)
+    }
+}
+
+impl DecodedInstruction {
+    pub fn branch_target(&self) -> Option<u64> {
+        match *self {
+            DecodedInstruction::Jump((target,))
+            | DecodedInstruction::JumpIf((target, _))
+            | DecodedInstruction::JumpIfFalse((target, _))
+            | DecodedInstruction::JumpIfErrorIndirect((target, _))
+            | DecodedInstruction::JumpIfErrorImmediate((target, _))
+            | DecodedInstruction::CallFunction((target,))
+            | DecodedInstruction::Spawn((target, _, _)) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn with_branch_target(self, target: u64) -> Self {
+        match self {
+            DecodedInstruction::Jump(_) => DecodedInstruction::Jump((target,)),
+            DecodedInstruction::JumpIf((_, reg)) => DecodedInstruction::JumpIf((target, reg)),
+            DecodedInstruction::JumpIfFalse((_, reg)) => {
+                DecodedInstruction::JumpIfFalse((target, reg))
+            }
+            DecodedInstruction::JumpIfErrorIndirect((_, code)) => {
+                DecodedInstruction::JumpIfErrorIndirect((target, code))
+            }
+            DecodedInstruction::JumpIfErrorImmediate((_, code)) => {
+                DecodedInstruction::JumpIfErrorImmediate((target, code))
+            }
+            DecodedInstruction::CallFunction(_) => DecodedInstruction::CallFunction((target,)),
+            DecodedInstruction::Spawn((_, arg, handle)) => {
+                DecodedInstruction::Spawn((target, arg, handle))
+            }
+            _ => self,
+        }
+    }
 }
\ No newline at end of file